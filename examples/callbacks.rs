@@ -0,0 +1,24 @@
+//! Runs the viewer with a render and an update callback, logging from both.
+//! Run with `RUST_LOG=info` to see them fire.
+
+use wgpu_tutorial::{run_with, Error, StateBuilder};
+use winit::event::VirtualKeyCode;
+
+fn main() -> Result<(), Error> {
+  let mut frames = 0u64;
+  let builder = StateBuilder::new()
+    .msaa(4)
+    .render_callback(move |pass, _state| {
+      pass.insert_debug_marker("render callback");
+      frames += 1;
+      if frames.is_multiple_of(600) {
+        log::info!("Rendered {} frames", frames);
+      }
+    })
+    .update_callback(|state, dt| {
+      if state.was_key_pressed(VirtualKeyCode::Space) {
+        log::info!("Space pressed, {:.1} ms since the last update", dt * 1000.0);
+      }
+    });
+  pollster::block_on(run_with(builder))
+}
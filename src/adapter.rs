@@ -0,0 +1,117 @@
+use crate::config::AlphaMode;
+
+/// Every adapter wgpu finds on `backends`, in the order `--adapter` picks
+/// from.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn enumerate(instance: &wgpu::Instance, backends: wgpu::Backends) -> Vec<wgpu::Adapter> {
+  instance.enumerate_adapters(backends).collect()
+}
+
+/// `info` on one line, e.g. `NVIDIA GeForce RTX 3070 (Vulkan, DiscreteGpu)`.
+pub fn describe(info: &wgpu::AdapterInfo) -> String {
+  format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type)
+}
+
+/// Prints the adapters on `backends` with the index that picks each.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn print_list(instance: &wgpu::Instance, backends: wgpu::Backends) {
+  let adapters = enumerate(instance, backends);
+  if adapters.is_empty() {
+    println!("No adapters found");
+  }
+  for (index, adapter) in adapters.iter().enumerate() {
+    println!("{}: {}", index, describe(&adapter.get_info()));
+  }
+}
+
+/// The adapter at `index` of `enumerate`, unless there isn't one or it can't
+/// draw to `surface`. The adapters found are logged either way, so the log
+/// shows what else there was to pick from.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn by_index(
+  instance: &wgpu::Instance,
+  backends: wgpu::Backends,
+  index: usize,
+  surface: Option<&wgpu::Surface>,
+) -> Option<wgpu::Adapter> {
+  let adapters = enumerate(instance, backends);
+  for (index, adapter) in adapters.iter().enumerate() {
+    log::info!("Adapter {}: {}", index, describe(&adapter.get_info()));
+  }
+  let count = adapters.len();
+  match adapters.into_iter().nth(index) {
+    Some(adapter) if surface.is_none_or(|surface| adapter.is_surface_supported(surface)) => {
+      Some(adapter)
+    }
+    Some(adapter) => {
+      log::warn!(
+        "Adapter {} ({}) can't draw to the window, picking one instead",
+        index,
+        describe(&adapter.get_info())
+      );
+      None
+    }
+    None => {
+      log::warn!(
+        "There's no adapter {}, only {} were found, picking one instead",
+        index,
+        count
+      );
+      None
+    }
+  }
+}
+
+/// Alpha modes the surface can be presented with, the one `Auto` picks
+/// first. wgpu 0.12 can neither ask a surface for its alpha modes nor set
+/// one, so every surface is presented opaque whatever the platform honors.
+pub const SUPPORTED_ALPHA_MODES: [AlphaMode; 1] = [AlphaMode::Opaque];
+
+/// `requested` if the surface supports it, otherwise the first mode it
+/// does, with a warning.
+pub fn alpha_mode(requested: AlphaMode) -> AlphaMode {
+  let fallback = SUPPORTED_ALPHA_MODES[0];
+  match requested {
+    AlphaMode::Auto => fallback,
+    mode if SUPPORTED_ALPHA_MODES.contains(&mode) => mode,
+    mode => {
+      log::warn!(
+        "The surface can't be presented {:?}, using {:?}",
+        mode,
+        fallback
+      );
+      fallback
+    }
+  }
+}
+
+/// Logs what's drawing on one line, the first thing to ask for about a
+/// black screen. `verbose` adds everything the adapter reports.
+pub fn log_capabilities(
+  adapter: &wgpu::Adapter,
+  device: &wgpu::Device,
+  config: &wgpu::SurfaceConfiguration,
+  alpha_mode: AlphaMode,
+  verbose: bool,
+) {
+  let limits = device.limits();
+  log::info!(
+    "Adapter: {}, {:?}, {:?}, {:?} alpha, max texture {}, {} bind groups, {} B uniform bindings",
+    describe(&adapter.get_info()),
+    config.format,
+    config.present_mode,
+    alpha_mode,
+    limits.max_texture_dimension_2d,
+    limits.max_bind_groups,
+    limits.max_uniform_buffer_binding_size
+  );
+  if verbose {
+    log::info!("Adapter info: {:#?}", adapter.get_info());
+    log::info!("Adapter features: {:?}", adapter.features());
+    log::info!("Device limits: {:#?}", limits);
+    log::info!(
+      "Downlevel capabilities: {:#?}",
+      adapter.get_downlevel_properties()
+    );
+  }
+}
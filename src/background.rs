@@ -0,0 +1,215 @@
+use anyhow::*;
+use wgpu::util::DeviceExt;
+
+use crate::texture;
+
+/// How the background image is fit to a window with a different aspect ratio.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackgroundFit {
+  /// Fill the whole window, cropping the image.
+  Cover,
+  /// Show the whole image, leaving bars of the clear color.
+  Contain,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BackgroundUniform {
+  uv_scale: [f32; 2],
+  _padding: [f32; 2],
+}
+
+/// A static image drawn with a fullscreen triangle before the scene.
+pub struct Background {
+  bind_group: wgpu::BindGroup,
+  buffer: wgpu::Buffer,
+  fit: BackgroundFit,
+  format: wgpu::TextureFormat,
+  image_size: (u32, u32),
+  pipeline: wgpu::RenderPipeline,
+  pipeline_layout: wgpu::PipelineLayout,
+}
+
+impl Background {
+  pub fn new(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    config: &wgpu::SurfaceConfiguration,
+    bytes: &[u8],
+    fit: BackgroundFit,
+    sample_count: u32,
+  ) -> Result<Self> {
+    let texture = texture::Texture::from_bytes(device, queue, bytes, "background")?;
+    let image_size = (texture.size.width, texture.size.height);
+
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Background Buffer"),
+      contents: bytemuck::cast_slice(&[Self::uniform(fit, image_size, config)]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let [texture_entry, sampler_entry] = texture::Texture::layout_entries(0);
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[
+        texture_entry,
+        sampler_entry,
+        wgpu::BindGroupLayoutEntry {
+          binding: 2,
+          visibility: wgpu::ShaderStages::VERTEX,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        },
+      ],
+      label: Some("background_bind_group_layout"),
+    });
+
+    let [view_entry, sampler_entry] = texture.bind_group_entries(0);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout: &bind_group_layout,
+      entries: &[
+        view_entry,
+        sampler_entry,
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: buffer.as_entire_binding(),
+        },
+      ],
+      label: Some("background_bind_group"),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Background Pipeline Layout"),
+      bind_group_layouts: &[&bind_group_layout],
+      push_constant_ranges: &[],
+    });
+    let pipeline = Self::create_pipeline(device, &pipeline_layout, config.format, sample_count);
+
+    Ok(Self {
+      bind_group,
+      buffer,
+      fit,
+      format: config.format,
+      image_size,
+      pipeline,
+      pipeline_layout,
+    })
+  }
+
+  fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+  ) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(&wgpu::include_wgsl!("background.wgsl"));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("Background Pipeline"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState {
+        module: &shader,
+        entry_point: "vs_main",
+        buffers: &[],
+      },
+      fragment: Some(wgpu::FragmentState {
+        module: &shader,
+        entry_point: "fs_main",
+        targets: &[wgpu::ColorTargetState {
+          format,
+          blend: Some(wgpu::BlendState::REPLACE),
+          write_mask: wgpu::ColorWrites::ALL,
+        }],
+      }),
+      primitive: wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: None,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: false,
+        conservative: false,
+      },
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState {
+        count: sample_count,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      multiview: None,
+    })
+  }
+
+  /// Rebuilds the pipeline for a scene target with a different multisample
+  /// count.
+  pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+    self.pipeline = Self::create_pipeline(device, &self.pipeline_layout, self.format, sample_count);
+  }
+
+  /// Texture coordinates are scaled about the image center by the ratio of
+  /// the two aspect ratios. Below 1.0 crops the image, above 1.0 leaves bars.
+  fn uniform(
+    fit: BackgroundFit,
+    image_size: (u32, u32),
+    config: &wgpu::SurfaceConfiguration,
+  ) -> BackgroundUniform {
+    let window_aspect = config.width as f32 / config.height as f32;
+    let image_aspect = image_size.0 as f32 / image_size.1 as f32;
+    let x = window_aspect / image_aspect;
+    let y = image_aspect / window_aspect;
+    let uv_scale = match fit {
+      BackgroundFit::Cover => [x.min(1.0), y.min(1.0)],
+      BackgroundFit::Contain => [x.max(1.0), y.max(1.0)],
+    };
+
+    BackgroundUniform {
+      uv_scale,
+      _padding: [0.0; 2],
+    }
+  }
+
+  pub fn resize(&self, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration) {
+    queue.write_buffer(
+      &self.buffer,
+      0,
+      bytemuck::cast_slice(&[Self::uniform(self.fit, self.image_size, config)]),
+    );
+  }
+
+  /// Switches between cover and contain.
+  pub fn toggle_fit(&mut self, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration) {
+    self.fit = match self.fit {
+      BackgroundFit::Cover => BackgroundFit::Contain,
+      BackgroundFit::Contain => BackgroundFit::Cover,
+    };
+    self.resize(queue, config);
+  }
+
+  /// Clears `view` to `clear_color` and draws the image over it. The scene
+  /// pass that follows should load rather than clear.
+  pub fn draw(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    clear_color: wgpu::Color,
+  ) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Background Pass"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Clear(clear_color),
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(&self.pipeline);
+    render_pass.set_bind_group(0, &self.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+  }
+}
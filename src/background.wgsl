@@ -0,0 +1,43 @@
+// Vertex shader
+struct BackgroundUniform {
+    // Scales texture coordinates about the image center to keep its aspect
+    // ratio, see `Background::uv_scale`.
+    uv_scale: vec2<f32>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> background: BackgroundUniform;
+
+struct VertexOutput {
+  [[builtin(position)]] clip_position: vec4<f32>;
+  [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// A single triangle covering the screen, no vertex buffer needed.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.tex_coords = (uv - vec2<f32>(0.5)) * background.uv_scale + vec2<f32>(0.5);
+  return out;
+}
+
+
+// Fragment shader
+[[group(0), binding(0)]]
+var t_background: texture_2d<f32>;
+
+[[group(0), binding(1)]]
+var s_background: sampler;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSampleLevel(t_background, s_background, in.tex_coords, 0.0);
+    // Letterboxing when the whole image is fit inside the window.
+    if (any(in.tex_coords < vec2<f32>(0.0)) || any(in.tex_coords > vec2<f32>(1.0))) {
+      discard;
+    }
+    return color;
+}
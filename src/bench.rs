@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+/// Times a fixed number of frames for `Config::bench_frames`.
+pub struct FrameBench {
+  frames: u32,
+  frame_times: Vec<Duration>,
+  last_frame: instant::Instant,
+}
+
+impl FrameBench {
+  pub fn new(frames: u32) -> Self {
+    Self {
+      frames,
+      frame_times: Vec::with_capacity(frames as usize),
+      last_frame: instant::Instant::now(),
+    }
+  }
+
+  /// Call after every presented frame. Each frame time runs from the end of
+  /// the previous frame (or from `new`), so everything the event loop does in
+  /// between counts. Returns true once all the frames are in.
+  pub fn frame_finished(&mut self) -> bool {
+    let now = instant::Instant::now();
+    self.frame_times.push(now - self.last_frame);
+    self.last_frame = now;
+    self.frame_times.len() >= self.frames as usize
+  }
+
+  /// The mean, median, 99th percentile and total of the frame times.
+  pub fn summary(&self) -> String {
+    let mut sorted = self.frame_times.clone();
+    sorted.sort();
+    let count = sorted.len().max(1);
+    let total: Duration = sorted.iter().sum();
+    let median = match sorted.len() {
+      0 => Duration::ZERO,
+      len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2,
+      len => sorted[len / 2],
+    };
+    // The smallest frame time at least 99% of frames are no slower than.
+    let p99 = sorted
+      .get((sorted.len() * 99).div_ceil(100).max(1) - 1)
+      .copied()
+      .unwrap_or_default();
+
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    format!(
+      "{} frames: mean {:.3} ms, median {:.3} ms, p99 {:.3} ms, total {:.3} ms",
+      sorted.len(),
+      ms(total) / count as f64,
+      ms(median),
+      ms(p99),
+      ms(total),
+    )
+  }
+}
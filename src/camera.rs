@@ -0,0 +1,176 @@
+use cgmath::prelude::*;
+use wgpu::util::DeviceExt;
+
+use crate::frustum;
+
+/// Flips wgpu's 0..1 clip space depth so that z becomes 1 - z.
+#[rustfmt::skip]
+pub const REVERSE_Z_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+pub struct Camera {
+  pub eye: cgmath::Point3<f32>,
+  pub target: cgmath::Point3<f32>,
+  pub up: cgmath::Vector3<f32>,
+  pub aspect: f32,
+  pub fovy: f32,
+  pub znear: f32,
+  pub zfar: f32,
+  /// Map the near plane to depth 1.0 and the far plane to 0.0.
+  pub reverse_z: bool,
+}
+
+/// The part of a `Camera` that gets saved, where it is and where it looks.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CameraPose {
+  eye: [f32; 3],
+  target: [f32; 3],
+  up: [f32; 3],
+}
+
+impl Camera {
+  /// Serializes the eye, target and up vector as JSON.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let pose = CameraPose {
+      eye: self.eye.into(),
+      target: self.target.into(),
+      up: self.up.into(),
+    };
+    serde_json::to_vec_pretty(&pose).expect("Camera poses always serialize")
+  }
+
+  /// A camera placed where `to_bytes` saved one, keeping the projection of
+  /// `lens` since the window may have changed size since.
+  pub fn from_bytes(bytes: &[u8], lens: &Camera) -> anyhow::Result<Self> {
+    let pose: CameraPose = serde_json::from_slice(bytes)?;
+    Ok(Self {
+      eye: pose.eye.into(),
+      target: pose.target.into(),
+      up: pose.up.into(),
+      ..*lens
+    })
+  }
+
+  /// The planes bounding what the camera sees, see `frustum::planes`.
+  pub fn frustum_planes(&self) -> [[f32; 4]; 6] {
+    frustum::planes(&self.build_view_projection_matrix())
+  }
+
+  pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+    // 1.
+    let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+    // 2.
+    let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+
+    // 3.
+    let proj = OPENGL_TO_WGPU_MATRIX * proj;
+    if self.reverse_z {
+      REVERSE_Z_MATRIX * proj * view
+    } else {
+      proj * view
+    }
+  }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+  // A vec4 rather than a vec3 to keep the uniform's 16 byte alignment.
+  view_position: [f32; 4],
+  // We can't use cgmath with bytemuck directly so convert the Matrix4 into a 4x4 f32 array
+  view_proj: [[f32; 4]; 4],
+}
+
+impl CameraUniform {
+  fn new() -> Self {
+    Self {
+      view_position: [0.0; 4],
+      view_proj: cgmath::Matrix4::identity().into(),
+    }
+  }
+
+  fn update_view_proj(&mut self, camera: &Camera) {
+    self.view_position = camera.eye.to_homogeneous().into();
+    self.view_proj = camera.build_view_projection_matrix().into()
+  }
+}
+
+/// A camera's matrices in a uniform buffer, and the bind group shaders read
+/// them through.
+pub struct CameraBinding {
+  pub bind_group: wgpu::BindGroup,
+  buffer: wgpu::Buffer,
+  /// What's in `buffer`.
+  uniform: CameraUniform,
+}
+
+impl CameraBinding {
+  /// The layout entry of the uniform, visible to both shader stages.
+  pub fn layout_entry() -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+      binding: 0,
+      visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+      ty: wgpu::BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: false,
+        min_binding_size: None,
+      },
+      count: None,
+    }
+  }
+
+  pub fn new(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera: &Camera,
+    label: &str,
+  ) -> Self {
+    let mut uniform = CameraUniform::new();
+    uniform.update_view_proj(camera);
+
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some(&format!("{} Buffer", label)),
+      contents: bytemuck::cast_slice(&[uniform]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some(&format!("{} Bind Group", label)),
+      layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: buffer.as_entire_binding(),
+      }],
+    });
+
+    Self {
+      bind_group,
+      buffer,
+      uniform,
+    }
+  }
+
+  /// Uploads the matrices of `camera`, unless they're what was uploaded
+  /// last. `camera`'s fields are public and change all over, so this compares
+  /// rather than tracking every change.
+  pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+    let mut uniform = self.uniform;
+    uniform.update_view_proj(camera);
+    if uniform == self.uniform {
+      return;
+    }
+    self.uniform = uniform;
+    queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+  }
+}
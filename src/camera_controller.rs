@@ -1,23 +1,41 @@
 use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
 
+use crate::gamepad::GamepadInput;
 use crate::Camera;
 
+/// Mouse motion per second that a fully deflected look stick stands for.
+const GAMEPAD_LOOK_SPEED: f32 = 480.0;
+
+/// Keeps mouse look from pitching the eye over the target's poles, where the
+/// view flips.
+const MAX_PITCH_COS: f32 = 0.99;
+
 pub struct CameraController {
+  /// Units the camera moves per second.
   pub speed: f32,
+  /// Radians the camera orbits by per unit of raw mouse motion, which is
+  /// roughly a pixel on most platforms.
+  pub sensitivity: f32,
   pub is_forward_pressed: bool,
   pub is_backward_pressed: bool,
   pub is_left_pressed: bool,
   pub is_right_pressed: bool,
+  /// Mouse motion since the last update.
+  mouse_delta: (f32, f32),
+  gamepad: GamepadInput,
 }
 
 impl CameraController {
-  pub fn new(speed: f32) -> Self {
+  pub fn new(speed: f32, sensitivity: f32) -> Self {
     Self {
       speed,
+      sensitivity,
       is_forward_pressed: false,
       is_backward_pressed: false,
       is_left_pressed: false,
       is_right_pressed: false,
+      mouse_delta: (0.0, 0.0),
+      gamepad: GamepadInput::default(),
     }
   }
 
@@ -57,19 +75,82 @@ impl CameraController {
     }
   }
 
-  pub fn update_camera(&self, camera: &mut Camera) {
-    use cgmath::InnerSpace;
+  /// Orbits the camera around its target on the next update, `dx` to the
+  /// side and `dy` up and down.
+  pub fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+    self.mouse_delta.0 += dx as f32;
+    self.mouse_delta.1 += dy as f32;
+  }
+
+  /// Moves the camera by what the gamepad does on every update, until the
+  /// next call.
+  #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+  pub fn set_gamepad(&mut self, input: GamepadInput) {
+    self.gamepad = input;
+  }
+
+  /// Whether any movement key is held, the mouse moved or a stick is off
+  /// center, so the camera changes on the next update.
+  pub fn is_moving(&self) -> bool {
+    self.mouse_delta != (0.0, 0.0)
+      || !self.gamepad.is_centered()
+      || self.is_forward_pressed
+      || self.is_backward_pressed
+      || self.is_left_pressed
+      || self.is_right_pressed
+  }
+
+  /// Moves `camera` by what happened since the last update, `dt` seconds
+  /// ago. Mouse motion is a distance already, so only the keys and sticks
+  /// scale with `dt`.
+  pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
+    use cgmath::{InnerSpace, Rotation3};
+
+    let (dx, dy) = std::mem::take(&mut self.mouse_delta);
+    // Mouse motion is y down, the stick's y up.
+    let (dx, dy) = (
+      dx + self.gamepad.look.0 * GAMEPAD_LOOK_SPEED * dt,
+      dy - self.gamepad.look.1 * GAMEPAD_LOOK_SPEED * dt,
+    );
+    if (dx, dy) != (0.0, 0.0) {
+      let up = camera.up.normalize();
+      let offset = camera.eye - camera.target;
+      let right = (-offset).cross(up).normalize();
+      let yaw = cgmath::Quaternion::from_axis_angle(up, cgmath::Rad(-dx * self.sensitivity));
+      let pitch = cgmath::Quaternion::from_axis_angle(right, cgmath::Rad(-dy * self.sensitivity));
+      let pitched = yaw * pitch * offset;
+      camera.eye = if pitched.normalize().dot(up).abs() < MAX_PITCH_COS {
+        camera.target + pitched
+      } else {
+        camera.target + yaw * offset
+      };
+    }
+
+    // How far to move, -1 to 1, from the keys and the stick.
+    let amount =
+      |positive: bool, negative: bool, stick: f32| (positive as i8 - negative as i8) as f32 + stick;
+    let forward_amount = amount(
+      self.is_forward_pressed,
+      self.is_backward_pressed,
+      self.gamepad.movement.1,
+    )
+    .clamp(-1.0, 1.0);
+    let right_amount = amount(
+      self.is_right_pressed,
+      self.is_left_pressed,
+      self.gamepad.movement.0,
+    )
+    .clamp(-1.0, 1.0);
+
+    let step = self.speed * dt;
     let forward = camera.target - camera.eye;
     let forward_norm = forward.normalize();
     let forward_mag = forward.magnitude();
 
     // Prevents glitching when camera gets too close to the
     // center of the scene.
-    if self.is_forward_pressed && forward_mag > self.speed {
-      camera.eye += forward_norm * self.speed;
-    }
-    if self.is_backward_pressed {
-      camera.eye -= forward_norm * self.speed;
+    if forward_amount < 0.0 || forward_mag > step * forward_amount {
+      camera.eye += forward_norm * step * forward_amount;
     }
 
     let right = forward_norm.cross(camera.up);
@@ -78,14 +159,12 @@ impl CameraController {
     let forward = camera.target - camera.eye;
     let forward_mag = forward.magnitude();
 
-    if self.is_right_pressed {
+    if right_amount != 0.0 {
       // Rescale the distance between the target and eye so
       // that it doesn't change. The eye therefore still
       // lies on the circle made by the target and eye.
-      camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
-    }
-    if self.is_left_pressed {
-      camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+      camera.eye =
+        camera.target - (forward + right * step * right_amount).normalize() * forward_mag;
     }
   }
 }
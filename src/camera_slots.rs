@@ -0,0 +1,43 @@
+use anyhow::*;
+
+fn key(slot: u32) -> String {
+  format!("camera-{}.json", slot)
+}
+
+/// Stores a saved camera in `slot`, as a file in the working directory or in
+/// the browser's local storage.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(slot: u32, bytes: &[u8]) -> Result<()> {
+  std::fs::write(key(slot), bytes)?;
+  Ok(())
+}
+
+/// Reads back what `save` stored in `slot`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(slot: u32) -> Result<Vec<u8>> {
+  std::fs::read(key(slot)).with_context(|| format!("No camera saved in slot {}", slot))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage> {
+  web_sys::window()
+    .and_then(|window| window.local_storage().ok().flatten())
+    .context("Local storage isn't available")
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(slot: u32, bytes: &[u8]) -> Result<()> {
+  let value = std::str::from_utf8(bytes)?;
+  local_storage()?
+    .set_item(&key(slot), value)
+    .map_err(|e| anyhow!("Couldn't write to local storage: {:?}", e))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(slot: u32) -> Result<Vec<u8>> {
+  local_storage()?
+    .get_item(&key(slot))
+    .map_err(|e| anyhow!("Couldn't read from local storage: {:?}", e))?
+    .map(String::into_bytes)
+    .with_context(|| format!("No camera saved in slot {}", slot))
+}
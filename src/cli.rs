@@ -0,0 +1,219 @@
+use clap::{ArgAction, Parser, ValueEnum};
+
+use crate::config::{AlphaMode, ColorSpace, Config};
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Backend {
+  All,
+  Primary,
+  Vulkan,
+  Metal,
+  Dx12,
+  Dx11,
+  Gl,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Power {
+  Low,
+  High,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Space {
+  Srgb,
+  Linear,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Alpha {
+  Auto,
+  Opaque,
+  PreMultiplied,
+  PostMultiplied,
+}
+
+/// Options that can be set at launch, anything not given keeps the value
+/// from `config.toml` or the `Config` default.
+#[derive(Parser)]
+#[command(about = "A small wgpu scene viewer")]
+struct Args {
+  /// Initial window width in physical pixels
+  #[arg(long, requires = "height")]
+  width: Option<u32>,
+  /// Initial window height in physical pixels
+  #[arg(long, requires = "width")]
+  height: Option<u32>,
+  /// Graphics API to render with
+  #[arg(long, value_enum)]
+  backend: Option<Backend>,
+  /// Wait for vertical sync when presenting
+  #[arg(long, action = ArgAction::Set, value_name = "BOOL")]
+  vsync: Option<bool>,
+  /// Without vsync, present frames immediately (may tear) rather than at
+  /// the next refresh
+  #[arg(long, action = ArgAction::Set, value_name = "BOOL")]
+  tearing: Option<bool>,
+  /// Multisample count of the scene, 1, 2, 4 or 8, lowered to what the
+  /// adapter supports
+  #[arg(long, value_parser = parse_sample_count, value_name = "SAMPLES")]
+  msaa: Option<u32>,
+  /// Anisotropic filtering of textures, 1 (off), 2, 4, 8 or 16
+  #[arg(long, value_parser = parse_anisotropy, value_name = "LEVEL")]
+  anisotropy: Option<u8>,
+  /// Units per second the camera moves at
+  #[arg(long, value_name = "SPEED")]
+  camera_speed: Option<f32>,
+  /// Radians mouse look turns by per pixel of mouse motion
+  #[arg(long, value_name = "RADIANS")]
+  mouse_sensitivity: Option<f32>,
+  /// Degrees per second the turntable (T) spins the skinned model by
+  #[arg(long, value_name = "DEGREES")]
+  turntable_speed: Option<f32>,
+  /// Whether the surface format encodes to sRGB or stores colors as-is
+  #[arg(long, value_enum)]
+  color_space: Option<Space>,
+  /// How the window is blended with what's behind it, where supported
+  #[arg(long, value_enum)]
+  alpha_mode: Option<Alpha>,
+  /// Prefer the integrated (low) or discrete (high) GPU
+  #[arg(long, value_enum)]
+  power: Option<Power>,
+  /// Use the adapter with this index in --list-adapters, overriding --power
+  #[arg(long, value_name = "INDEX")]
+  adapter: Option<usize>,
+  /// Print the adapters of the --backend and exit
+  #[arg(long)]
+  list_adapters: bool,
+  /// Log all the adapter's features and limits at startup
+  #[arg(long)]
+  verbose_capabilities: bool,
+  /// Record the shadow pass on its own thread
+  #[arg(long)]
+  parallel_encoding: bool,
+  /// glTF (.glb) file with a skinned model to show
+  #[arg(long, value_name = "PATH")]
+  model: Option<String>,
+  /// Wavefront (.obj) file with a static model to show
+  #[arg(long, value_name = "PATH")]
+  obj: Option<String>,
+  /// Background color, e.g. #ff8800 or #ff8800ff
+  #[arg(long, value_parser = parse_hex_color, value_name = "HEX")]
+  clear_color: Option<String>,
+  /// Keep the last SECONDS of frames for F8 to save as a GIF
+  #[arg(long, value_name = "SECONDS")]
+  gif_seconds: Option<f32>,
+  /// Grayscale image to build the ground from
+  #[arg(long, value_name = "PATH")]
+  heightmap: Option<String>,
+}
+
+fn parse_sample_count(value: &str) -> Result<u32, String> {
+  match value.parse() {
+    Ok(samples) if crate::msaa::REQUESTABLE.contains(&samples) => Ok(samples),
+    _ => Err("the sample count has to be 1, 2, 4 or 8".to_string()),
+  }
+}
+
+fn parse_anisotropy(value: &str) -> Result<u8, String> {
+  match value.parse() {
+    Ok(anisotropy @ (1 | 2 | 4 | 8 | 16)) => Ok(anisotropy),
+    _ => Err("the level has to be 1, 2, 4, 8 or 16".to_string()),
+  }
+}
+
+fn parse_hex_color(value: &str) -> Result<String, String> {
+  crate::color::from_hex(value)
+    .map(|_| value.to_string())
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Overrides `config` with whatever the command line sets. Bad arguments
+/// print the usage and exit.
+pub fn apply_args(config: &mut Config) {
+  let args = Args::parse();
+
+  if let (Some(width), Some(height)) = (args.width, args.height) {
+    config.window_size = Some(winit::dpi::PhysicalSize::new(width, height));
+  }
+  if let Some(backend) = args.backend {
+    config.backends = match backend {
+      Backend::All => wgpu::Backends::all(),
+      Backend::Primary => wgpu::Backends::PRIMARY,
+      Backend::Vulkan => wgpu::Backends::VULKAN,
+      Backend::Metal => wgpu::Backends::METAL,
+      Backend::Dx12 => wgpu::Backends::DX12,
+      Backend::Dx11 => wgpu::Backends::DX11,
+      Backend::Gl => wgpu::Backends::GL,
+    };
+  }
+  if args.list_adapters {
+    crate::adapter::print_list(&wgpu::Instance::new(config.backends), config.backends);
+    std::process::exit(0);
+  }
+  if let Some(vsync) = args.vsync {
+    config.vsync = vsync;
+  }
+  if let Some(tearing) = args.tearing {
+    config.tearing = tearing;
+  }
+  if let Some(samples) = args.msaa {
+    config.msaa_samples = samples;
+  }
+  if let Some(anisotropy) = args.anisotropy {
+    config.anisotropy = anisotropy;
+  }
+  if let Some(speed) = args.camera_speed {
+    config.camera_speed = speed;
+  }
+  if let Some(sensitivity) = args.mouse_sensitivity {
+    config.mouse_sensitivity = sensitivity;
+  }
+  if let Some(speed) = args.turntable_speed {
+    config.turntable_speed = speed;
+  }
+  if let Some(color_space) = args.color_space {
+    config.color_space = match color_space {
+      Space::Srgb => ColorSpace::Srgb,
+      Space::Linear => ColorSpace::Linear,
+    };
+  }
+  if let Some(alpha_mode) = args.alpha_mode {
+    config.alpha_mode = match alpha_mode {
+      Alpha::Auto => AlphaMode::Auto,
+      Alpha::Opaque => AlphaMode::Opaque,
+      Alpha::PreMultiplied => AlphaMode::PreMultiplied,
+      Alpha::PostMultiplied => AlphaMode::PostMultiplied,
+    };
+  }
+  if let Some(power) = args.power {
+    config.power_preference = match power {
+      Power::Low => wgpu::PowerPreference::LowPower,
+      Power::High => wgpu::PowerPreference::HighPerformance,
+    };
+  }
+  if args.adapter.is_some() {
+    config.adapter_index = args.adapter;
+  }
+  if args.verbose_capabilities {
+    config.verbose_capabilities = true;
+  }
+  if args.parallel_encoding {
+    config.parallel_encoding = true;
+  }
+  if args.model.is_some() {
+    config.model = args.model;
+  }
+  if args.obj.is_some() {
+    config.obj = args.obj;
+  }
+  if args.clear_color.is_some() {
+    config.clear_color_hex = args.clear_color;
+  }
+  if args.gif_seconds.is_some() {
+    config.gif_seconds = args.gif_seconds;
+  }
+  if args.heightmap.is_some() {
+    config.heightmap = args.heightmap;
+  }
+}
@@ -0,0 +1,169 @@
+use anyhow::{bail, Result};
+
+/// Parses `#rrggbb` or `#rrggbbaa`, with or without the `#`. The components
+/// stay sRGB encoded like the string, see `for_surface`.
+pub fn from_hex(hex: &str) -> Result<wgpu::Color> {
+  let digits = hex.strip_prefix('#').unwrap_or(hex);
+  if !matches!(digits.len(), 6 | 8) {
+    bail!("Expected a color like #rrggbb or #rrggbbaa, got {:?}", hex);
+  }
+  // `from_str_radix` alone would also take a sign.
+  if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+    bail!("{:?} in the color {:?} isn't a hex digit", c, hex);
+  }
+
+  let component = |i: usize| -> Result<f64> {
+    Ok(u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)? as f64 / 255.0)
+  };
+  Ok(wgpu::Color {
+    r: component(0)?,
+    g: component(1)?,
+    b: component(2)?,
+    a: if digits.len() == 8 {
+      component(3)?
+    } else {
+      1.0
+    },
+  })
+}
+
+/// Decodes sRGB encoded `rgb`, which is what images and color pickers use,
+/// into the linear values lighting and blending work with.
+pub fn srgb_to_linear(rgb: [f32; 3]) -> [f32; 3] {
+  rgb.map(|value| {
+    if value <= 0.04045 {
+      value / 12.92
+    } else {
+      ((value + 0.055) / 1.055).powf(2.4)
+    }
+  })
+}
+
+/// The inverse of `srgb_to_linear`.
+#[allow(dead_code)]
+pub fn linear_to_srgb(rgb: [f32; 3]) -> [f32; 3] {
+  rgb.map(|value| {
+    if value <= 0.0031308 {
+      value * 12.92
+    } else {
+      1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+  })
+}
+
+/// Hue, saturation and value to RGB, all of them in [0, 1]. The hue goes from
+/// red over green and blue back to red.
+pub fn hsv_to_rgb([hue, saturation, value]: [f32; 3]) -> [f32; 3] {
+  let channel = |offset: f32| {
+    let k = (hue * 6.0 + offset).rem_euclid(6.0);
+    value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+  };
+  [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+/// The inverse of `hsv_to_rgb`. Grays have a hue of 0, black also a
+/// saturation of 0.
+#[allow(dead_code)]
+pub fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+  let max = r.max(g).max(b);
+  let delta = max - r.min(g).min(b);
+  let sextant = if delta == 0.0 {
+    0.0
+  } else if max == r {
+    ((g - b) / delta).rem_euclid(6.0)
+  } else if max == g {
+    (b - r) / delta + 2.0
+  } else {
+    (r - g) / delta + 4.0
+  };
+  let saturation = if max == 0.0 { 0.0 } else { delta / max };
+  [sextant / 6.0, saturation, max]
+}
+
+/// What to write to a `format` texture for the sRGB encoded `color` to end up
+/// on screen. An sRGB format encodes what it's given, so that has to be the
+/// linear color. A linear one is shown as-is. Alpha is never encoded.
+pub fn for_surface(color: wgpu::Color, format: wgpu::TextureFormat) -> wgpu::Color {
+  if !format.describe().srgb {
+    return color;
+  }
+  let [r, g, b] = srgb_to_linear([color.r as f32, color.g as f32, color.b as f32]);
+  wgpu::Color {
+    r: r as f64,
+    g: g as f64,
+    b: b as f64,
+    a: color.a,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+    for (a, e) in actual.iter().zip(expected) {
+      assert!((a - e).abs() < 1e-4, "{:?} isn't {:?}", actual, expected);
+    }
+  }
+
+  #[test]
+  fn converts_known_srgb_values() {
+    // sRGB and linear, the second pair where the linear segment ends.
+    for (srgb, linear) in [
+      (0.0, 0.0),
+      (0.04045, 0.0031308),
+      (0.2, 0.033105),
+      (0.5, 0.214041),
+      (0.8, 0.603827),
+      (1.0, 1.0),
+    ] {
+      assert_close(srgb_to_linear([srgb; 3]), [linear; 3]);
+      assert_close(linear_to_srgb([linear; 3]), [srgb; 3]);
+    }
+  }
+
+  #[test]
+  fn round_trips_srgb() {
+    for i in 0..=255 {
+      let value = i as f32 / 255.0;
+      assert_close(linear_to_srgb(srgb_to_linear([value; 3])), [value; 3]);
+      assert_close(srgb_to_linear(linear_to_srgb([value; 3])), [value; 3]);
+    }
+  }
+
+  #[test]
+  fn converts_known_hsv_values() {
+    for (hsv, rgb) in [
+      ([0.0, 1.0, 1.0], [1.0, 0.0, 0.0]),
+      ([1.0 / 3.0, 1.0, 1.0], [0.0, 1.0, 0.0]),
+      ([2.0 / 3.0, 1.0, 1.0], [0.0, 0.0, 1.0]),
+      ([1.0 / 6.0, 1.0, 0.5], [0.5, 0.5, 0.0]),
+      ([0.0, 0.0, 0.25], [0.25; 3]),
+      ([0.0, 0.0, 0.0], [0.0; 3]),
+    ] {
+      assert_close(hsv_to_rgb(hsv), rgb);
+      assert_close(rgb_to_hsv(rgb), hsv);
+    }
+  }
+
+  #[test]
+  fn round_trips_hsv() {
+    for hue in 0..12 {
+      let hsv = [hue as f32 / 12.0, 0.75, 0.5];
+      assert_close(rgb_to_hsv(hsv_to_rgb(hsv)), hsv);
+    }
+  }
+
+  #[test]
+  fn parses_hex() {
+    let color = from_hex("#ff8000").unwrap();
+    assert_eq!(
+      [color.r, color.g, color.b, color.a],
+      [1.0, 128.0 / 255.0, 0.0, 1.0]
+    );
+    assert_eq!(from_hex("00000080").unwrap().a, 128.0 / 255.0);
+    for bad in ["#fff", "#ff80000", "+f8000", "#gg8000"] {
+      assert!(from_hex(bad).is_err(), "{}", bad);
+    }
+  }
+}
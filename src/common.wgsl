@@ -0,0 +1,48 @@
+// Declarations shared by shader.wgsl and skinned.wgsl, which include this
+// with `//!include "common.wgsl"`.
+
+struct CameraUniform {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct LightUniform {
+    view_proj: mat4x4<f32>;
+    direction: vec3<f32>;
+    color: vec3<f32>;
+};
+
+[[group(2), binding(0)]]
+var<uniform> light: LightUniform;
+
+[[group(2), binding(1)]]
+var t_shadow: texture_depth_2d;
+
+[[group(2), binding(2)]]
+var s_shadow: sampler_comparison;
+
+// A normal as a color, x, y and z as red, green and blue, each remapped from
+// -1..1 to 0..1. Normals facing up are green, flipped ones stand out as the
+// color opposite their neighbours'.
+fn normal_color(normal: vec3<f32>) -> vec4<f32> {
+  return vec4<f32>(normalize(normal) * 0.5 + 0.5, 1.0);
+}
+
+// Returns 0.0 when fully in shadow and 1.0 when fully lit.
+fn fetch_shadow(light_space_position: vec4<f32>) -> f32 {
+  if (light_space_position.w <= 0.0) {
+    return 1.0;
+  }
+  // Clip space has y up, texture coordinates have y down.
+  let flip_correction = vec2<f32>(0.5, -0.5);
+  let ndc = light_space_position.xyz / light_space_position.w;
+  let shadow_coords = ndc.xy * flip_correction + vec2<f32>(0.5, 0.5);
+  // Anything outside the light's frustum is treated as lit.
+  if (any(shadow_coords < vec2<f32>(0.0)) || any(shadow_coords > vec2<f32>(1.0)) || ndc.z > 1.0) {
+    return 1.0;
+  }
+  return textureSampleCompareLevel(t_shadow, s_shadow, shadow_coords, ndc.z);
+}
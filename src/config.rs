@@ -0,0 +1,248 @@
+use crate::background::BackgroundFit;
+
+/// Whether the surface stores colors gamma encoded (sRGB) or linearly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+  /// The GPU encodes shader output to sRGB on write.
+  Srgb,
+  /// Shader output is written as-is.
+  Linear,
+}
+
+/// How the compositor blends the window with what's behind it, going by the
+/// alpha the scene is drawn with. Where transparency is honored depends on
+/// the platform: browsers do for canvases, Wayland and macOS compositors
+/// generally do, X11 only with a compositing window manager, and Windows
+/// only for swapchains made for DirectComposition.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AlphaMode {
+  /// The first mode the surface supports.
+  Auto,
+  /// Alpha is ignored, the window covers what's behind it.
+  Opaque,
+  /// Colors are already multiplied by their alpha.
+  PreMultiplied,
+  /// The compositor multiplies colors by their alpha.
+  PostMultiplied,
+}
+
+/// When the event loop redraws.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RedrawMode {
+  /// Every time the event loop runs out of events (`ControlFlow::Poll`), like
+  /// a game. Keeps a CPU core busy.
+  Continuous,
+  /// Only after something changed, waiting for events otherwise. While
+  /// something animates the loop wakes itself for each frame with
+  /// `ControlFlow::WaitUntil`.
+  OnDemand,
+}
+
+/// Options the viewer is started with.
+#[derive(Clone)]
+pub struct Config {
+  /// Initial size of the window's contents. The web build sizes the canvas to
+  /// the element it's in instead.
+  pub window_size: Option<winit::dpi::PhysicalSize<u32>>,
+  /// Graphics APIs wgpu may pick an adapter from.
+  pub backends: wgpu::Backends,
+  pub power_preference: wgpu::PowerPreference,
+  /// Features the device is created with, beyond what the viewer needs,
+  /// which is none. Setting up fails on adapters without them.
+  pub features: wgpu::Features,
+  /// Index of the adapter to use among those `--list-adapters` prints, in
+  /// place of the one `power_preference` picks. Ignored if there's no such
+  /// adapter or it can't draw to the window.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub adapter_index: Option<usize>,
+  /// Log everything the adapter reports at startup, not just the summary.
+  pub verbose_capabilities: bool,
+  /// Wait for the display's refresh before presenting a frame.
+  pub vsync: bool,
+  /// Without vsync, present each frame right away, which may tear, rather
+  /// than replacing the one waiting for the next refresh, which doesn't.
+  /// See `present_mode`.
+  pub tearing: bool,
+  /// Multisample count of the scene pass, 1, 2, 4 or 8. Lowered to the
+  /// highest the adapter supports, see `msaa::supported`.
+  pub msaa_samples: u32,
+  /// Anisotropic taps of the image textures' samplers, 1 (off), 2, 4, 8 or
+  /// 16. Lowered to 1 where the adapter can't filter anisotropically. I
+  /// cycles it at runtime.
+  pub anisotropy: u8,
+  /// Shade the scene with its normals as colors instead of lighting it, to
+  /// spot flipped or missing ones. F2 toggles it at runtime.
+  pub show_normals: bool,
+  /// Which faces the scene pipelines cull, `None` for neither, and which
+  /// winding faces the front. U cycles the culled face at runtime, Shift+U
+  /// flips the winding, to find models wound the other way.
+  pub cull_mode: Option<wgpu::Face>,
+  pub front_face: wgpu::FrontFace,
+  /// Units per second WASD and the left stick move the camera by.
+  pub camera_speed: f32,
+  /// Radians mouse look turns the camera by per unit of mouse motion,
+  /// roughly a pixel.
+  pub mouse_sensitivity: f32,
+  /// Degrees per second the skinned model spins around Y by while the
+  /// turntable (T) is on.
+  pub turntable_speed: f32,
+  /// What the scene is cleared to, behind the background image if there is
+  /// one.
+  pub clear_color: wgpu::Color,
+  /// Replaces `clear_color` with a color written as `#rrggbb` or
+  /// `#rrggbbaa`, which looks like the hex code whatever the surface format.
+  pub clear_color_hex: Option<String>,
+  /// glTF file with the skinned model, or `None` for the built in
+  /// `simple-skin.glb`.
+  pub model: Option<String>,
+  /// Wavefront .obj file with a static model to show beside the rest, see
+  /// `model::Model::load`.
+  pub obj: Option<String>,
+  /// Image drawn behind the scene, or `None` to only clear it. The browser
+  /// can't read files, so the web build always uses the embedded
+  /// `background.png`.
+  pub background: Option<String>,
+  pub background_fit: BackgroundFit,
+  /// Grayscale image to raise the ground from, or `None` for a flat ground.
+  /// The web build always uses the embedded `heightmap.png`.
+  pub heightmap: Option<String>,
+  /// Distance between the heightmap's pixels on the ground.
+  pub terrain_spacing: f32,
+  /// How far above the ground a white heightmap pixel is.
+  pub terrain_amplitude: f32,
+  /// Freeze animation and only redraw occasionally while the window is in
+  /// the background.
+  pub pause_when_unfocused: bool,
+  /// Software frame limiter in frames per second, applied on top of whatever
+  /// the present mode does. L toggles it at runtime.
+  pub frame_cap: Option<u32>,
+  /// Fraction of the window resolution the scene is rendered at, between
+  /// 0.25 and 1.0. -/= adjust it at runtime.
+  pub render_scale: f32,
+  /// When set, the render scale drops while frames take longer than this and
+  /// recovers once there is headroom again.
+  pub target_frame_time: Option<std::time::Duration>,
+  /// Surface formats to use, most preferred first. The first supported one
+  /// in `color_space` wins, otherwise any supported one in `color_space`,
+  /// otherwise whatever the surface supports.
+  pub surface_formats: Vec<wgpu::TextureFormat>,
+  pub color_space: ColorSpace,
+  /// Falls back to the first supported mode, see `adapter::alpha_mode`.
+  pub alpha_mode: AlphaMode,
+  /// Value the depth buffer is cleared to and how fragments are tested
+  /// against it. Pair 1.0 with `Less` (the default) or 0.0 with `Greater` for
+  /// reverse-Z, which also flips the camera's projection so near maps to 1.0.
+  /// Reverse-Z spreads float depth precision more evenly with distance.
+  pub depth_clear: f32,
+  pub depth_compare: wgpu::CompareFunction,
+  pub redraw_mode: RedrawMode,
+  /// Draw this many frames as fast as possible, print how long they took and
+  /// exit.
+  pub bench_frames: Option<u32>,
+  /// Advance animations by this much every frame instead of by the time that
+  /// passed, so runs with the same input draw the same frames. Nothing that
+  /// draws a frame reads the clock then.
+  pub fixed_time_step: Option<std::time::Duration>,
+  /// Where screenshots are saved, created when the first one is. The browser
+  /// downloads them instead.
+  pub screenshot_dir: String,
+  /// Keep this many seconds of frames to save as a GIF with F8, or `None`
+  /// to not record any. See `GifRecorder` for what it costs.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub gif_seconds: Option<f32>,
+  #[cfg(not(target_arch = "wasm32"))]
+  pub gif_fps: u32,
+  /// What the width and height of GIF frames are divided by.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub gif_scale: u32,
+  /// Record the shadow pass on a second thread while the rest of the frame
+  /// is recorded, see `State::encode_frame`.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub parallel_encoding: bool,
+  /// File to write every input event to.
+  #[cfg(feature = "record")]
+  pub record_input: Option<String>,
+  /// File of recorded input to play back, at the times it was recorded.
+  #[cfg(feature = "record")]
+  pub replay_input: Option<String>,
+}
+
+impl Config {
+  /// Sets `clear_color` from `hex` as drawn to a `format` surface, keeping
+  /// the old color if `hex` isn't one.
+  pub fn set_clear_color_hex(&mut self, hex: &str, format: wgpu::TextureFormat) {
+    match crate::color::from_hex(hex) {
+      Ok(color) => self.clear_color = crate::color::for_surface(color, format),
+      Err(e) => log::warn!("Ignoring the clear color: {:#}", e),
+    }
+  }
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      window_size: None,
+      backends: wgpu::Backends::all(),
+      power_preference: wgpu::PowerPreference::default(),
+      features: wgpu::Features::empty(),
+      #[cfg(not(target_arch = "wasm32"))]
+      adapter_index: None,
+      verbose_capabilities: false,
+      vsync: true,
+      tearing: true,
+      msaa_samples: 4,
+      anisotropy: 1,
+      show_normals: false,
+      cull_mode: Some(wgpu::Face::Back),
+      front_face: wgpu::FrontFace::Ccw,
+      camera_speed: 12.0,
+      mouse_sensitivity: 0.005,
+      turntable_speed: 30.0,
+      clear_color: wgpu::Color {
+        r: 0.1,
+        g: 0.1,
+        b: 0.1,
+        a: 1.0,
+      },
+      clear_color_hex: None,
+      model: None,
+      obj: None,
+      background: Some("background.png".to_string()),
+      background_fit: BackgroundFit::Cover,
+      heightmap: None,
+      terrain_spacing: 0.08,
+      terrain_amplitude: 0.6,
+      pause_when_unfocused: true,
+      frame_cap: None,
+      render_scale: 1.0,
+      target_frame_time: None,
+      surface_formats: vec![
+        wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        wgpu::TextureFormat::Bgra8Unorm,
+        wgpu::TextureFormat::Rgba8Unorm,
+      ],
+      color_space: ColorSpace::Srgb,
+      alpha_mode: AlphaMode::Auto,
+      depth_clear: 1.0,
+      depth_compare: wgpu::CompareFunction::Less,
+      redraw_mode: RedrawMode::OnDemand,
+      bench_frames: None,
+      fixed_time_step: None,
+      screenshot_dir: "screenshots".to_string(),
+      #[cfg(not(target_arch = "wasm32"))]
+      gif_seconds: None,
+      #[cfg(not(target_arch = "wasm32"))]
+      gif_fps: 10,
+      #[cfg(not(target_arch = "wasm32"))]
+      gif_scale: 4,
+      #[cfg(not(target_arch = "wasm32"))]
+      parallel_encoding: false,
+      #[cfg(feature = "record")]
+      record_input: None,
+      #[cfg(feature = "record")]
+      replay_input: None,
+    }
+  }
+}
@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+
+use crate::config::{ColorSpace, Config};
+
+/// Where the optional config file is read from, relative to the working
+/// directory.
+pub const CONFIG_PATH: &str = "config.toml";
+
+/// The settings `config.toml` may contain. Anything left out keeps its
+/// default, the command line overrides the file.
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+  // Only read at startup.
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  pub model: Option<String>,
+  pub obj: Option<String>,
+  pub background: Option<String>,
+  pub heightmap: Option<String>,
+  /// See `Config::adapter_index`.
+  pub adapter: Option<usize>,
+  /// `"srgb"` or `"linear"`.
+  pub color_space: Option<ColorSpace>,
+  // Can also change while running, see `State::reload_config`.
+  pub vsync: Option<bool>,
+  pub msaa: Option<u32>,
+  pub anisotropy: Option<u8>,
+  pub render_scale: Option<f32>,
+  pub camera_speed: Option<f32>,
+  pub mouse_sensitivity: Option<f32>,
+  pub turntable_speed: Option<f32>,
+  /// Linear RGBA.
+  pub clear_color: Option<[f64; 4]>,
+  /// `#rrggbb` or `#rrggbbaa`, wins over `clear_color`.
+  pub clear_color_hex: Option<String>,
+}
+
+impl ConfigFile {
+  /// Reads `path`, or returns the empty config if there is no such file.
+  pub fn load(path: &str) -> Result<Self> {
+    let contents = match std::fs::read_to_string(path) {
+      Ok(contents) => contents,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+      Err(e) => return Err(e.into()),
+    };
+    toml::from_str(&contents).with_context(|| format!("Couldn't parse {}", path))
+  }
+
+  /// Overrides `config` with everything set in the file.
+  pub fn apply(&self, config: &mut Config) {
+    if let (Some(width), Some(height)) = (self.width, self.height) {
+      config.window_size = Some(winit::dpi::PhysicalSize::new(width, height));
+    }
+    if self.model.is_some() {
+      config.model = self.model.clone();
+    }
+    if self.obj.is_some() {
+      config.obj = self.obj.clone();
+    }
+    if self.background.is_some() {
+      config.background = self.background.clone();
+    }
+    if self.heightmap.is_some() {
+      config.heightmap = self.heightmap.clone();
+    }
+    if self.adapter.is_some() {
+      config.adapter_index = self.adapter;
+    }
+    if let Some(color_space) = self.color_space {
+      config.color_space = color_space;
+    }
+    if let Some(vsync) = self.vsync {
+      config.vsync = vsync;
+    }
+    if let Some(samples) = self.msaa_samples() {
+      config.msaa_samples = samples;
+    }
+    if let Some(anisotropy) = self.anisotropy() {
+      config.anisotropy = anisotropy;
+    }
+    if let Some(render_scale) = self.render_scale {
+      config.render_scale = render_scale;
+    }
+    if let Some(speed) = self.camera_speed {
+      config.camera_speed = speed;
+    }
+    if let Some(sensitivity) = self.mouse_sensitivity {
+      config.mouse_sensitivity = sensitivity;
+    }
+    if let Some(speed) = self.turntable_speed {
+      config.turntable_speed = speed;
+    }
+    if let Some(color) = self.clear_color() {
+      config.clear_color = color;
+    }
+    if self.clear_color_hex.is_some() {
+      config.clear_color_hex = self.clear_color_hex.clone();
+    }
+  }
+
+  /// The MSAA sample count, if it's set to one that can be asked for.
+  /// Whether the adapter supports it is checked later.
+  pub fn msaa_samples(&self) -> Option<u32> {
+    match self.msaa {
+      Some(samples) if crate::msaa::REQUESTABLE.contains(&samples) => Some(samples),
+      Some(samples) => {
+        log::warn!("Ignoring msaa = {}, it has to be 1, 2, 4 or 8", samples);
+        None
+      }
+      None => None,
+    }
+  }
+
+  /// The anisotropic filtering level, if it's set to one wgpu takes.
+  pub fn anisotropy(&self) -> Option<u8> {
+    match self.anisotropy {
+      Some(anisotropy @ (1 | 2 | 4 | 8 | 16)) => Some(anisotropy),
+      Some(anisotropy) => {
+        log::warn!(
+          "Ignoring anisotropy = {}, it has to be 1, 2, 4, 8 or 16",
+          anisotropy
+        );
+        None
+      }
+      None => None,
+    }
+  }
+
+  pub fn clear_color(&self) -> Option<wgpu::Color> {
+    self
+      .clear_color
+      .map(|[r, g, b, a]| wgpu::Color { r, g, b, a })
+  }
+
+  /// Names of the startup-only settings that differ from `previous`.
+  #[cfg(feature = "watch-config")]
+  pub fn restart_only_changes(&self, previous: &Self) -> Vec<&'static str> {
+    let mut changes = Vec::new();
+    if self.width != previous.width {
+      changes.push("width");
+    }
+    if self.height != previous.height {
+      changes.push("height");
+    }
+    if self.model != previous.model {
+      changes.push("model");
+    }
+    if self.obj != previous.obj {
+      changes.push("obj");
+    }
+    if self.background != previous.background {
+      changes.push("background");
+    }
+    if self.heightmap != previous.heightmap {
+      changes.push("heightmap");
+    }
+    if self.adapter != previous.adapter {
+      changes.push("adapter");
+    }
+    if self.color_space != previous.color_space {
+      changes.push("color_space");
+    }
+    changes
+  }
+}
+
+/// Calls `on_change` from a background thread whenever the file at `path` is
+/// written, created or replaced. Watching stops when the watcher is dropped.
+#[cfg(feature = "watch-config")]
+pub fn watch(
+  path: &str,
+  on_change: impl Fn() + Send + 'static,
+) -> Result<notify::RecommendedWatcher> {
+  use notify::Watcher;
+
+  // Editors often save by writing a new file and renaming it over the old
+  // one, which a watch on the file itself would miss.
+  let path = std::path::Path::new(path);
+  let file_name = path
+    .file_name()
+    .context("Config path has no file name")?
+    .to_owned();
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+    _ => std::path::PathBuf::from("."),
+  };
+
+  let mut watcher =
+    notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+      Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+        if event
+          .paths
+          .iter()
+          .any(|path| path.file_name() == Some(file_name.as_os_str()))
+        {
+          on_change();
+        }
+      }
+      Ok(_) => {}
+      Err(e) => log::warn!("Error watching the config file: {:?}", e),
+    })?;
+  watcher.watch(&dir, notify::RecursiveMode::NonRecursive)?;
+
+  Ok(watcher)
+}
@@ -0,0 +1,31 @@
+use cgmath::prelude::*;
+
+use crate::frustum::Frustum;
+use crate::{Instance, Vertex};
+
+/// The distance from the origin to the furthest of `vertices`, so a sphere of
+/// this radius around an instance holds all of it.
+pub fn bounding_radius(vertices: &[Vertex]) -> f32 {
+  vertices
+    .iter()
+    .map(|vertex| cgmath::Vector3::from(vertex.position).magnitude())
+    .fold(0.0, f32::max)
+}
+
+/// The indices of the `instances` whose bounding spheres are at least partly
+/// inside `frustum`.
+///
+/// Instances hidden behind others are still drawn. Skipping those would need
+/// occlusion queries, and while wgpu 0.12 can create an occlusion
+/// `QuerySet`, a render pass has no way to begin one until
+/// `RenderPass::begin_occlusion_query` in wgpu 0.17.
+pub fn visible_instances(frustum: &Frustum, instances: &[Instance], radius: f32) -> Vec<usize> {
+  instances
+    .iter()
+    .enumerate()
+    .filter(|(_, instance)| {
+      frustum.contains_sphere(cgmath::Point3::from_vec(instance.position), radius)
+    })
+    .map(|(index, _)| index)
+    .collect()
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+/// Notices when the device is lost, say to a driver reset, so everything on
+/// it can be created again, see `State::recreate`.
+#[derive(Clone, Default)]
+pub struct DeviceLoss {
+  reason: Arc<Mutex<Option<String>>>,
+}
+
+impl DeviceLoss {
+  /// Watches `device` through its uncaptured error handler. Errors other than
+  /// a loss stay fatal, as they are without a handler.
+  pub fn watch(device: &wgpu::Device) -> Self {
+    let loss = Self::default();
+    let reason = loss.reason.clone();
+    device.on_uncaptured_error(move |error| {
+      if is_loss(&error) {
+        *reason.lock().unwrap() = Some(error.to_string());
+      } else {
+        log::error!("Handling wgpu errors as fatal");
+        panic!("wgpu error: {}", error);
+      }
+    });
+    loss
+  }
+
+  /// Why the device was lost, `None` while it's fine.
+  #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+  pub fn reason(&self) -> Option<String> {
+    self.reason.lock().unwrap().clone()
+  }
+}
+
+/// wgpu 0.12 has no device lost callback. A loss shows up as the validation
+/// error of whichever call ran into it, caused by wgpu-core's
+/// `DeviceError::Lost`, which wgpu doesn't export. So this goes by its
+/// message.
+fn is_loss(error: &wgpu::Error) -> bool {
+  match error {
+    wgpu::Error::Validation { description, .. } => description.contains("device is lost"),
+    wgpu::Error::OutOfMemory { .. } => false,
+  }
+}
@@ -0,0 +1,144 @@
+use std::ops::Range;
+
+/// One indexed draw and all the state it needs.
+pub struct DrawItem<'a> {
+  pub pipeline: &'a wgpu::RenderPipeline,
+  /// Each bound at the group of its index.
+  pub bind_groups: Vec<&'a wgpu::BindGroup>,
+  /// Each bound at the slot of its index.
+  pub vertex_buffers: Vec<wgpu::BufferSlice<'a>>,
+  pub index_buffer: wgpu::BufferSlice<'a>,
+  pub index_format: wgpu::IndexFormat,
+  pub indices: Range<u32>,
+  pub instances: Range<u32>,
+  pub stencil_reference: u32,
+}
+
+impl DrawItem<'_> {
+  /// Orders items with the same pipeline, then the same bind groups, next to
+  /// each other. Objects are compared by address, since wgpu doesn't give
+  /// them ids.
+  fn sort_key(&self) -> (*const wgpu::RenderPipeline, Vec<*const wgpu::BindGroup>) {
+    (
+      self.pipeline,
+      self
+        .bind_groups
+        .iter()
+        .map(|bind_group| *bind_group as *const _)
+        .collect(),
+    )
+  }
+}
+
+/// How often a `DrawQueue` changed the pipeline or a bind group, out of how
+/// often drawing in submission order would have.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DrawStats {
+  pub switches: u32,
+  pub unsorted_switches: u32,
+}
+
+impl DrawStats {
+  pub fn saved(&self) -> u32 {
+    self.unsorted_switches - self.switches
+  }
+}
+
+impl std::ops::AddAssign for DrawStats {
+  fn add_assign(&mut self, other: Self) {
+    self.switches += other.switches;
+    self.unsorted_switches += other.unsorted_switches;
+  }
+}
+
+/// What's currently bound in a render pass.
+#[derive(Default)]
+struct Bound {
+  pipeline: Option<*const wgpu::RenderPipeline>,
+  bind_groups: Vec<Option<*const wgpu::BindGroup>>,
+}
+
+impl Bound {
+  /// Marks the state of `item` as bound. Returns whether the pipeline
+  /// changed and which bind groups did.
+  fn bind(&mut self, item: &DrawItem) -> (bool, Vec<u32>) {
+    let pipeline = Some(item.pipeline as *const _);
+    let pipeline_changed = self.pipeline != pipeline;
+    self.pipeline = pipeline;
+
+    if self.bind_groups.len() < item.bind_groups.len() {
+      self.bind_groups.resize(item.bind_groups.len(), None);
+    }
+    let mut changed_groups = Vec::new();
+    for (index, bind_group) in item.bind_groups.iter().enumerate() {
+      let bind_group = Some(*bind_group as *const _);
+      if self.bind_groups[index] != bind_group {
+        self.bind_groups[index] = bind_group;
+        changed_groups.push(index as u32);
+      }
+    }
+    (pipeline_changed, changed_groups)
+  }
+}
+
+fn switches(pipeline_changed: bool, changed_groups: &[u32]) -> u32 {
+  pipeline_changed as u32 + changed_groups.len() as u32
+}
+
+/// Collects draws so they can be issued in the order that changes state the
+/// least. Use it for draws that don't depend on the order they're drawn in,
+/// like opaque geometry with depth testing.
+pub struct DrawQueue<'a> {
+  items: Vec<DrawItem<'a>>,
+}
+
+impl<'a> DrawQueue<'a> {
+  pub fn new() -> Self {
+    Self { items: Vec::new() }
+  }
+
+  pub fn submit_draw(&mut self, item: DrawItem<'a>) {
+    self.items.push(item);
+  }
+
+  /// Draws everything submitted so far, sorted by pipeline and then bind
+  /// groups, and empties the queue. Vertex and index buffers are set for
+  /// every draw. The pass is left with the state of the last draw bound.
+  pub fn flush(&mut self, render_pass: &mut wgpu::RenderPass<'a>) -> DrawStats {
+    let mut unsorted = Bound::default();
+    let unsorted_switches = self
+      .items
+      .iter()
+      .map(|item| {
+        let (pipeline_changed, changed_groups) = unsorted.bind(item);
+        switches(pipeline_changed, &changed_groups)
+      })
+      .sum();
+
+    // Stable, so items with the same state keep their submission order.
+    self.items.sort_by_key(DrawItem::sort_key);
+
+    let mut bound = Bound::default();
+    let mut stats = DrawStats {
+      switches: 0,
+      unsorted_switches,
+    };
+    for item in self.items.drain(..) {
+      let (pipeline_changed, changed_groups) = bound.bind(&item);
+      stats.switches += switches(pipeline_changed, &changed_groups);
+      if pipeline_changed {
+        render_pass.set_pipeline(item.pipeline);
+      }
+      for index in changed_groups {
+        render_pass.set_bind_group(index, item.bind_groups[index as usize], &[]);
+      }
+      for (slot, buffer) in item.vertex_buffers.into_iter().enumerate() {
+        render_pass.set_vertex_buffer(slot as u32, buffer);
+      }
+      render_pass.set_index_buffer(item.index_buffer, item.index_format);
+      render_pass.set_stencil_reference(item.stencil_reference);
+      render_pass.draw_indexed(item.indices, 0, item.instances);
+    }
+    stats
+  }
+}
@@ -0,0 +1,149 @@
+use cgmath::InnerSpace;
+
+const FACE_SIZE: u32 = 64;
+
+const ZENITH_COLOR: [f32; 3] = [0.18, 0.36, 0.75];
+const HORIZON_COLOR: [f32; 3] = [0.85, 0.9, 1.0];
+const GROUND_COLOR: [f32; 3] = [0.3, 0.27, 0.25];
+
+/// An environment cubemap used for reflections.
+///
+/// There's no skybox asset yet, so the faces are filled with a simple
+/// procedural sky: a gradient from the horizon up to the zenith over a flat
+/// ground color.
+pub struct Environment {
+  pub bind_group: wgpu::BindGroup,
+  pub bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Environment {
+  pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+    let size = wgpu::Extent3d {
+      width: FACE_SIZE,
+      height: FACE_SIZE,
+      depth_or_array_layers: 6,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("environment_cubemap"),
+      size,
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::Rgba8UnormSrgb,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    queue.write_texture(
+      wgpu::ImageCopyTexture {
+        aspect: wgpu::TextureAspect::All,
+        texture: &texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+      },
+      &Self::sky_faces(),
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: std::num::NonZeroU32::new(4 * FACE_SIZE),
+        rows_per_image: std::num::NonZeroU32::new(FACE_SIZE),
+      },
+      size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+      label: Some("environment_cubemap_view"),
+      dimension: Some(wgpu::TextureViewDimension::Cube),
+      ..Default::default()
+    });
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("environment_sampler"),
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      ..Default::default()
+    });
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::Cube,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+          },
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 1,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+          count: None,
+        },
+      ],
+      label: Some("environment_bind_group_layout"),
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("environment_bind_group"),
+      layout: &bind_group_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: wgpu::BindingResource::TextureView(&view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(&sampler),
+        },
+      ],
+    });
+
+    Self {
+      bind_group,
+      bind_group_layout,
+    }
+  }
+
+  /// Builds the six faces in the order wgpu expects (+X, -X, +Y, -Y, +Z, -Z)
+  /// as tightly packed sRGB RGBA texels.
+  fn sky_faces() -> Vec<u8> {
+    let mut data = Vec::with_capacity((FACE_SIZE * FACE_SIZE * 4 * 6) as usize);
+    for face in 0..6 {
+      for y in 0..FACE_SIZE {
+        for x in 0..FACE_SIZE {
+          // Map the texel center to [-1, 1] on the face.
+          let u = 2.0 * (x as f32 + 0.5) / FACE_SIZE as f32 - 1.0;
+          let v = 2.0 * (y as f32 + 0.5) / FACE_SIZE as f32 - 1.0;
+          let direction = match face {
+            0 => cgmath::vec3(1.0, -v, -u),
+            1 => cgmath::vec3(-1.0, -v, u),
+            2 => cgmath::vec3(u, 1.0, v),
+            3 => cgmath::vec3(u, -1.0, -v),
+            4 => cgmath::vec3(u, -v, 1.0),
+            _ => cgmath::vec3(-u, -v, -1.0),
+          };
+          let color = Self::sky_color(direction.normalize().y);
+          data.extend(color.iter().map(|c| (c * 255.0).round() as u8));
+          data.push(255);
+        }
+      }
+    }
+    data
+  }
+
+  fn sky_color(height: f32) -> [f32; 3] {
+    if height < 0.0 {
+      return GROUND_COLOR;
+    }
+    let t = height.sqrt();
+    [
+      HORIZON_COLOR[0] + (ZENITH_COLOR[0] - HORIZON_COLOR[0]) * t,
+      HORIZON_COLOR[1] + (ZENITH_COLOR[1] - HORIZON_COLOR[1]) * t,
+      HORIZON_COLOR[2] + (ZENITH_COLOR[2] - HORIZON_COLOR[2]) * t,
+    ]
+  }
+}
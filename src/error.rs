@@ -0,0 +1,23 @@
+/// Why the viewer couldn't be set up.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+  #[error("Couldn't create the window")]
+  Window(#[from] winit::error::OsError),
+  #[error("No adapter can draw to the window, not even a software one")]
+  NoAdapter,
+  #[error("{adapter} lacks the features {missing:?}, ask for fewer")]
+  MissingFeatures {
+    adapter: String,
+    missing: wgpu::Features,
+  },
+  #[error("Couldn't create the device, the adapter may not support the limits asked for")]
+  DeviceRequest(#[from] wgpu::RequestDeviceError),
+  #[error("{0} can't draw to the window's surface, try another adapter or backend")]
+  SurfaceUnsupported(String),
+  #[error("Couldn't compile {path}")]
+  ShaderCompile {
+    path: &'static str,
+    #[source]
+    source: anyhow::Error,
+  },
+}
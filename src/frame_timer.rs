@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+/// How long frames are averaged over before a frame rate is reported.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Weight of the newest frame in the moving average of frame times. Low
+/// enough that a single hitch barely moves it.
+const SMOOTHING: f32 = 0.05;
+
+/// Counts frames, reports the average frame rate once per second and keeps a
+/// moving average of the frame time. The caller says what time it is, see
+/// `State::now`.
+pub struct FrameTimer {
+  average_frame_time: Option<f32>,
+  frames: u32,
+  interval_start: instant::Instant,
+  last_tick: instant::Instant,
+}
+
+impl FrameTimer {
+  pub fn new(now: instant::Instant) -> Self {
+    Self {
+      average_frame_time: None,
+      frames: 0,
+      interval_start: now,
+      last_tick: now,
+    }
+  }
+
+  /// Call once per frame. Returns the frames per second over the last
+  /// interval whenever one completes.
+  pub fn tick(&mut self, now: instant::Instant) -> Option<f32> {
+    let frame_time = (now - self.last_tick).as_secs_f32();
+    self.last_tick = now;
+    self.average_frame_time = Some(match self.average_frame_time {
+      Some(average) => average + (frame_time - average) * SMOOTHING,
+      None => frame_time,
+    });
+
+    self.frames += 1;
+    let elapsed = now - self.interval_start;
+    if elapsed < REPORT_INTERVAL {
+      return None;
+    }
+    let fps = self.frames as f32 / elapsed.as_secs_f32();
+    self.frames = 0;
+    self.interval_start = now;
+    Some(fps)
+  }
+
+  /// Exponential moving average of the time between ticks in seconds, `None`
+  /// until the first tick.
+  pub fn average_frame_time(&self) -> Option<f32> {
+    self.average_frame_time
+  }
+
+  /// Forgets the average, e.g. after a change that makes older frames
+  /// unrepresentative.
+  pub fn reset_average(&mut self) {
+    self.average_frame_time = None;
+  }
+}
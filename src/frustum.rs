@@ -0,0 +1,110 @@
+use cgmath::prelude::*;
+
+/// Extracts the left, right, bottom, top, near and far planes of the view
+/// volume of `view_proj` (Gribb and Hartmann's method), as `[a, b, c, d]`
+/// with `a * x + b * y + c * z + d` the signed distance of a point to the
+/// plane, positive inside. Clip space depth goes from 0 to w here, unlike
+/// OpenGL's -w to w.
+pub fn planes(view_proj: &cgmath::Matrix4<f32>) -> [[f32; 4]; 6] {
+  let row = |i| view_proj.row(i);
+  [
+    row(3) + row(0),
+    row(3) - row(0),
+    row(3) + row(1),
+    row(3) - row(1),
+    // With reverse Z these two swap, which doesn't matter when testing
+    // against all six.
+    row(2),
+    row(3) - row(2),
+  ]
+  .map(|plane| (plane / plane.truncate().magnitude()).into())
+}
+
+/// A view volume, for testing what a camera can see.
+pub struct Frustum {
+  planes: [cgmath::Vector4<f32>; 6],
+}
+
+impl Frustum {
+  /// `planes` as returned by `planes` or `Camera::frustum_planes`.
+  pub fn new(planes: [[f32; 4]; 6]) -> Self {
+    Self {
+      planes: planes.map(cgmath::Vector4::from),
+    }
+  }
+
+  /// Whether any of the sphere is inside. Spheres just outside a corner,
+  /// behind two planes but in front of each, count as inside too.
+  pub fn contains_sphere(&self, center: cgmath::Point3<f32>, radius: f32) -> bool {
+    let center = center.to_homogeneous();
+    self.planes.iter().all(|plane| plane.dot(center) >= -radius)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::camera::Camera;
+
+  /// Looks down -Z from the origin with a 90° square view, so the sides are
+  /// as far from the axis as the plane is away, from 1 to 10.
+  fn frustum(reverse_z: bool) -> Frustum {
+    let camera = Camera {
+      eye: (0.0, 0.0, 0.0).into(),
+      target: (0.0, 0.0, -1.0).into(),
+      up: cgmath::Vector3::unit_y(),
+      aspect: 1.0,
+      fovy: 90.0,
+      znear: 1.0,
+      zfar: 10.0,
+      reverse_z,
+    };
+    Frustum::new(camera.frustum_planes())
+  }
+
+  #[test]
+  fn normalizes_the_planes() {
+    let view_proj = cgmath::perspective(cgmath::Deg(90.0), 1.0, 1.0, 10.0);
+    for plane in planes(&(crate::OPENGL_TO_WGPU_MATRIX * view_proj)) {
+      let [a, b, c, _] = plane;
+      assert!(
+        ((a * a + b * b + c * c).sqrt() - 1.0).abs() < 1e-5,
+        "{:?}",
+        plane
+      );
+    }
+  }
+
+  #[test]
+  fn classifies_spheres() {
+    let spheres = [
+      // Inside.
+      ((0.0, 0.0, -5.0), 0.5, true),
+      ((4.0, -4.0, -5.0), 0.5, true),
+      // Straddling the right, near and far planes.
+      ((5.5, 0.0, -5.0), 1.0, true),
+      ((0.0, 0.0, -0.5), 1.0, true),
+      ((0.0, 0.0, -10.5), 1.0, true),
+      // Outside to the right and top, behind, before the near and past the
+      // far plane.
+      ((6.0, 0.0, -5.0), 0.5, false),
+      ((0.0, 7.0, -5.0), 1.0, false),
+      ((0.0, 0.0, 5.0), 0.5, false),
+      ((0.0, 0.0, -0.5), 0.25, false),
+      ((0.0, 0.0, -20.0), 1.0, false),
+    ];
+    for reverse_z in [false, true] {
+      let frustum = frustum(reverse_z);
+      for (center, radius, inside) in spheres {
+        assert_eq!(
+          frustum.contains_sphere(center.into(), radius),
+          inside,
+          "{:?} with radius {}, reverse Z {}",
+          center,
+          radius,
+          reverse_z
+        );
+      }
+    }
+  }
+}
@@ -0,0 +1,32 @@
+/// Stick deflections up to this count as centered. Sticks rarely rest at
+/// exactly 0, and the drift would keep the camera creeping.
+const DEADZONE: f32 = 0.15;
+
+/// What the gamepad is doing to the camera. Both are x to the right and y
+/// up, at most 1 long.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GamepadInput {
+  /// Moves the camera like WASD, from the left stick or the D-pad.
+  pub movement: (f32, f32),
+  /// Orbits the camera like mouse look, from the right stick.
+  pub look: (f32, f32),
+}
+
+impl GamepadInput {
+  pub fn is_centered(&self) -> bool {
+    self.movement == (0.0, 0.0) && self.look == (0.0, 0.0)
+  }
+}
+
+/// A stick's `(x, y)` with the deadzone cut out of the middle and the rest
+/// stretched back to a length of 1, so leaving the deadzone starts from 0
+/// instead of jumping.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub fn apply_deadzone((x, y): (f32, f32)) -> (f32, f32) {
+  let length = (x * x + y * y).sqrt();
+  if length <= DEADZONE {
+    return (0.0, 0.0);
+  }
+  let scale = ((length - DEADZONE) / (1.0 - DEADZONE)).min(1.0) / length;
+  (x * scale, y * scale)
+}
@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+
+/// How hard `gif` works to find a palette for each frame, from 1, the
+/// best, to 30, the fastest.
+const QUANTIZE_SPEED: i32 = 10;
+
+/// A downscaled frame as tightly packed RGBA, and when it was captured.
+struct Frame {
+  pixels: Vec<u8>,
+  time: instant::Instant,
+}
+
+/// Keeps the last few seconds of frames around to save as a GIF.
+///
+/// A frame takes four bytes per pixel after downscaling, so the buffer
+/// holds about `seconds * fps * (width / scale) * (height / scale) * 4`
+/// bytes: 5 seconds at 10 fps of a 1920x1080 window scaled by 4 is 26 MB. Every
+/// captured frame also stalls rendering on the readback, which a lower
+/// `fps` makes rarer.
+pub struct GifRecorder {
+  capacity: usize,
+  frame_interval: std::time::Duration,
+  frames: VecDeque<Frame>,
+  height: u32,
+  scale: u32,
+  width: u32,
+}
+
+impl GifRecorder {
+  pub fn new(seconds: f32, fps: u32, scale: u32) -> Self {
+    let fps = fps.max(1);
+    Self {
+      capacity: ((seconds * fps as f32).ceil() as usize).max(1),
+      frame_interval: std::time::Duration::from_secs(1) / fps,
+      frames: VecDeque::new(),
+      height: 0,
+      scale: scale.max(1),
+      width: 0,
+    }
+  }
+
+  /// Whether enough time has passed since the last frame to capture another.
+  pub fn is_due(&self, now: instant::Instant) -> bool {
+    self
+      .frames
+      .back()
+      .is_none_or(|frame| now - frame.time >= self.frame_interval)
+  }
+
+  /// Adds a `width` by `height` frame of tightly packed RGBA `pixels`,
+  /// dropping the oldest one if the buffer is full. Frames of another size
+  /// than the ones before, after a resize, start the buffer over.
+  pub fn push(&mut self, pixels: &[u8], width: u32, height: u32, now: instant::Instant) {
+    let scaled_width = (width / self.scale).max(1);
+    let scaled_height = (height / self.scale).max(1);
+    if (scaled_width, scaled_height) != (self.width, self.height) {
+      self.frames.clear();
+      self.width = scaled_width;
+      self.height = scaled_height;
+    }
+
+    let mut scaled = Vec::with_capacity((scaled_width * scaled_height * 4) as usize);
+    for y in 0..scaled_height {
+      for x in 0..scaled_width {
+        let offset = (((y * self.scale) * width + x * self.scale) * 4) as usize;
+        scaled.extend_from_slice(&pixels[offset..offset + 3]);
+        scaled.push(255);
+      }
+    }
+
+    if self.frames.len() == self.capacity {
+      self.frames.pop_front();
+    }
+    self.frames.push_back(Frame {
+      pixels: scaled,
+      time: now,
+    });
+  }
+
+  /// Writes the buffered frames to `path` as a looping GIF, each shown for
+  /// as long as it was on screen. Fails if nothing was captured yet.
+  pub fn save(&self, path: &std::path::Path) -> Result<()> {
+    if self.frames.is_empty() {
+      bail!("No frames captured yet");
+    }
+    let mut gif = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut gif, self.width as u16, self.height as u16, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    for (i, frame) in self.frames.iter().enumerate() {
+      // The last frame stays up for one frame interval. Viewers treat delays
+      // under 2 as slow, so that's the shortest.
+      let next = self
+        .frames
+        .get(i + 1)
+        .map_or(frame.time + self.frame_interval, |next| next.time);
+      let delay = ((next - frame.time).as_millis() / 10).clamp(2, u16::MAX as u128) as u16;
+
+      let mut pixels = frame.pixels.clone();
+      let mut gif_frame = gif::Frame::from_rgba_speed(
+        self.width as u16,
+        self.height as u16,
+        &mut pixels,
+        QUANTIZE_SPEED,
+      );
+      gif_frame.delay = delay;
+      encoder.write_frame(&gif_frame)?;
+    }
+    // Writes the trailer.
+    encoder.into_inner()?;
+
+    std::fs::write(path, &gif).with_context(|| format!("Couldn't write {}", path.display()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn saves_frames_a_decoder_reads_back() {
+    let mut recorder = GifRecorder::new(1.0, 10, 2);
+    let start = instant::Instant::now();
+    let red = [255, 0, 0, 255].repeat(4 * 2);
+    let blue = [0, 0, 255, 255].repeat(4 * 2);
+    recorder.push(&red, 4, 2, start);
+    recorder.push(&blue, 4, 2, start + std::time::Duration::from_millis(200));
+    let path = std::env::temp_dir().join("gif_recorder_test.gif");
+    recorder.save(&path).unwrap();
+
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options
+      .read_info(std::fs::File::open(&path).unwrap())
+      .unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (2, 1));
+    let first = decoder.read_next_frame().unwrap().unwrap();
+    assert_eq!(first.delay, 20);
+    assert_eq!(first.buffer[..], [255, 0, 0, 255, 255, 0, 0, 255]);
+    let second = decoder.read_next_frame().unwrap().unwrap();
+    assert_eq!(second.buffer[..], [0, 0, 255, 255, 0, 0, 255, 255]);
+    assert!(decoder.read_next_frame().unwrap().is_none());
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn keeps_the_last_frames_and_restarts_on_resize() {
+    let mut recorder = GifRecorder::new(0.2, 10, 1);
+    let now = instant::Instant::now();
+    for _ in 0..3 {
+      recorder.push(&[0; 4], 1, 1, now);
+    }
+    assert_eq!(recorder.frames.len(), 2);
+    recorder.push(&[0; 8], 2, 1, now);
+    assert_eq!(recorder.frames.len(), 1);
+    assert_eq!((recorder.width, recorder.height), (2, 1));
+  }
+}
@@ -0,0 +1,196 @@
+use std::path::{Path, PathBuf};
+
+/// Set to `1` to have `assert_image_matches` write the images it's given as
+/// the new references instead of comparing.
+const UPDATE_ENV_VAR: &str = "UPDATE_GOLDEN";
+
+/// How far an image may be from its reference. GPUs round and filter a bit
+/// differently, so exact matches only hold on one adapter.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tolerance {
+  /// Largest difference in any channel that still counts as the same pixel.
+  pub channel: u8,
+  /// Fraction of the pixels, between 0 and 1, that may differ by more.
+  pub max_differing_fraction: f32,
+}
+
+impl Default for Tolerance {
+  /// Enough for software rasterizers and hardware to agree.
+  fn default() -> Self {
+    Self {
+      channel: 2,
+      max_differing_fraction: 0.001,
+    }
+  }
+}
+
+/// Panics unless `actual` matches the PNG at `golden_path` within
+/// `tolerance`. On a mismatch the image is written next to the reference as
+/// `<name>.actual.png`, along with `<name>.diff.png`, which shows the pixels
+/// that differ in red over a faded copy of it.
+///
+/// With `UPDATE_GOLDEN=1` in the environment `actual` is written to
+/// `golden_path` instead, to add or update the reference.
+pub fn assert_image_matches(
+  actual: &image::RgbaImage,
+  golden_path: impl AsRef<Path>,
+  tolerance: Tolerance,
+) {
+  let golden_path = golden_path.as_ref();
+  if std::env::var(UPDATE_ENV_VAR).as_deref() == Ok("1") {
+    if let Some(dir) = golden_path.parent() {
+      std::fs::create_dir_all(dir).unwrap();
+    }
+    actual.save(golden_path).unwrap();
+    log::info!("Updated {}", golden_path.display());
+    return;
+  }
+
+  let expected = match image::open(golden_path) {
+    Ok(expected) => expected.into_rgba8(),
+    Err(e) => panic!(
+      "Couldn't read {} ({}), run with {}=1 to create it",
+      golden_path.display(),
+      e,
+      UPDATE_ENV_VAR
+    ),
+  };
+  if expected.dimensions() != actual.dimensions() {
+    let actual_path = sibling(golden_path, "actual");
+    actual.save(&actual_path).unwrap();
+    panic!(
+      "{} is {:?} but the image is {:?}, see {}",
+      golden_path.display(),
+      expected.dimensions(),
+      actual.dimensions(),
+      actual_path.display()
+    );
+  }
+
+  let (differing, diff) = compare(actual, &expected, tolerance.channel);
+  let pixels = (actual.width() * actual.height()) as f32;
+  let fraction = differing as f32 / pixels;
+  if fraction > tolerance.max_differing_fraction {
+    let actual_path = sibling(golden_path, "actual");
+    let diff_path = sibling(golden_path, "diff");
+    actual.save(&actual_path).unwrap();
+    diff.save(&diff_path).unwrap();
+    panic!(
+      "{} of the pixels differ from {} by more than {}, up to {} may. See {} and {}",
+      fraction,
+      golden_path.display(),
+      tolerance.channel,
+      tolerance.max_differing_fraction,
+      actual_path.display(),
+      diff_path.display()
+    );
+  }
+}
+
+/// How many pixels of `actual` differ from `expected` by more than
+/// `channel_tolerance` in any channel, and the diff image showing them.
+fn compare(
+  actual: &image::RgbaImage,
+  expected: &image::RgbaImage,
+  channel_tolerance: u8,
+) -> (usize, image::RgbaImage) {
+  let mut differing = 0;
+  let diff = image::RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+    let (a, e) = (actual.get_pixel(x, y), expected.get_pixel(x, y));
+    let differs = a
+      .0
+      .iter()
+      .zip(e.0)
+      .any(|(&a, e)| a.abs_diff(e) > channel_tolerance);
+    if differs {
+      differing += 1;
+      image::Rgba([255, 0, 0, 255])
+    } else {
+      // A quarter of the luma shows where things are without drowning out
+      // the red.
+      let luma = (a[0] as u32 * 299 + a[1] as u32 * 587 + a[2] as u32 * 114) / 4000;
+      image::Rgba([luma as u8, luma as u8, luma as u8, 255])
+    }
+  });
+  (differing, diff)
+}
+
+/// `dir/name.png` to `dir/name.suffix.png`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{headless, Config, Error};
+
+  fn filled(width: u32, height: u32, value: u8) -> image::RgbaImage {
+    image::RgbaImage::from_pixel(width, height, image::Rgba([value, value, value, 255]))
+  }
+
+  #[test]
+  fn counts_pixels_over_the_tolerance_only() {
+    let expected = filled(3, 1, 100);
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 0, image::Rgba([102, 100, 100, 255]));
+    actual.put_pixel(2, 0, image::Rgba([100, 100, 97, 255]));
+    let (differing, diff) = compare(&actual, &expected, 2);
+    assert_eq!(differing, 1);
+    assert_eq!(diff.get_pixel(1, 0)[0], 25);
+    assert_eq!(*diff.get_pixel(2, 0), image::Rgba([255, 0, 0, 255]));
+  }
+
+  /// A 1000 pixel reference in its own directory, and a copy of it with
+  /// `differing` pixels far off.
+  fn reference(name: &str, differing: u32) -> (PathBuf, image::RgbaImage) {
+    let dir = std::env::temp_dir().join(format!("golden_test_{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("reference.png");
+    let expected = filled(100, 10, 0);
+    expected.save(&path).unwrap();
+    let mut actual = expected;
+    for x in 0..differing {
+      actual.put_pixel(x, 0, image::Rgba([255; 4]));
+    }
+    (path, actual)
+  }
+
+  #[test]
+  fn allows_up_to_the_differing_fraction() {
+    let (path, actual) = reference("within", 1);
+    assert_image_matches(&actual, &path, Tolerance::default());
+  }
+
+  #[test]
+  #[should_panic(expected = "of the pixels differ")]
+  fn fails_past_the_differing_fraction() {
+    let (path, actual) = reference("over", 2);
+    assert_image_matches(&actual, &path, Tolerance::default());
+  }
+
+  /// Skipped on machines without any adapter, software ones included.
+  #[test]
+  fn default_scene_matches_its_reference() {
+    let (width, height) = (128, 96);
+    let rendered = pollster::block_on(headless::render_frame(
+      Config::default(),
+      width,
+      height,
+      0,
+      0,
+    ));
+    let pixels = match rendered {
+      Ok(pixels) => pixels,
+      Err(e) if matches!(e.downcast_ref(), Some(Error::NoAdapter)) => {
+        eprintln!("No adapter, skipping the golden image test");
+        return;
+      }
+      Err(e) => panic!("{:?}", e),
+    };
+    let image = image::RgbaImage::from_raw(width, height, pixels).unwrap();
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/default_scene.png");
+    assert_image_matches(&image, golden_path, Tolerance::default());
+  }
+}
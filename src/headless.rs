@@ -0,0 +1,96 @@
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+
+use crate::config::{ColorSpace, Config};
+use crate::screenshot::Screenshot;
+use crate::{Output, State, Target};
+
+/// Time step of `render_frame` for configs that don't have one.
+const DEFAULT_TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// The texture frames are drawn into without a window, in place of a
+/// surface.
+pub struct OffscreenTarget {
+  texture: wgpu::Texture,
+  readback: Screenshot,
+}
+
+impl OffscreenTarget {
+  pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("offscreen_target"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: config.format,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    });
+
+    Self {
+      texture,
+      readback: Screenshot::new(device, config),
+    }
+  }
+
+  pub fn create_view(&self) -> wgpu::TextureView {
+    self
+      .texture
+      .create_view(&wgpu::TextureViewDescriptor::default())
+  }
+
+  /// Copies what was drawn into the texture out for `read`.
+  pub fn capture(&self, encoder: &mut wgpu::CommandEncoder) {
+    self.readback.capture_texture(encoder, &self.texture);
+  }
+
+  /// The frame from the last submitted `capture` as RGBA rows.
+  pub fn read(&self, device: &wgpu::Device) -> Result<Vec<u8>> {
+    self.readback.read(device)
+  }
+}
+
+/// The format frames are drawn in without a window. There's no surface to
+/// ask, so it's RGBA, which reads back without swapping channels.
+pub fn format(color_space: ColorSpace) -> wgpu::TextureFormat {
+  match color_space {
+    ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+    ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+  }
+}
+
+/// Draws frames 0 to `frame` of what `config` shows, `width` by `height`
+/// and without a window, and returns the last one as RGBA rows. The noise
+/// texture is generated from `seed`.
+///
+/// Meant for golden image tests, so the same arguments draw the same pixels
+/// on the same adapter: time only moves by the config's fixed time step (or
+/// 1/60 s without one) and textures are waited for instead of swapped in
+/// whenever they finish loading.
+pub async fn render_frame(
+  mut config: Config,
+  width: u32,
+  height: u32,
+  seed: u32,
+  frame: u32,
+) -> Result<Vec<u8>> {
+  config.fixed_time_step.get_or_insert(DEFAULT_TIME_STEP);
+  let size = winit::dpi::PhysicalSize::new(width, height);
+  let mut state = State::new(Target::Headless(size), config).await?;
+  state.noise_seed = seed;
+  let loaded = state.texture_loader.wait();
+  state.upload_textures(loaded);
+
+  for _ in 0..=frame {
+    state.update();
+    state.render().context("Couldn't draw a frame")?;
+  }
+  match &state.output {
+    Output::Offscreen(target) => target.read(&state.device),
+    Output::Surface(_) => bail!("Drew to a surface instead of offscreen"),
+  }
+}
@@ -0,0 +1,169 @@
+use anyhow::*;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use winit::event::*;
+
+/// The kinds of `WindowEvent` that `State::input` reacts to, without the
+/// device ids that can't be saved.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum RecordedEvent {
+  Keyboard {
+    scancode: ScanCode,
+    state: ElementState,
+    virtual_keycode: Option<VirtualKeyCode>,
+  },
+  CursorMoved {
+    x: f64,
+    y: f64,
+  },
+  MouseInput {
+    state: ElementState,
+    button: MouseButton,
+  },
+  ModifiersChanged(ModifiersState),
+}
+
+impl RecordedEvent {
+  fn from_window_event(event: &WindowEvent) -> Option<Self> {
+    let recorded = match event {
+      WindowEvent::KeyboardInput { input, .. } => Self::Keyboard {
+        scancode: input.scancode,
+        state: input.state,
+        virtual_keycode: input.virtual_keycode,
+      },
+      WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+        x: position.x,
+        y: position.y,
+      },
+      WindowEvent::MouseInput { state, button, .. } => Self::MouseInput {
+        state: *state,
+        button: *button,
+      },
+      WindowEvent::ModifiersChanged(modifiers) => Self::ModifiersChanged(*modifiers),
+      _ => return None,
+    };
+    Some(recorded)
+  }
+
+  // The per-event `modifiers` fields are deprecated but still have to be set.
+  #[allow(deprecated)]
+  fn to_window_event(&self) -> WindowEvent<'static> {
+    // SAFETY: The dummy id doesn't belong to any real device, which only
+    // matters to code that passes it back to winit. `State::input` never
+    // looks at it.
+    let device_id = unsafe { DeviceId::dummy() };
+    match *self {
+      Self::Keyboard {
+        scancode,
+        state,
+        virtual_keycode,
+      } => WindowEvent::KeyboardInput {
+        device_id,
+        input: KeyboardInput {
+          scancode,
+          state,
+          virtual_keycode,
+          modifiers: ModifiersState::empty(),
+        },
+        is_synthetic: true,
+      },
+      Self::CursorMoved { x, y } => WindowEvent::CursorMoved {
+        device_id,
+        position: winit::dpi::PhysicalPosition::new(x, y),
+        modifiers: ModifiersState::empty(),
+      },
+      Self::MouseInput { state, button } => WindowEvent::MouseInput {
+        device_id,
+        state,
+        button,
+        modifiers: ModifiersState::empty(),
+      },
+      Self::ModifiersChanged(modifiers) => WindowEvent::ModifiersChanged(modifiers),
+    }
+  }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+  time: f64,
+  event: RecordedEvent,
+}
+
+/// Appends events to a recording as they happen. Recordings are JSON lines,
+/// each an event and the seconds since the recorder was created.
+pub struct Recorder {
+  file: std::fs::File,
+  start: instant::Instant,
+}
+
+impl Recorder {
+  pub fn create(path: &str) -> Result<Self> {
+    Ok(Self {
+      file: std::fs::File::create(path)?,
+      start: instant::Instant::now(),
+    })
+  }
+
+  /// Writes `event` out straight away, the event loop may exit the process
+  /// without dropping us.
+  pub fn record(&mut self, event: &WindowEvent) -> Result<()> {
+    let event = match RecordedEvent::from_window_event(event) {
+      Some(event) => event,
+      None => return Ok(()),
+    };
+    let entry = Entry {
+      time: self.start.elapsed().as_secs_f64(),
+      event,
+    };
+    let mut line = serde_json::to_vec(&entry)?;
+    line.push(b'\n');
+    self.file.write_all(&line)?;
+    Ok(())
+  }
+}
+
+/// Hands back the events of a recording once as much time has passed as
+/// when they were recorded.
+pub struct Replay {
+  entries: VecDeque<Entry>,
+  start: instant::Instant,
+}
+
+impl Replay {
+  pub fn load(path: &str) -> Result<Self> {
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut entries = VecDeque::new();
+    for (number, line) in file.lines().enumerate() {
+      let entry = serde_json::from_str(&line?)
+        .with_context(|| format!("Bad event on line {} of {}", number + 1, path))?;
+      entries.push_back(entry);
+    }
+
+    Ok(Self {
+      entries,
+      start: instant::Instant::now(),
+    })
+  }
+
+  /// The events that are due, oldest first.
+  pub fn due_events(&mut self) -> Vec<WindowEvent<'static>> {
+    let elapsed = self.start.elapsed().as_secs_f64();
+    let mut events = Vec::new();
+    while let Some(entry) = self.entries.front() {
+      if entry.time > elapsed {
+        break;
+      }
+      events.push(entry.event.to_window_event());
+      self.entries.pop_front();
+    }
+    events
+  }
+
+  /// When the next event is due, or `None` once everything was replayed.
+  pub fn next_event_time(&self) -> Option<instant::Instant> {
+    self
+      .entries
+      .front()
+      .map(|entry| self.start + std::time::Duration::from_secs_f64(entry.time))
+  }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+/// A key pressed while holding exactly `modifiers`, like Ctrl+S.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Chord {
+  pub modifiers: ModifiersState,
+  pub key: VirtualKeyCode,
+}
+
+impl Chord {
+  pub const fn new(modifiers: ModifiersState, key: VirtualKeyCode) -> Self {
+    Self { modifiers, key }
+  }
+}
+
+/// Which keys are held, to tell new presses from the operating system's
+/// auto-repeat.
+pub struct KeyboardState {
+  /// Chords that already fired and are still held.
+  active_chords: Vec<Chord>,
+  held: HashSet<VirtualKeyCode>,
+  // winit reports modifiers in their own event rather than as held keys.
+  modifiers: ModifiersState,
+  pressed_this_frame: HashSet<VirtualKeyCode>,
+}
+
+impl KeyboardState {
+  pub fn new() -> Self {
+    Self {
+      active_chords: Vec::new(),
+      held: HashSet::new(),
+      modifiers: ModifiersState::empty(),
+      pressed_this_frame: HashSet::new(),
+    }
+  }
+
+  /// Records a key event. Returns true if it's a repeat of a key that was
+  /// already down, which should be ignored by anything that fires once per
+  /// press.
+  pub fn process(&mut self, input: &KeyboardInput) -> bool {
+    let keycode = match input.virtual_keycode {
+      Some(keycode) => keycode,
+      None => return false,
+    };
+    match input.state {
+      ElementState::Pressed => {
+        let repeat = !self.held.insert(keycode);
+        if !repeat {
+          self.pressed_this_frame.insert(keycode);
+        }
+        repeat
+      }
+      ElementState::Released => {
+        self.held.remove(&keycode);
+        false
+      }
+    }
+  }
+
+  pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+    self.modifiers = modifiers;
+  }
+
+  pub fn modifiers(&self) -> ModifiersState {
+    self.modifiers
+  }
+
+  /// Returns true once when `chord` becomes held. Letting go of any of its
+  /// keys ends the activation, so it can fire again.
+  pub fn chord_fired(&mut self, chord: Chord) -> bool {
+    let held = self.modifiers == chord.modifiers && self.held.contains(&chord.key);
+    let active = self
+      .active_chords
+      .iter()
+      .position(|active| *active == chord);
+    match (held, active) {
+      (true, None) => {
+        self.active_chords.push(chord);
+        true
+      }
+      (false, Some(index)) => {
+        self.active_chords.swap_remove(index);
+        false
+      }
+      _ => false,
+    }
+  }
+
+  pub fn is_held(&self, keycode: VirtualKeyCode) -> bool {
+    self.held.contains(&keycode)
+  }
+
+  /// Whether `keycode` went down since the last `end_frame`, not counting
+  /// repeats.
+  pub fn was_pressed_this_frame(&self, keycode: VirtualKeyCode) -> bool {
+    self.pressed_this_frame.contains(&keycode)
+  }
+
+  /// Forgets every held key, for when their releases can't be seen.
+  pub fn release_all(&mut self) {
+    self.held.clear();
+    self.modifiers = ModifiersState::empty();
+    self.active_chords.clear();
+  }
+
+  /// Call at the end of every update.
+  pub fn end_frame(&mut self) {
+    self.pressed_this_frame.clear();
+  }
+}
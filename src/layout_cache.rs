@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Hands out one shared `BindGroupLayout` for each distinct list of entries,
+/// instead of structurally identical layouts being created over and over.
+pub struct BindGroupLayoutCache {
+  layouts: HashMap<Vec<wgpu::BindGroupLayoutEntry>, Rc<wgpu::BindGroupLayout>>,
+}
+
+impl BindGroupLayoutCache {
+  pub fn new() -> Self {
+    Self {
+      layouts: HashMap::new(),
+    }
+  }
+
+  /// The layout with `entries`, created the first time they're asked for.
+  /// It keeps the label it was created with, whoever asks for it later.
+  pub fn get(
+    &mut self,
+    device: &wgpu::Device,
+    entries: &[wgpu::BindGroupLayoutEntry],
+    label: &str,
+  ) -> Rc<wgpu::BindGroupLayout> {
+    self
+      .layouts
+      .entry(entries.to_vec())
+      .or_insert_with(|| {
+        Rc::new(
+          device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries,
+            label: Some(label),
+          }),
+        )
+      })
+      .clone()
+  }
+}
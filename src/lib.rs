@@ -1,51 +1,228 @@
+use background::Background;
+use bench::FrameBench;
+use camera::CameraBinding;
 use camera_controller::CameraController;
-use wgpu::{include_wgsl, util::DeviceExt};
+use cgmath::prelude::*;
+use device_loss::DeviceLoss;
+use draw_queue::{DrawQueue, DrawStats};
+use environment::Environment;
+use frame_timer::FrameTimer;
+#[cfg(not(target_arch = "wasm32"))]
+use gif_recorder::GifRecorder;
+use keyboard::{Chord, KeyboardState};
+use layout_cache::BindGroupLayoutCache;
+use material::{Material, MaterialParams};
+use mesh::{Mesh, Vertex as _};
+use model::{DrawModel, Model};
+use noise::NoiseTexture;
+use outline::Outline;
+use picking::Picker;
+use render_graph::{PassViews, RenderGraph, Transient};
+use scissor::{Rect, ScissorDemo};
+use screenshot::Screenshot;
+use shadow::{DirectionalLight, ShadowMap};
+use skinning::SkinnedModel;
+use texture_loader::TextureLoader;
+use texture_lod::{LodTexture, Quality};
+use upscale::{DynamicResolution, Upscaler};
+use wgpu::util::DeviceExt;
 use winit::{
   event::*,
   event_loop::{ControlFlow, EventLoop},
-  window::{Window, WindowBuilder},
+  window::{Fullscreen, Window, WindowBuilder},
 };
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+pub use background::BackgroundFit;
+pub use camera::{Camera, OPENGL_TO_WGPU_MATRIX, REVERSE_Z_MATRIX};
+pub use config::{AlphaMode, ColorSpace, Config, RedrawMode};
+pub use error::Error;
+pub use mesh::{cube, grid, plane, uv_sphere};
+pub use state_builder::StateBuilder;
+
+mod adapter;
+mod background;
+mod bench;
+mod camera;
 mod camera_controller;
+mod camera_slots;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+mod color;
+mod config;
+#[cfg(not(target_arch = "wasm32"))]
+mod config_file;
+mod culling;
+mod device_loss;
+mod draw_queue;
+mod environment;
+mod error;
+mod frame_timer;
+mod frustum;
+mod gamepad;
+#[cfg(not(target_arch = "wasm32"))]
+mod gif_recorder;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod golden;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+#[cfg(feature = "record")]
+mod input_recording;
+mod keyboard;
+mod layout_cache;
+mod material;
+mod mesh;
+mod model;
+mod msaa;
+mod noise;
+mod outline;
+mod picking;
+mod pipeline;
+mod render_graph;
+mod scissor;
+mod screenshot;
+mod shader_include;
+mod shadow;
+mod skinning;
+mod state_builder;
+mod terrain;
 mod texture;
+mod texture_loader;
+mod texture_lod;
+mod uniform_buffer;
+mod upscale;
+#[cfg(feature = "ffmpeg")]
+mod video_recording;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(all(feature = "record", target_arch = "wasm32"))]
+compile_error!("The `record` feature needs a filesystem and isn't supported on the web");
+#[cfg(all(feature = "ffmpeg", target_arch = "wasm32"))]
+compile_error!("The `ffmpeg` feature runs a process and isn't supported on the web");
+
+/// Starts the viewer on the web, where failing to is only logged.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(start)]
+pub async fn start() {
+  if let Err(e) = run().await {
+    log::error!("{:?}", anyhow::Error::from(e));
+  }
+}
+
+/// Opens the window and runs the viewer in it until it's closed, set up from
+/// `config.toml` and the command line. Returns only if setting up fails.
+pub async fn run() -> Result<(), Error> {
+  init_logging();
 
-#[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
-pub async fn run() {
+  // The command line overrides the config file, which overrides the defaults.
+  #[cfg(not(target_arch = "wasm32"))]
+  #[cfg_attr(not(feature = "watch-config"), allow(unused_variables))]
+  let (app_config, config_file) = {
+    let mut app_config = Config::default();
+    let config_file = config_file::ConfigFile::load(config_file::CONFIG_PATH).unwrap_or_else(|e| {
+      log::error!("{:?}", e);
+      Default::default()
+    });
+    config_file.apply(&mut app_config);
+    cli::apply_args(&mut app_config);
+    (app_config, config_file)
+  };
+  // There's no file system or command line in the browser.
+  #[cfg(target_arch = "wasm32")]
+  let app_config = Config::default();
+
+  run_event_loop(
+    StateBuilder::from_config(app_config),
+    #[cfg(feature = "watch-config")]
+    Some(config_file),
+  )
+  .await
+}
+
+/// Like `run`, but with the `State` `builder` sets up, callbacks included.
+/// Neither `config.toml` nor the command line are read.
+pub async fn run_with(builder: StateBuilder) -> Result<(), Error> {
+  init_logging();
+  run_event_loop(
+    builder,
+    #[cfg(feature = "watch-config")]
+    None,
+  )
+  .await
+}
+
+/// Logs to the console, natively as `RUST_LOG` says. Keeps a logger that's
+/// already set up, say by an embedder of `run_with`.
+fn init_logging() {
   cfg_if::cfg_if! {
       if #[cfg(target_arch = "wasm32")] {
           std::panic::set_hook(Box::new(console_error_panic_hook::hook));
-          console_log::init_with_level(log::Level::Warn).expect("Could't initialize logger");
+          let _ = console_log::init_with_level(log::Level::Warn);
       } else {
-          env_logger::init();
+          let _ = env_logger::try_init();
       }
   }
+}
 
-  let event_loop = EventLoop::new();
-  let window = WindowBuilder::new().build(&event_loop).unwrap();
+/// Runs the window and the `State` `builder` sets up for it. The config
+/// file, if there is one, is watched for changes.
+async fn run_event_loop(
+  builder: StateBuilder,
+  #[cfg(feature = "watch-config")] config_file: Option<config_file::ConfigFile>,
+) -> Result<(), Error> {
+  let event_loop = EventLoop::<UserEvent>::with_user_event();
+  // Lives as long as the event loop, which never returns.
+  #[cfg(feature = "watch-config")]
+  let _config_watcher = config_file.as_ref().and_then(|_| {
+    let proxy = event_loop.create_proxy();
+    config_file::watch(config_file::CONFIG_PATH, move || {
+      // Fails only once the event loop is gone.
+      let _ = proxy.send_event(UserEvent::ConfigChanged);
+    })
+    .map_err(|e| log::error!("Couldn't watch {}: {:?}", config_file::CONFIG_PATH, e))
+    .ok()
+  });
+
+  let mut window_builder = WindowBuilder::new().with_window_icon(load_window_icon());
+  if let Some(size) = builder.config().window_size {
+    window_builder = window_builder.with_inner_size(size);
+  }
+  let window = window_builder.build(&event_loop)?;
 
   #[cfg(target_arch = "wasm32")]
   {
+    use winit::platform::web::WindowExtWebSys;
+    let canvas = web_sys::Element::from(window.canvas());
+    web::attach_canvas(&canvas);
+    web::watch_texture_files(&canvas, event_loop.create_proxy());
+
     // Winit prevents sizing with CSS, so we have to set
     // the size manually when on web.
-    use winit::dpi::PhysicalSize;
-    window.set_inner_size(PhysicalSize::new(450, 400));
-
-    use winit::platform::web::WindowExtWebSys;
-    web_sys::window()
-      .and_then(|win| win.document())
-      .and_then(|doc| {
-        let dst = doc.get_element_by_id("wasm-example")?;
-        let canvas = web_sys::Element::from(window.canvas());
-        dst.append_child(&canvas).ok()?;
-        Some(())
-      })
-      .expect("Couldn't append canvas to document body.");
+    window.set_inner_size(web::canvas_size());
+    web::watch_resize(event_loop.create_proxy());
+    web::watch_pointer_lock(event_loop.create_proxy());
+    web::watch_gamepads(event_loop.create_proxy());
+    web::watch_context(&canvas, event_loop.create_proxy());
   }
+  // Shared with the task that creates a new state once a lost context is
+  // restored, see `recreate_state`.
+  #[cfg(target_arch = "wasm32")]
+  let (window, recreated, proxy) = (
+    std::rc::Rc::new(window),
+    std::rc::Rc::new(std::cell::RefCell::new(None)),
+    event_loop.create_proxy(),
+  );
 
-  let mut state = State::new(&window).await;
+  #[cfg_attr(not(feature = "watch-config"), allow(unused_mut))]
+  let mut state = builder.build(&window).await?;
+  #[cfg(feature = "watch-config")]
+  if let Some(config_file) = config_file {
+    state.config_file = config_file;
+  }
+  let mut last_redraw = instant::Instant::now();
 
   event_loop.run(move |event, _, control_flow| {
     match event {
@@ -53,86 +230,406 @@ pub async fn run() {
         ref event,
         window_id,
       } if window_id == window.id() => {
-        if !state.input(event) {
-          match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-              input:
-                KeyboardInput {
-                  state: ElementState::Pressed,
-                  virtual_keycode: Some(VirtualKeyCode::Escape),
-                  ..
-                },
-              ..
-            } => *control_flow = ControlFlow::Exit,
-            WindowEvent::Resized(physical_size) => {
-              state.resize(*physical_size);
-            }
-            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-              // new_inner_size is &&mut so we have to dereference it twice
-              state.resize(**new_inner_size);
-            }
-            _ => {}
+        let response = state.input(&window, event);
+        if response.redraw {
+          state.mark_dirty();
+        }
+        if response.consumed {
+          return;
+        }
+        match event {
+          WindowEvent::CloseRequested
+          | WindowEvent::KeyboardInput {
+            input:
+              KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(VirtualKeyCode::Escape),
+                ..
+              },
+            ..
+          } => *control_flow = ControlFlow::Exit,
+          WindowEvent::Resized(physical_size) => {
+            state.resize(*physical_size);
           }
+          WindowEvent::ScaleFactorChanged {
+            scale_factor,
+            new_inner_size,
+          } => {
+            state.scale_factor = *scale_factor;
+            // new_inner_size is &&mut so we have to dereference it twice
+            state.resize(**new_inner_size);
+          }
+          WindowEvent::Focused(focused) => state.set_focused(&window, *focused),
+          _ => {}
         }
       }
+      Event::DeviceEvent { ref event, .. } => state.device_event(event),
       Event::RedrawRequested(window_id) if window_id == window.id() => {
+        #[cfg(target_arch = "wasm32")]
+        if state.context_lost {
+          return;
+        }
+        last_redraw = instant::Instant::now();
         state.update();
         match state.render() {
-          Ok(_) => {}
+          Ok(_) => {
+            if state.bench_frame_finished() {
+              *control_flow = ControlFlow::Exit;
+            }
+          }
           Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
           Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
           Err(e) => eprintln!("{:?}", e),
         }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(reason) = state.device_loss.reason() {
+          log::error!("Lost the device, creating a new one: {}", reason);
+          if let Err(e) = state.recreate(&window) {
+            log::error!("{:?}", anyhow::Error::from(e));
+            *control_flow = ControlFlow::Exit;
+          }
+        }
+      }
+      #[cfg(feature = "watch-config")]
+      Event::UserEvent(UserEvent::ConfigChanged) => state.reload_config(),
+      // winit follows up with a `Resized` if that changes the size. It sizes
+      // a fullscreen canvas to the screen itself.
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::BrowserResized) if window.fullscreen().is_none() => {
+        window.set_inner_size(web::canvas_size())
+      }
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::PointerLockChanged) => {
+        state.set_pointer_locked(web::pointer_locked())
+      }
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::GamepadsChanged) => state.gamepads_changed(),
+      // Swapped in by `upload_loaded_textures` once it's decoded.
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::TextureFileChosen(file)) => state.texture_loader.load_file(file),
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::ContextLost) => {
+        log::warn!("Lost the WebGL context, waiting for it to be restored");
+        state.context_lost = true;
+      }
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::ContextRestored) => recreate_state(
+        window.clone(),
+        state.app_config.clone(),
+        recreated.clone(),
+        proxy.clone(),
+      ),
+      #[cfg(target_arch = "wasm32")]
+      Event::UserEvent(UserEvent::StateRecreated) => {
+        if let Some(new_state) = recreated.borrow_mut().take() {
+          let old = std::mem::replace(&mut state, new_state);
+          state.take_over(old);
+          log::warn!("Restored the WebGL context");
+        }
       }
       Event::MainEventsCleared => {
-        // RedrawRequested will only trigger once, unless we manually request it.
-        window.request_redraw();
+        #[cfg(feature = "record")]
+        let next_replay = state.replay_input(&window);
+        #[cfg(target_arch = "wasm32")]
+        state.poll_gamepad();
+        let now = instant::Instant::now();
+        *control_flow = match state.redraw_interval() {
+          _ if !state.needs_redraw() => ControlFlow::Wait,
+          Some(interval) if now < last_redraw + interval => {
+            ControlFlow::WaitUntil(last_redraw + interval)
+          }
+          interval => {
+            // RedrawRequested will only trigger once, unless we manually request it.
+            window.request_redraw();
+            if state.redraws_continuously() {
+              ControlFlow::Poll
+            } else {
+              // Nothing else wakes the loop for the next animation frame.
+              // Once nothing is animating the wake finds no work and goes
+              // back to waiting.
+              ControlFlow::WaitUntil(now + interval.unwrap_or(ANIMATION_FRAME_INTERVAL))
+            }
+          }
+        };
+        #[cfg(feature = "record")]
+        if let Some(at) = next_replay {
+          *control_flow = wake_by(*control_flow, at);
+        }
+        // Gamepads can't wake the loop, it has to check on them.
+        #[cfg(target_arch = "wasm32")]
+        if state.gamepad_connected {
+          *control_flow = wake_by(*control_flow, now + ANIMATION_FRAME_INTERVAL);
+        }
       }
       _ => {}
     }
   });
 }
 
-struct State {
+/// Draws one frame of what `config` shows, `width` by `height`, without a
+/// window or event loop. It asks for wgpu's fallback adapter, a software
+/// renderer, so it works without a display or GPU, and only uses another
+/// adapter if there's no such thing. Animations are one time step in, the
+/// config's `fixed_time_step` or 1/60 s.
+///
+/// Panics if there's no adapter at all or the frame can't be read back.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn render_to_image(config: &Config, width: u32, height: u32) -> image::RgbaImage {
+  let pixels = headless::render_frame(config.clone(), width, height, 0, 0)
+    .await
+    .expect("Couldn't draw the frame");
+  image::RgbaImage::from_raw(width, height, pixels).expect("The frame has the wrong size")
+}
+
+/// `control_flow`, but waking up by `at` at the latest.
+#[cfg(any(feature = "record", target_arch = "wasm32"))]
+fn wake_by(control_flow: ControlFlow, at: instant::Instant) -> ControlFlow {
+  match control_flow {
+    ControlFlow::Wait => ControlFlow::WaitUntil(at),
+    ControlFlow::WaitUntil(until) => ControlFlow::WaitUntil(until.min(at)),
+    control_flow => control_flow,
+  }
+}
+
+/// Events sent to the event loop from other threads, or from browser event
+/// listeners.
+#[derive(Debug, Clone)]
+enum UserEvent {
+  /// `config.toml` was written to.
+  #[cfg(feature = "watch-config")]
+  ConfigChanged,
+  /// The browser window changed size, so the canvas might have to.
+  #[cfg(target_arch = "wasm32")]
+  BrowserResized,
+  /// The pointer was locked to the canvas or let go of.
+  #[cfg(target_arch = "wasm32")]
+  PointerLockChanged,
+  /// A gamepad was connected or disconnected.
+  #[cfg(target_arch = "wasm32")]
+  GamepadsChanged,
+  /// The user dropped or picked an image to use as the diffuse texture.
+  #[cfg(target_arch = "wasm32")]
+  TextureFileChosen(web_sys::File),
+  /// The browser took the canvas's WebGL context away.
+  #[cfg(target_arch = "wasm32")]
+  ContextLost,
+  /// The browser gave the context back, everything on it has to be created
+  /// again.
+  #[cfg(target_arch = "wasm32")]
+  ContextRestored,
+  /// `recreate_state` is done.
+  #[cfg(target_arch = "wasm32")]
+  StateRecreated,
+}
+
+/// What `State::input` did with an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct InputResponse {
+  /// The event was handled and shouldn't be acted on again.
+  consumed: bool,
+  /// What's on screen changed, so on-demand mode should draw a new frame.
+  redraw: bool,
+}
+
+impl InputResponse {
+  const IGNORED: Self = Self {
+    consumed: false,
+    redraw: false,
+  };
+  const CONSUMED: Self = Self {
+    consumed: true,
+    redraw: false,
+  };
+  const REDRAW: Self = Self {
+    consumed: true,
+    redraw: true,
+  };
+}
+
+/// What `State` draws into.
+enum Target<'a> {
+  Window(&'a Window),
+  /// An offscreen texture this big, see `headless`.
+  #[cfg(not(target_arch = "wasm32"))]
+  Headless(winit::dpi::PhysicalSize<u32>),
+}
+
+/// Where finished frames go.
+enum Output {
+  Surface(wgpu::Surface),
+  #[cfg(not(target_arch = "wasm32"))]
+  Offscreen(headless::OffscreenTarget),
+}
+
+/// Draws into the scene pass after the scene, see `State::set_render_callback`.
+pub type RenderCallback = dyn for<'a> FnMut(&mut wgpu::RenderPass<'a>, &'a State);
+
+/// Runs every update with the seconds since the last one, see
+/// `State::set_update_callback`.
+pub type UpdateCallback = dyn FnMut(&mut State, f32);
+
+/// The GPU context and everything drawn with it, created and driven by
+/// `run`.
+pub struct State {
+  /// What the device was created on, to tell whether F4 switched GPUs.
+  adapter_info: wgpu::AdapterInfo,
+  app_config: Config,
+  background: Option<Background>,
+  benchmark: bool,
   camera: Camera,
-  camera_bind_group: wgpu::BindGroup,
-  camera_buffer: wgpu::Buffer,
+  camera_binding: CameraBinding,
+  bind_group_layouts: BindGroupLayoutCache,
+  camera_bind_group_layout: std::rc::Rc<wgpu::BindGroupLayout>,
   camera_controller: CameraController,
-  camera_uniform: CameraUniform,
+  /// When `now` starts counting with a fixed time step.
+  clock_start: instant::Instant,
   config: wgpu::SurfaceConfiguration,
+  /// What `config.toml` held when it was last read.
+  #[cfg(feature = "watch-config")]
+  config_file: config_file::ConfigFile,
+  /// Nothing is drawn until the browser restores the WebGL context.
+  #[cfg(target_arch = "wasm32")]
+  context_lost: bool,
+  /// Mouse look is on, see `set_cursor_grab`.
+  cursor_grabbed: bool,
+  cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+  cursor_visible: bool,
+  /// `None` until it's loaded, see `upload_loaded_textures`.
+  diffuse_texture: Option<LodTexture>,
   device: wgpu::Device,
-  diffuse_bind_group: wgpu::BindGroup,
-  index_buffer: wgpu::Buffer,
-  num_indices: u32,
+  #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+  device_loss: DeviceLoss,
+  dirty: bool,
+  /// State changes in the last frame's scene pass.
+  draw_stats: std::cell::Cell<DrawStats>,
+  dynamic_resolution: Option<DynamicResolution>,
+  environment: Environment,
+  focused: bool,
+  frame_bench: Option<FrameBench>,
+  frame_cap: Option<u32>,
+  /// The passes after the shadow pass, with the targets only they use.
+  frame_graph: RenderGraph<State>,
+  frame_timer: FrameTimer,
+  frames_updated: u32,
+  /// A gamepad is polled every frame, see `poll_gamepad`.
+  #[cfg(target_arch = "wasm32")]
+  gamepad_connected: bool,
+  #[cfg(feature = "ffmpeg")]
+  ffmpeg_available: bool,
+  #[cfg(not(target_arch = "wasm32"))]
+  gif_recorder: Option<GifRecorder>,
+  keyboard: KeyboardState,
+  ground_instance_buffer: wgpu::Buffer,
+  ground_material: Material,
+  ground_mesh: Mesh,
+  /// The diffuse texture quality bound, `None` for the placeholder.
+  ground_quality: Option<Quality>,
+  instance_buffer: wgpu::Buffer,
+  /// What every instance is drawn as.
+  instance_mesh: Mesh,
+  instances: Vec<Instance>,
+  /// Bounds each instance for frustum culling.
+  instance_radius: f32,
+  /// Kept to load models dropped onto the window and rebuild the picking
+  /// pipelines.
+  joint_bind_group_layout: wgpu::BindGroupLayout,
+  last_update: instant::Instant,
+  light: DirectionalLight,
+  /// Whether what's loaded at startup has been uploaded. Until then frames
+  /// only show `encode_loading`'s pulse rather than a half-textured scene.
+  loaded: bool,
+  material: Material,
+  material_bind_group_layout: wgpu::BindGroupLayout,
+  material_quality: Option<Quality>,
+  /// Anisotropic taps the adapter's samplers can take, 1 for none.
+  max_anisotropy: u8,
+  /// What X cycles through, see `msaa::supported`.
+  msaa_sample_counts: Vec<u32>,
+  /// `None` where compute shaders aren't supported. J shows it, Shift+J
+  /// toggles whether it tiles.
+  noise: Option<NoiseTexture>,
+  noise_preview: bool,
+  noise_seed: u32,
+  /// The texture has to be generated again before it's next drawn.
+  noise_stale: bool,
+  noise_tileable: bool,
+  /// See `Config::obj`.
+  obj_model: Option<Model>,
+  obj_instance_buffer: wgpu::Buffer,
+  outline: Outline,
+  output: Output,
+  /// The browser locked the pointer to the canvas, which mouse look waits
+  /// for.
+  #[cfg(target_arch = "wasm32")]
+  pointer_locked: bool,
+  picker: Picker,
   queue: wgpu::Queue,
+  #[cfg(feature = "record")]
+  recorder: Option<input_recording::Recorder>,
+  render_scale: f32,
+  /// See `set_render_callback`.
+  render_callback: std::cell::RefCell<Option<Box<RenderCallback>>>,
   render_pipeline: wgpu::RenderPipeline,
+  render_pipeline_layout: wgpu::PipelineLayout,
+  #[cfg(feature = "record")]
+  replay: Option<input_recording::Replay>,
+  /// Physical pixels per logical pixel of the display, `devicePixelRatio` on
+  /// the web. Pixel sizes like the outline's are multiplied by it.
+  scale_factor: f64,
+  screenshot: Screenshot,
+  screenshot_requested: bool,
+  scissor_demo: ScissorDemo,
+  selected: Option<usize>,
+  skin_paused: bool,
+  show_scissor_demo: bool,
+  shadow_map: ShadowMap,
   size: winit::dpi::PhysicalSize<u32>,
-  surface: wgpu::Surface,
-  vertex_buffer: wgpu::Buffer,
+  split_screen: bool,
+  split_view: SplitView,
+  skinned_instance: Instance,
+  skinned_instance_buffer: wgpu::Buffer,
+  skinned_model: SkinnedModel,
+  skinned_pipeline: wgpu::RenderPipeline,
+  skinned_pipeline_layout: wgpu::PipelineLayout,
+  texture_loader: TextureLoader,
+  turntable: bool,
+  /// See `set_update_callback`.
+  update_callback: Option<Box<UpdateCallback>>,
+  upscaler: Upscaler,
+  vertex_gradient: bool,
+  /// What the scene pass draws of `instances`, with `visible_instances` the
+  /// index of each in `instances`. The shadow and picking passes still draw
+  /// them all from `instance_buffer`.
+  visible_instance_buffer: wgpu::Buffer,
+  visible_instances: Vec<usize>,
+  /// Set while F9 records a video.
+  #[cfg(feature = "ffmpeg")]
+  video_recorder: Option<video_recording::VideoRecorder>,
+  white_texture: texture::Texture,
 }
 
 impl State {
-  async fn new(window: &Window) -> Self {
-    let size = window.inner_size();
-
-    // The instance is a handle to our GPU
-    // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
-    let instance = wgpu::Instance::new(wgpu::Backends::all());
-    let surface = unsafe { instance.create_surface(window) };
-    let adapter = instance
-      .request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::default(),
-        compatible_surface: Some(&surface),
-        force_fallback_adapter: false,
-      })
-      .await
-      .unwrap();
+  /// Fails if there's no adapter at all for `target`, it lacks the features
+  /// `app_config` asks for, or it can't create a device.
+  async fn new(target: Target<'_>, mut app_config: Config) -> Result<Self, Error> {
+    let (size, scale_factor) = match target {
+      Target::Window(window) => (window.inner_size(), window.scale_factor()),
+      #[cfg(not(target_arch = "wasm32"))]
+      Target::Headless(size) => (size, 1.0),
+    };
+    let (adapter, surface) = request_adapter(&target, &app_config).await?;
+    let missing_features = app_config.features - adapter.features();
+    if !missing_features.is_empty() {
+      return Err(Error::MissingFeatures {
+        adapter: adapter::describe(&adapter.get_info()),
+        missing: missing_features,
+      });
+    }
 
     let (device, queue) = adapter
       .request_device(
         &wgpu::DeviceDescriptor {
-          features: wgpu::Features::empty(),
+          features: app_config.features,
           // WebGL doesn't support all of wgpu's features...
           limits: if cfg!(target_arch = "wasm32") {
             wgpu::Limits::downlevel_webgl2_defaults()
@@ -143,346 +640,3257 @@ impl State {
         },
         None, // Trace path
       )
-      .await
-      .unwrap();
+      .await?;
+    let device_loss = DeviceLoss::watch(&device);
 
+    let format = match &surface {
+      Some(surface) => choose_surface_format(
+        surface,
+        &adapter,
+        &app_config.surface_formats,
+        app_config.color_space,
+      )
+      .ok_or_else(|| Error::SurfaceUnsupported(adapter::describe(&adapter.get_info())))?,
+      #[cfg(not(target_arch = "wasm32"))]
+      None => headless::format(app_config.color_space),
+      #[cfg(target_arch = "wasm32")]
+      None => unreachable!("The web always draws to a canvas"),
+    };
+    if let Some(hex) = app_config.clear_color_hex.clone() {
+      app_config.set_clear_color_hex(&hex, format);
+    }
+
+    let surface_size = clamp_surface_size(size, device.limits().max_texture_dimension_2d);
     let config = wgpu::SurfaceConfiguration {
       usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-      format: surface.get_preferred_format(&adapter).unwrap(),
-      width: size.width,
-      height: size.height,
-      present_mode: wgpu::PresentMode::Fifo,
+      format,
+      width: surface_size.width,
+      height: surface_size.height,
+      // Benchmarks shouldn't wait for vsync.
+      present_mode: present_mode(
+        app_config.vsync && app_config.bench_frames.is_none(),
+        app_config.tearing,
+      ),
     };
-    surface.configure(&device, &config);
+    adapter::log_capabilities(
+      &adapter,
+      &device,
+      &config,
+      adapter::alpha_mode(app_config.alpha_mode),
+      app_config.verbose_capabilities,
+    );
+    let msaa_sample_counts = msaa::supported(&adapter, config.format);
+    let max_anisotropy = texture::max_anisotropy(&adapter);
+    app_config.anisotropy = clamp_anisotropy(app_config.anisotropy, max_anisotropy);
+    app_config.msaa_samples = msaa::clamp(app_config.msaa_samples, &msaa_sample_counts);
+    let output = match surface {
+      Some(surface) => {
+        surface.configure(&device, &config);
+        Output::Surface(surface)
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      None => Output::Offscreen(headless::OffscreenTarget::new(&device, &config)),
+      #[cfg(target_arch = "wasm32")]
+      None => unreachable!("The web always draws to a canvas"),
+    };
+    let clock_start = instant::Instant::now();
 
-    let camera_controller = CameraController::new(0.2);
+    let camera_controller =
+      CameraController::new(app_config.camera_speed, app_config.mouse_sensitivity);
 
-    let diffuse_bytes = include_bytes!("../happy-tree.png");
+    // Decoding the diffuse texture would hold up the first frame, so draw
+    // with a placeholder until it's ready, see `upload_loaded_textures`.
+    let mut texture_loader = TextureLoader::new();
+    texture_loader.load(DIFFUSE_TEXTURE_PATH);
     let diffuse_texture =
-      texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png").unwrap();
-
-    let texture_bind_group_layout =
-      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[
-          wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            ty: wgpu::BindingType::Texture {
-              multisampled: false,
-              view_dimension: wgpu::TextureViewDimension::D2,
-              sample_type: wgpu::TextureSampleType::Float { filterable: true },
-            },
-            count: None,
-          },
-          wgpu::BindGroupLayoutEntry {
-            binding: 1,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            // This should match the filterable field of the corresponding Texture entry above.
-            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-            count: None,
-          },
-        ],
-        label: Some("texture_bind_group_layout"),
-      });
+      texture::Texture::from_color(&device, &queue, [128, 128, 128, 255], "placeholder");
 
-    let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-      layout: &texture_bind_group_layout,
-      entries: &[
-        wgpu::BindGroupEntry {
-          binding: 0,
-          resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-        },
-        wgpu::BindGroupEntry {
-          binding: 1,
-          resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-        },
-      ],
-      label: Some("diffuse_bind_group"),
-    });
+    let white_texture = texture::Texture::from_color(&device, &queue, [255; 4], "white");
+
+    let material_bind_group_layout = Material::bind_group_layout(&device);
+    let material = Material::new(
+      &device,
+      &material_bind_group_layout,
+      &diffuse_texture,
+      &white_texture,
+      MaterialParams {
+        metallic: 0.0,
+        roughness: 0.4,
+        reflectivity: 0.2,
+        ..Default::default()
+      },
+      "material",
+    );
+    let ground_material = Material::new(
+      &device,
+      &material_bind_group_layout,
+      &diffuse_texture,
+      &white_texture,
+      MaterialParams {
+        roughness: 0.9,
+        ..Default::default()
+      },
+      "ground_material",
+    );
+
+    let environment = Environment::new(&device, &queue);
 
+    validate_depth_setup(app_config.depth_clear, app_config.depth_compare);
     let camera = Camera {
-      eye: (0.0, 1.0, 2.0).into(),
-      target: (0.0, 0.0, 0.0).into(),
+      eye: CAMERA_HOME_EYE,
+      target: CAMERA_HOME_TARGET,
       up: cgmath::Vector3::unit_y(),
       aspect: config.width as f32 / config.height as f32,
       fovy: 45.0,
       znear: 0.1,
       zfar: 100.0,
+      reverse_z: is_reverse_z(app_config.depth_compare),
     };
 
-    let mut camera_uniform = CameraUniform::new();
-    camera_uniform.update_view_proj(&camera);
+    // buffers
+    let instance_mesh = Mesh::new(&device, "Instance", VERTICES, INDICES);
 
-    let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-      label: Some("Camera Buffer"),
-      contents: bytemuck::cast_slice(&[camera_uniform]),
-      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-    });
+    let instances = (0..NUM_INSTANCES_PER_ROW)
+      .flat_map(|z| {
+        (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+          let offset = (NUM_INSTANCES_PER_ROW - 1) as f32 * 0.5;
+          let position = cgmath::Vector3 {
+            x: (x as f32 - offset) * INSTANCE_SPACING,
+            y: 0.0,
+            z: (z as f32 - offset) * INSTANCE_SPACING,
+          };
+          let index = z * NUM_INSTANCES_PER_ROW + x;
+          let hue = index as f32 / (NUM_INSTANCES_PER_ROW * NUM_INSTANCES_PER_ROW) as f32;
+          Instance {
+            position,
+            rotation: cgmath::Quaternion::one(),
+            tint: rainbow(hue),
+          }
+        })
+      })
+      .collect::<Vec<_>>();
 
-    // buffers
-    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-      label: Some("Vertex Buffer"),
-      contents: bytemuck::cast_slice(VERTICES),
+    let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+    let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Instance Buffer"),
+      contents: bytemuck::cast_slice(&instance_data),
       usage: wgpu::BufferUsages::VERTEX,
     });
+    let visible_instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Visible Instance Buffer"),
+      contents: bytemuck::cast_slice(&instance_data),
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    let visible_instances = (0..instances.len()).collect();
+
+    let (ground_vertices, ground_indices) = ground_mesh(&app_config);
+    let ground_mesh = Mesh::new(&device, "Ground", &ground_vertices, &ground_indices);
 
-    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-      label: Some("Index Buffer"),
-      contents: bytemuck::cast_slice(INDICES),
-      usage: wgpu::BufferUsages::INDEX,
+    let ground_instance = Instance {
+      position: cgmath::Vector3::zero(),
+      rotation: cgmath::Quaternion::one(),
+      tint: [1.0; 4],
+    };
+    let ground_instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Ground Instance Buffer"),
+      contents: bytemuck::cast_slice(&[ground_instance.to_raw()]),
+      usage: wgpu::BufferUsages::VERTEX,
     });
 
-    let camera_bind_group_layout =
-      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        entries: &[wgpu::BindGroupLayoutEntry {
-          binding: 0,
-          visibility: wgpu::ShaderStages::VERTEX,
-          ty: wgpu::BindingType::Buffer {
-            ty: wgpu::BufferBindingType::Uniform,
-            has_dynamic_offset: false,
-            min_binding_size: None,
-          },
-          count: None,
-        }],
-        label: Some("camera_bind_group_layout"),
-      });
+    // Everything the scene pass draws into is sized by the render scale.
+    let render_scale = app_config
+      .render_scale
+      .clamp(upscale::MIN_RENDER_SCALE, upscale::MAX_RENDER_SCALE);
+    let scene_config = upscale::scaled_config(&config, render_scale);
+    let mut frame_graph = frame_graph(config.format, app_config.msaa_samples);
+    frame_graph
+      .resize(&device, scene_config.width, scene_config.height)
+      .expect("The surface size was clamped to the device's limits");
+    let upscaler = Upscaler::new(&device, &config, &scene_config);
+    let noise = NoiseTexture::new(&adapter, &device, config.format);
+    let dynamic_resolution = app_config.target_frame_time.map(DynamicResolution::new);
 
-    let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-      label: Some("camera_bind_group"),
-      layout: &camera_bind_group_layout,
-      entries: &[wgpu::BindGroupEntry {
-        binding: 0,
-        resource: camera_buffer.as_entire_binding(),
-      }],
+    #[cfg(feature = "record")]
+    let recorder = app_config.record_input.as_deref().and_then(|path| {
+      input_recording::Recorder::create(path)
+        .map_err(|e| log::error!("Couldn't record input to {}: {:?}", path, e))
+        .ok()
+    });
+    #[cfg(feature = "record")]
+    let replay = app_config.replay_input.as_deref().and_then(|path| {
+      input_recording::Replay::load(path)
+        .map_err(|e| log::error!("Couldn't replay input from {}: {:?}", path, e))
+        .ok()
+    });
+    let background = app_config.background.as_deref().and_then(|path| {
+      let background = load_background(path).and_then(|bytes| {
+        Background::new(
+          &device,
+          &queue,
+          &scene_config,
+          &bytes,
+          app_config.background_fit,
+          app_config.msaa_samples,
+        )
+      });
+      match background {
+        Ok(background) => Some(background),
+        Err(e) => {
+          log::warn!("Failed to load background {}: {:?}", path, e);
+          None
+        }
+      }
     });
+    let screenshot = Screenshot::new(&device, &scene_config);
+    #[cfg(feature = "ffmpeg")]
+    let ffmpeg_available = video_recording::ffmpeg_available();
+    #[cfg(feature = "ffmpeg")]
+    if !ffmpeg_available {
+      log::warn!("ffmpeg isn't on the PATH, F9 can't record video");
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let gif_recorder = app_config
+      .gif_seconds
+      .map(|seconds| GifRecorder::new(seconds, app_config.gif_fps, app_config.gif_scale));
 
-    let num_indices = INDICES.len() as u32;
+    let light = DirectionalLight {
+      position: (2.0, 4.0, 3.0).into(),
+      target: (0.0, 0.0, 0.0).into(),
+      color: (1.0, 1.0, 1.0).into(),
+    };
+    // The camera, light and outline layouts are all a single uniform, so
+    // they end up sharing one.
+    let mut bind_group_layouts = BindGroupLayoutCache::new();
+    let shadow_map = ShadowMap::new(
+      &device,
+      &mut bind_group_layouts,
+      &light,
+      &[Vertex::desc(), InstanceRaw::desc()],
+    );
+
+    let camera_bind_group_layout = bind_group_layouts.get(
+      &device,
+      &[CameraBinding::layout_entry()],
+      "camera_bind_group_layout",
+    );
+    let camera_binding = CameraBinding::new(&device, &camera_bind_group_layout, &camera, "Camera");
 
-    let shader = device.create_shader_module(&include_wgsl!("shader.wgsl"));
+    let split_view = SplitView::new(
+      &device,
+      &camera_bind_group_layout,
+      Camera {
+        eye: (3.5, 1.5, 0.0).into(),
+        target: (0.0, 0.0, 0.0).into(),
+        up: cgmath::Vector3::unit_y(),
+        aspect: config.width as f32 / config.height as f32,
+        fovy: 45.0,
+        znear: 0.1,
+        zfar: 100.0,
+        reverse_z: is_reverse_z(app_config.depth_compare),
+      },
+    );
 
-    let render_pipline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
       label: Some("Render Pipeline Layout"),
-      bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+      bind_group_layouts: &[
+        &material_bind_group_layout,
+        &camera_bind_group_layout,
+        &shadow_map.bind_group_layout,
+        &environment.bind_group_layout,
+      ],
       push_constant_ranges: &[],
     });
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-      label: Some("Render Pipeline"),
-      layout: Some(&render_pipline_layout),
-      vertex: wgpu::VertexState {
-        module: &shader,
-        entry_point: "vs_main",
-        buffers: &[Vertex::desc()],
-      },
-      fragment: Some(wgpu::FragmentState {
-        module: &shader,
-        entry_point: "fs_main",
-        targets: &[wgpu::ColorTargetState {
-          format: config.format,
-          blend: Some(wgpu::BlendState::REPLACE),
-          write_mask: wgpu::ColorWrites::ALL,
-        }],
-      }),
-      primitive: wgpu::PrimitiveState {
-        topology: wgpu::PrimitiveTopology::TriangleList,
-        strip_index_format: None,
-        front_face: wgpu::FrontFace::Ccw,
-        cull_mode: Some(wgpu::Face::Back),
-        polygon_mode: wgpu::PolygonMode::Fill,
-        unclipped_depth: false,
-        conservative: false,
-      },
-      depth_stencil: None,
-      multisample: wgpu::MultisampleState {
-        count: 1,
-        mask: !0,
-        alpha_to_coverage_enabled: false,
-      },
-      multiview: None,
+    let joint_bind_group_layout = SkinnedModel::joint_bind_group_layout(&device);
+    let skinned_model = app_config
+      .model
+      .as_deref()
+      .and_then(|path| {
+        let model = std::fs::read(path)
+          .map_err(anyhow::Error::from)
+          .and_then(|bytes| SkinnedModel::from_glb(&device, &joint_bind_group_layout, &bytes));
+        match model {
+          Ok(model) => Some(model),
+          Err(e) => {
+            log::warn!("Failed to load model {}: {:?}", path, e);
+            None
+          }
+        }
+      })
+      .unwrap_or_else(|| {
+        SkinnedModel::from_glb(
+          &device,
+          &joint_bind_group_layout,
+          include_bytes!("../simple-skin.glb"),
+        )
+        .unwrap()
+      });
+    let skinned_instance = Instance {
+      position: (0.0, -0.5, -2.2).into(),
+      rotation: cgmath::Quaternion::one(),
+      tint: [1.0; 4],
+    };
+    let skinned_instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Skinned Instance Buffer"),
+      contents: bytemuck::cast_slice(&[skinned_instance.to_raw()]),
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
     });
 
-    Self {
+    let obj_model = app_config.obj.as_deref().and_then(|path| {
+      match Model::load(
+        &device,
+        &queue,
+        &material_bind_group_layout,
+        std::path::Path::new(path),
+      ) {
+        Ok(model) => Some(model),
+        Err(e) => {
+          log::warn!("Failed to load model {}: {:?}", path, e);
+          None
+        }
+      }
+    });
+    let obj_instance = Instance {
+      position: OBJ_MODEL_POSITION,
+      rotation: cgmath::Quaternion::one(),
+      tint: [1.0; 4],
+    };
+    let obj_instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("OBJ Instance Buffer"),
+      contents: bytemuck::cast_slice(&[obj_instance.to_raw()]),
+      usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let skinned_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Skinned Pipeline Layout"),
+      bind_group_layouts: &[
+        &material_bind_group_layout,
+        &camera_bind_group_layout,
+        &shadow_map.bind_group_layout,
+        &joint_bind_group_layout,
+      ],
+      push_constant_ranges: &[],
+    });
+    let (render_pipeline, skinned_pipeline) = pipeline::create_scene_pipelines(
+      &device,
+      &render_pipeline_layout,
+      &skinned_pipeline_layout,
+      config.format,
+      &app_config,
+    )?;
+
+    let outline = Outline::new(
+      &device,
+      &mut bind_group_layouts,
+      &scene_config,
+      &camera_bind_group_layout,
+      &[Vertex::desc(), InstanceRaw::desc()],
+      &app_config,
+      scale_factor,
+    );
+
+    let scissor_demo = ScissorDemo::new(&device);
+
+    let picker = Picker::new(
+      &device,
+      &config,
+      &camera_bind_group_layout,
+      &joint_bind_group_layout,
+      &app_config,
+    )?;
+
+    Ok(Self {
+      adapter_info: adapter.get_info(),
+      frame_bench: app_config.bench_frames.map(FrameBench::new),
+      frame_cap: app_config.frame_cap,
+      frame_graph,
+      #[cfg(feature = "record")]
+      recorder,
+      #[cfg(feature = "record")]
+      replay,
+      app_config,
+      background,
+      benchmark: false,
       camera,
-      camera_bind_group,
-      camera_buffer,
+      camera_binding,
+      bind_group_layouts,
+      camera_bind_group_layout,
       camera_controller,
-      camera_uniform,
+      clock_start,
       config,
+      #[cfg(feature = "watch-config")]
+      config_file: Default::default(),
+      #[cfg(target_arch = "wasm32")]
+      context_lost: false,
+      cursor_grabbed: false,
+      cursor_position: None,
+      cursor_visible: true,
+      diffuse_texture: None,
       device,
-      diffuse_bind_group,
-      index_buffer,
-      num_indices,
+      device_loss,
+      dirty: true,
+      draw_stats: Default::default(),
+      dynamic_resolution,
+      environment,
+      focused: true,
+      frame_timer: FrameTimer::new(clock_start),
+      frames_updated: 0,
+      #[cfg(target_arch = "wasm32")]
+      gamepad_connected: false,
+      #[cfg(feature = "ffmpeg")]
+      ffmpeg_available,
+      #[cfg(not(target_arch = "wasm32"))]
+      gif_recorder,
+      keyboard: KeyboardState::new(),
+      ground_instance_buffer,
+      ground_material,
+      ground_mesh,
+      ground_quality: None,
+      instance_buffer,
+      instance_mesh,
+      instances,
+      instance_radius: culling::bounding_radius(VERTICES),
+      joint_bind_group_layout,
+      last_update: clock_start,
+      light,
+      loaded: false,
+      material,
+      material_bind_group_layout,
+      material_quality: None,
+      max_anisotropy,
+      msaa_sample_counts,
+      noise,
+      noise_preview: false,
+      noise_seed: 0,
+      noise_stale: true,
+      noise_tileable: true,
+      obj_model,
+      obj_instance_buffer,
+      outline,
+      output,
+      #[cfg(target_arch = "wasm32")]
+      pointer_locked: false,
+      picker,
       queue,
+      render_scale,
+      render_callback: Default::default(),
       render_pipeline,
+      render_pipeline_layout,
+      scale_factor,
+      screenshot,
+      screenshot_requested: false,
+      scissor_demo,
+      selected: None,
+      skin_paused: false,
+      show_scissor_demo: false,
+      shadow_map,
       size,
-      surface,
-      vertex_buffer,
-    }
+      split_screen: false,
+      split_view,
+      skinned_instance,
+      skinned_instance_buffer,
+      skinned_model,
+      skinned_pipeline,
+      skinned_pipeline_layout,
+      texture_loader,
+      turntable: false,
+      update_callback: None,
+      upscaler,
+      vertex_gradient: false,
+      visible_instance_buffer,
+      visible_instances,
+      #[cfg(feature = "ffmpeg")]
+      video_recorder: None,
+      white_texture,
+    })
   }
 
-  fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-    if new_size.width > 0 && new_size.height > 0 {
-      self.size = new_size;
-      self.config.width = new_size.width;
-      self.config.height = new_size.height;
-      self.surface.configure(&self.device, &self.config);
+  /// Blocks until the GPU has finished everything submitted, so resources
+  /// about to be replaced aren't dropped while it still uses them. Only
+  /// native builds can block. The browser keeps resources alive for work in
+  /// flight itself, code there that must wait should await
+  /// `queue.on_submitted_work_done()` and so yield to it instead.
+  pub fn wait_idle(&self) {
+    #[cfg(not(target_arch = "wasm32"))]
+    self.device.poll(wgpu::Maintain::Wait);
+  }
+
+  /// Applies `config` to whatever frames are drawn into.
+  fn configure_output(&mut self) {
+    self.wait_idle();
+    match &mut self.output {
+      Output::Surface(surface) => surface.configure(&self.device, &self.config),
+      #[cfg(not(target_arch = "wasm32"))]
+      Output::Offscreen(target) => {
+        *target = headless::OffscreenTarget::new(&self.device, &self.config)
+      }
     }
   }
 
-  fn input(&mut self, event: &WindowEvent) -> bool {
-    self.camera_controller.process_events(event)
+  /// The device everything is created on, to create more on. `Device` is
+  /// `Send + Sync` natively, so it can be shared with other threads, see
+  /// `encode_shadow_pass`. On the web it's tied to the page's thread.
+  pub fn device(&self) -> &wgpu::Device {
+    &self.device
   }
 
-  fn update(&mut self) {
-    self.camera_controller.update_camera(&mut self.camera);
-    self.camera_uniform.update_view_proj(&self.camera);
-    self.queue.write_buffer(
-      &self.camera_buffer,
-      0,
-      bytemuck::cast_slice(&[self.camera_uniform]),
-    );
+  /// The queue the frames are submitted to. Like `device`, `Send + Sync`
+  /// natively, submissions from other threads are ordered by when they
+  /// happen.
+  pub fn queue(&self) -> &wgpu::Queue {
+    &self.queue
   }
 
-  fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-    let output = self.surface.get_current_texture()?;
+  /// The adapter the device was created on. Its backend and device type
+  /// tell which of `request_adapter`'s attempts found it, `Cpu` for the
+  /// fallback adapter.
+  pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+    &self.adapter_info
+  }
 
-    let view = output
-      .texture
-      .create_view(&wgpu::TextureViewDescriptor::default());
+  /// Multisample count of the scene pass.
+  pub fn msaa_samples(&self) -> u32 {
+    self.app_config.msaa_samples
+  }
 
-    let mut encoder = self
-      .device
-      .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-        label: Some("Render Encoder"),
-      });
+  /// Calls `callback` in the scene pass every frame, after the scene is
+  /// drawn, with the viewport and scissor covering all of it. Pipelines it
+  /// sets have to match the pass: `surface_config().format` with
+  /// `msaa_samples()` samples and a `Depth24PlusStencil8` depth-stencil
+  /// attachment.
+  ///
+  /// The pass borrows whatever is bound to it for as long as it lasts, so
+  /// the callback can bind what `State` owns, but not what it captured,
+  /// which is only borrowed for the call. Resources it creates once and
+  /// binds every frame can be leaked to `&'static`. The callback is dropped
+  /// when the device is recreated, resources made on the old one would be
+  /// invalid anyway.
+  pub fn set_render_callback(
+    &mut self,
+    callback: impl for<'a> FnMut(&mut wgpu::RenderPass<'a>, &'a State) + 'static,
+  ) {
+    *self.render_callback.get_mut() = Some(Box::new(callback));
+  }
 
-    {
-      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: Some("Render Pass"),
-        color_attachments: &[wgpu::RenderPassColorAttachment {
-          view: &view,
-          resolve_target: None,
-          ops: wgpu::Operations {
-            load: wgpu::LoadOp::Clear(wgpu::Color {
-              r: 0.1,
-              g: 0.1,
-              b: 0.1,
-              a: 1.0,
-            }),
-            store: true,
-          },
-        }],
-        depth_stencil_attachment: None,
-      });
-      render_pass.set_pipeline(&self.render_pipeline);
-      render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-      render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-      render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-      render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-      render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-    }
+  /// Calls `callback` at the end of every update, unless the viewer is
+  /// paused, with the seconds since the last one. In on-demand redraw mode
+  /// updates only happen for frames drawn anyway. The callback may set
+  /// another one, which replaces it from the next update on. Like the
+  /// render callback it's dropped when the device is recreated.
+  pub fn set_update_callback(&mut self, callback: impl FnMut(&mut State, f32) + 'static) {
+    self.update_callback = Some(Box::new(callback));
+  }
 
-    // submit will accept anyting that implments IntoIter
-    self.queue.submit(std::iter::once(encoder.finish()));
-    output.present();
+  /// Whether `key` is held down.
+  pub fn is_key_held(&self, key: VirtualKeyCode) -> bool {
+    self.keyboard.is_held(key)
+  }
 
-    Ok(())
+  /// Whether `key` went down since the last update, not counting the
+  /// operating system's repeats.
+  pub fn was_key_pressed(&self, key: VirtualKeyCode) -> bool {
+    self.keyboard.was_pressed_this_frame(key)
   }
-}
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-  position: [f32; 3],
-  tex_coords: [f32; 2],
-}
+  /// The modifier keys held this frame.
+  pub fn modifiers(&self) -> ModifiersState {
+    self.keyboard.modifiers()
+  }
 
-impl Vertex {
-  const ATTRIBS: [wgpu::VertexAttribute; 2] =
-    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+  /// What the surface, or the offscreen target, is configured with.
+  pub fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
+    &self.config
+  }
 
-  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-    use std::mem;
+  /// Changes the surface configuration with `f` and configures the surface
+  /// with the result, resizing what depends on its size. Fails, keeping the
+  /// old configuration, if the result has another format, which every
+  /// pipeline was built for, or a size no texture can have, or can't be
+  /// rendered to. Any present mode is valid, wgpu falls back to Fifo.
+  pub fn reconfigure_surface(
+    &mut self,
+    f: impl FnOnce(&mut wgpu::SurfaceConfiguration),
+  ) -> anyhow::Result<()> {
+    let mut config = self.config.clone();
+    f(&mut config);
+    if config.format != self.config.format {
+      anyhow::bail!(
+        "The surface format can't change from {:?} to {:?} while running",
+        self.config.format,
+        config.format
+      );
+    }
+    let max_dimension = self.device.limits().max_texture_dimension_2d;
+    if config.width == 0
+      || config.height == 0
+      || config.width > max_dimension
+      || config.height > max_dimension
+    {
+      anyhow::bail!(
+        "The surface can't be {}x{}, each side has to be between 1 and {}",
+        config.width,
+        config.height,
+        max_dimension
+      );
+    }
+    if !config
+      .usage
+      .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+    {
+      anyhow::bail!("The surface has to be usable as a render attachment");
+    }
 
-    wgpu::VertexBufferLayout {
-      array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-      step_mode: wgpu::VertexStepMode::Vertex,
-      attributes: &Self::ATTRIBS,
+    let resized = (config.width, config.height) != (self.config.width, self.config.height);
+    if resized {
+      self.resize_scene_targets(&config, self.render_scale)?;
+    }
+    self.config = config;
+    self.configure_output();
+    if resized {
+      self.mark_dirty();
+      self.picker.resize(&self.device, &self.config);
+      self.update_camera_aspects();
     }
+    Ok(())
   }
-}
 
-const VERTICES: &[Vertex] = &[
-  Vertex {
-    position: [-0.0868241, 0.49240386, 0.0],
-    tex_coords: [0.4131759, 1.0 - 0.99240386],
-  }, // A
-  Vertex {
-    position: [-0.49513406, 0.06958647, 0.0],
-    tex_coords: [0.0048659444, 1.0 - 0.56958647],
-  }, // B
-  Vertex {
-    position: [-0.21918549, -0.44939706, 0.0],
-    tex_coords: [0.28081453, 1.0 - 0.05060294],
-  }, // C
-  Vertex {
-    position: [0.35966998, -0.3473291, 0.0],
-    tex_coords: [0.85967, 1.0 - 0.1526709],
-  }, // D
-  Vertex {
-    position: [0.44147372, 0.2347359, 0.0],
-    tex_coords: [0.9414737, 1.0 - 0.7347359],
-  }, // E
-];
+  fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    if new_size.width > 0 && new_size.height > 0 {
+      let surface_size =
+        clamp_surface_size(new_size, self.device.limits().max_texture_dimension_2d);
+      let mut config = self.config.clone();
+      config.width = surface_size.width;
+      config.height = surface_size.height;
+      if let Err(e) = self.resize_scene_targets(&config, self.render_scale) {
+        log::error!("Keeping the previous size: {:?}", e);
+        return;
+      }
+      self.size = new_size;
+      self.config = config;
+      self.mark_dirty();
+      self.configure_output();
+      self.picker.resize(&self.device, &self.config);
+      self.update_camera_aspects();
+    }
+  }
 
-const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+  /// Size of the scene targets, which is the window size times the render
+  /// scale.
+  fn scene_size(&self) -> (u32, u32) {
+    let scene_config = upscale::scaled_config(&self.config, self.render_scale);
+    (scene_config.width, scene_config.height)
+  }
+
+  /// Matches the cameras to whatever part of the window they're shown in.
+  fn update_camera_aspects(&mut self) {
+    let (width, height) = self.scene_size();
+    if self.split_screen {
+      let left = width / 2;
+      self.camera.aspect = left as f32 / height as f32;
+      self.split_view.camera.aspect = (width - left) as f32 / height as f32;
+    } else {
+      self.camera.aspect = width as f32 / height as f32;
+    }
+  }
 
-#[rustfmt::skip]
-pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.0,
-    0.0, 0.0, 0.5, 1.0,
-);
+  /// Recreates everything sized by the scene resolution for a surface
+  /// configured with `config` and rendered at `render_scale`, before either
+  /// changes. The frame graph's targets go first, if they can't be created
+  /// nothing changes and the caller keeps the previous size.
+  fn resize_scene_targets(
+    &mut self,
+    config: &wgpu::SurfaceConfiguration,
+    render_scale: f32,
+  ) -> anyhow::Result<()> {
+    self.wait_idle();
+    let scene_config = upscale::scaled_config(config, render_scale);
+    self
+      .frame_graph
+      .resize(&self.device, scene_config.width, scene_config.height)?;
+    self.screenshot.resize(&self.device, &scene_config);
+    self.upscaler.resize(&self.device, &scene_config);
+    if let Some(background) = &self.background {
+      background.resize(&self.queue, &scene_config);
+    }
+    self
+      .outline
+      .resize(&self.queue, &scene_config, self.scale_factor);
+    Ok(())
+  }
 
-pub struct Camera {
-  pub eye: cgmath::Point3<f32>,
-  pub target: cgmath::Point3<f32>,
-  pub up: cgmath::Vector3<f32>,
-  pub aspect: f32,
-  pub fovy: f32,
-  pub znear: f32,
-  pub zfar: f32,
-}
+  /// Rebuilds the scene pipelines from `app_config`.
+  fn rebuild_scene_pipelines(&mut self) {
+    let (render_pipeline, skinned_pipeline) = pipeline::create_scene_pipelines(
+      &self.device,
+      &self.render_pipeline_layout,
+      &self.skinned_pipeline_layout,
+      self.config.format,
+      &self.app_config,
+    )
+    .expect("The scene shaders compiled at startup");
+    self.render_pipeline = render_pipeline;
+    self.skinned_pipeline = skinned_pipeline;
+  }
 
-impl Camera {
-  fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-    // 1.
-    let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-    // 2.
-    let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+  /// Recreates the outline from `app_config`, so it's drawn with the scene's
+  /// multisample count and culling.
+  fn rebuild_outline(&mut self) {
+    self.outline = Outline::new(
+      &self.device,
+      &mut self.bind_group_layouts,
+      &upscale::scaled_config(&self.config, self.render_scale),
+      &self.camera_bind_group_layout,
+      &[Vertex::desc(), InstanceRaw::desc()],
+      &self.app_config,
+      self.scale_factor,
+    );
+  }
 
-    // 3.
-    return OPENGL_TO_WGPU_MATRIX * proj * view;
+  /// Rebuilds the picking pipelines from `app_config`, so they cull like the
+  /// scene.
+  fn rebuild_picking_pipelines(&mut self) {
+    self
+      .picker
+      .rebuild_pipelines(
+        &self.device,
+        &self.camera_bind_group_layout,
+        &self.joint_bind_group_layout,
+        &self.app_config,
+      )
+      .expect("The picking shader compiled at startup");
   }
-}
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
-  // We can't use cgmath with bytemuck directly so convert the Matrix4 into a 4x4 f32 array
-  view_proj: [[f32; 4]; 4],
-}
+  /// Switches the scene between lit and shaded with its normals.
+  fn toggle_normals(&mut self) {
+    self.app_config.show_normals = !self.app_config.show_normals;
+    self.rebuild_scene_pipelines();
+    if self.app_config.show_normals {
+      log::info!("Shading: normals as colors");
+    } else {
+      log::info!("Shading: lit");
+    }
+  }
 
-impl CameraUniform {
-  fn new() -> Self {
-    use cgmath::SquareMatrix;
-    Self {
-      view_proj: cgmath::Matrix4::identity().into(),
+  /// Culls the back faces, then the front ones, then neither, or with
+  /// `flip_winding` keeps the culled face and swaps which winding is front.
+  fn cycle_culling(&mut self, flip_winding: bool) {
+    if flip_winding {
+      self.app_config.front_face = match self.app_config.front_face {
+        wgpu::FrontFace::Ccw => wgpu::FrontFace::Cw,
+        wgpu::FrontFace::Cw => wgpu::FrontFace::Ccw,
+      };
+    } else {
+      self.app_config.cull_mode = match self.app_config.cull_mode {
+        Some(wgpu::Face::Back) => Some(wgpu::Face::Front),
+        Some(wgpu::Face::Front) => None,
+        None => Some(wgpu::Face::Back),
+      };
+    }
+    self.rebuild_scene_pipelines();
+    self.rebuild_picking_pipelines();
+    self.rebuild_outline();
+    log::info!(
+      "Culling: {}, front faces wound {:?}",
+      match self.app_config.cull_mode {
+        Some(wgpu::Face::Back) => "back faces",
+        Some(wgpu::Face::Front) => "front faces",
+        None => "none",
+      },
+      self.app_config.front_face
+    );
+  }
+
+  /// Rebuilds everything that depends on the scene's multisample count.
+  /// `samples` is clamped to what's supported.
+  fn set_msaa_samples(&mut self, samples: u32) {
+    let samples = msaa::clamp(samples, &self.msaa_sample_counts);
+    if samples == self.app_config.msaa_samples {
+      return;
+    }
+    self.app_config.msaa_samples = samples;
+    self.rebuild_scene_pipelines();
+    self.rebuild_outline();
+    if let Some(background) = &mut self.background {
+      background.set_sample_count(&self.device, samples);
+    }
+    self.frame_graph = frame_graph(self.config.format, samples);
+    self
+      .resize_scene_targets(&self.config.clone(), self.render_scale)
+      .expect("The scene targets already had this size");
+    log::info!("MSAA samples: {}", samples);
+  }
+
+  /// Recreates the diffuse texture's samplers with `anisotropy` taps,
+  /// clamped to what's supported, and rebinds them.
+  fn set_anisotropy(&mut self, anisotropy: u8) {
+    let anisotropy = clamp_anisotropy(anisotropy, self.max_anisotropy);
+    if anisotropy == self.app_config.anisotropy {
+      return;
+    }
+    self.app_config.anisotropy = anisotropy;
+    if let Some(texture) = &mut self.diffuse_texture {
+      texture.set_anisotropy(&self.device, anisotropy);
+      self.material_quality = None;
+      self.ground_quality = None;
+    }
+    if anisotropy > 1 {
+      log::info!(
+        "Anisotropic filtering: {}x, the far ground stays sharp where 1x smears it",
+        anisotropy
+      );
+    } else {
+      log::info!("Anisotropic filtering: off");
+    }
+  }
+
+  #[cfg(feature = "watch-config")]
+  fn set_vsync(&mut self, vsync: bool) {
+    if vsync == self.app_config.vsync {
+      return;
+    }
+    self.app_config.vsync = vsync;
+    let present_mode = present_mode(vsync && !self.benchmark, self.app_config.tearing);
+    self
+      .reconfigure_surface(|config| config.present_mode = present_mode)
+      .expect("Any present mode is valid");
+    log::info!("Present mode: {:?}", self.config.present_mode);
+  }
+
+  /// Rereads `config.toml` and applies the settings that can change while
+  /// running. The others are logged and keep their old values.
+  #[cfg(feature = "watch-config")]
+  fn reload_config(&mut self) {
+    let config_file = match config_file::ConfigFile::load(config_file::CONFIG_PATH) {
+      Ok(config_file) => config_file,
+      Err(e) => {
+        log::error!("Couldn't reload the config: {:?}", e);
+        return;
+      }
+    };
+    if config_file == self.config_file {
+      return;
+    }
+    for name in config_file.restart_only_changes(&self.config_file) {
+      log::warn!(
+        "Ignoring the change to {}, it only applies after a restart",
+        name
+      );
+    }
+
+    if let Some(vsync) = config_file.vsync {
+      self.set_vsync(vsync);
+    }
+    if let Some(samples) = config_file.msaa_samples() {
+      self.set_msaa_samples(samples);
+    }
+    if let Some(anisotropy) = config_file.anisotropy() {
+      self.set_anisotropy(anisotropy);
+    }
+    if let Some(render_scale) = config_file.render_scale {
+      self.set_render_scale(render_scale);
+    }
+    if let Some(speed) = config_file.camera_speed {
+      self.app_config.camera_speed = speed;
+      self.camera_controller.speed = speed;
+    }
+    if let Some(sensitivity) = config_file.mouse_sensitivity {
+      self.app_config.mouse_sensitivity = sensitivity;
+      self.camera_controller.sensitivity = sensitivity;
+    }
+    if let Some(speed) = config_file.turntable_speed {
+      self.app_config.turntable_speed = speed;
+    }
+    if let Some(color) = config_file.clear_color() {
+      self.app_config.clear_color = color;
+    }
+    if let Some(hex) = &config_file.clear_color_hex {
+      self.app_config.set_clear_color_hex(hex, self.config.format);
+    }
+    self.config_file = config_file;
+    self.mark_dirty();
+    log::info!("Reloaded {}", config_file::CONFIG_PATH);
+  }
+
+  /// Creates everything on the GPU again, on a new device, after the old one
+  /// was lost. It's all built from `app_config` like at startup, see
+  /// `take_over`. Keeps the old state if that fails.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn recreate(&mut self, window: &Window) -> Result<(), Error> {
+    self.wait_idle();
+    let state = pollster::block_on(State::new(Target::Window(window), self.app_config.clone()))?;
+    let old = std::mem::replace(self, state);
+    self.take_over(old);
+    Ok(())
+  }
+
+  /// Recreates everything on the adapter the opposite power preference
+  /// picks, to compare the integrated and discrete GPU without a restart.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn switch_power_preference(&mut self, window: &Window) {
+    let (power_preference, adapter_index) = (
+      self.app_config.power_preference,
+      self.app_config.adapter_index,
+    );
+    self.app_config.power_preference = match self.app_config.power_preference {
+      wgpu::PowerPreference::LowPower => wgpu::PowerPreference::HighPerformance,
+      wgpu::PowerPreference::HighPerformance => wgpu::PowerPreference::LowPower,
+    };
+    // It would pick the same adapter again.
+    self.app_config.adapter_index = None;
+    let previous = self.adapter_info.clone();
+    if let Err(e) = self.recreate(window) {
+      log::error!("Couldn't switch adapters: {:?}", anyhow::Error::from(e));
+      self.app_config.power_preference = power_preference;
+      self.app_config.adapter_index = adapter_index;
+      return;
+    }
+    log::info!(
+      "Power preference {:?}: {}",
+      self.app_config.power_preference,
+      adapter::describe(&self.adapter_info)
+    );
+    if self.adapter_info == previous {
+      log::warn!(
+        "Still on the same adapter, there's no other one or the driver ignored the preference"
+      );
+    }
+  }
+
+  /// Carries over what the user changed in `old`, a state on a device that's
+  /// gone, to this new one.
+  fn take_over(&mut self, old: State) {
+    self.camera = old.camera;
+    self.camera_controller = old.camera_controller;
+    #[cfg(feature = "watch-config")]
+    {
+      self.config_file = old.config_file;
+    }
+    self.cursor_grabbed = old.cursor_grabbed;
+    self.cursor_position = old.cursor_position;
+    self.cursor_visible = old.cursor_visible;
+    self.focused = old.focused;
+    #[cfg(target_arch = "wasm32")]
+    {
+      self.gamepad_connected = old.gamepad_connected;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+      self.gif_recorder = old.gif_recorder;
+    }
+    self.keyboard = old.keyboard;
+    self.noise_seed = old.noise_seed;
+    #[cfg(target_arch = "wasm32")]
+    {
+      self.pointer_locked = old.pointer_locked;
+    }
+    #[cfg(feature = "record")]
+    {
+      self.recorder = old.recorder;
+      self.replay = old.replay;
+    }
+    self.selected = old.selected;
+    self.skin_paused = old.skin_paused;
+    self.split_screen = old.split_screen;
+    self.turntable = old.turntable;
+    #[cfg(feature = "ffmpeg")]
+    {
+      self.video_recorder = old.video_recorder;
+    }
+    self.write_camera_uniform();
+    self.set_render_scale(old.render_scale);
+    self.upscaler.set_gamma(&self.queue, old.upscaler.gamma());
+    self
+      .upscaler
+      .set_exposure(&self.queue, old.upscaler.exposure());
+  }
+
+  fn set_render_scale(&mut self, render_scale: f32) {
+    let render_scale = render_scale.clamp(upscale::MIN_RENDER_SCALE, upscale::MAX_RENDER_SCALE);
+    if render_scale == self.render_scale {
+      return;
+    }
+    if let Err(e) = self.resize_scene_targets(&self.config.clone(), render_scale) {
+      log::error!(
+        "Keeping the render scale at {:.2}: {:?}",
+        self.render_scale,
+        e
+      );
+      return;
+    }
+    self.render_scale = render_scale;
+    log::info!("Render scale: {:.2}", self.render_scale);
+  }
+
+  fn input(&mut self, window: &Window, event: &WindowEvent) -> InputResponse {
+    #[cfg(feature = "record")]
+    if let Some(recorder) = &mut self.recorder {
+      if let Err(e) = recorder.record(event) {
+        log::error!("Stopped recording input: {:?}", e);
+        self.recorder = None;
+      }
+    }
+
+    let repeat = match event {
+      WindowEvent::KeyboardInput { input, .. } => self.keyboard.process(input),
+      WindowEvent::ModifiersChanged(modifiers) => {
+        self.keyboard.set_modifiers(*modifiers);
+        false
+      }
+      _ => false,
+    };
+    if let Some(response) = self.chord_action(event) {
+      return response;
+    }
+
+    match event {
+      // Holding a scrub key keeps scrubbing, everything else fires once per
+      // press.
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(keycode),
+            ..
+          },
+        ..
+      } if self.scrub_material(*keycode) => InputResponse::REDRAW,
+      WindowEvent::KeyboardInput { .. } if repeat => InputResponse::CONSUMED,
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode:
+              Some(
+                keycode @ (VirtualKeyCode::Key1
+                | VirtualKeyCode::Key2
+                | VirtualKeyCode::Key3
+                | VirtualKeyCode::Key4),
+              ),
+            ..
+          },
+        ..
+      } => {
+        // Shift+1 to 4 save the camera, 1 to 4 restore it.
+        let slot = *keycode as u32 - VirtualKeyCode::Key1 as u32 + 1;
+        if self.keyboard.modifiers().shift() {
+          self.save_camera(slot);
+          InputResponse::CONSUMED
+        } else {
+          self.restore_camera(slot);
+          InputResponse::REDRAW
+        }
+      }
+      WindowEvent::CursorMoved { position, .. } => {
+        self.cursor_position = Some(*position);
+        InputResponse::IGNORED
+      }
+      // Tinted while a file is dragged over the window, to show dropping it
+      // does something.
+      WindowEvent::HoveredFile(_) => {
+        self.upscaler.set_tint(&self.queue, DROP_TINT);
+        InputResponse::REDRAW
+      }
+      WindowEvent::HoveredFileCancelled => {
+        self.upscaler.set_tint(&self.queue, upscale::NO_TINT);
+        InputResponse::REDRAW
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      WindowEvent::DroppedFile(path) => {
+        self.upscaler.set_tint(&self.queue, upscale::NO_TINT);
+        self.load_dropped_file(path);
+        InputResponse::REDRAW
+      }
+      // A click is also the only time browsers lock the pointer.
+      #[cfg(target_arch = "wasm32")]
+      WindowEvent::MouseInput {
+        state: ElementState::Pressed,
+        button: MouseButton::Left,
+        ..
+      } if self.cursor_grabbed => {
+        if !self.pointer_locked {
+          self.set_cursor_grab(window, true);
+        }
+        InputResponse::CONSUMED
+      }
+      WindowEvent::MouseInput {
+        state: ElementState::Pressed,
+        button: MouseButton::Left,
+        ..
+      } => {
+        self.pick();
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Escape),
+            ..
+          },
+        ..
+      } if self.cursor_grabbed => {
+        self.set_cursor_grab(window, false);
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::G),
+            ..
+          },
+        ..
+      } => {
+        self.toggle_vertex_gradient();
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::F),
+            ..
+          },
+        ..
+      } => {
+        // The `Resized` that follows redraws.
+        toggle_fullscreen(window);
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::T),
+            ..
+          },
+        ..
+      } => {
+        self.turntable = !self.turntable;
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::B),
+            ..
+          },
+        ..
+      } => {
+        if let Some(background) = &mut self.background {
+          let scene_config = upscale::scaled_config(&self.config, self.render_scale);
+          background.toggle_fit(&self.queue, &scene_config);
+        }
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::F3),
+            ..
+          },
+        ..
+      } => {
+        self.toggle_benchmark();
+        InputResponse::CONSUMED
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::F4),
+            ..
+          },
+        ..
+      } => {
+        self.switch_power_preference(window);
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::L),
+            ..
+          },
+        ..
+      } => {
+        self.toggle_frame_cap();
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(keycode @ (VirtualKeyCode::Minus | VirtualKeyCode::Equals)),
+            ..
+          },
+        ..
+      } => {
+        let step = if *keycode == VirtualKeyCode::Minus {
+          -RENDER_SCALE_STEP
+        } else {
+          RENDER_SCALE_STEP
+        };
+        self.set_render_scale(self.render_scale + step);
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(keycode @ (VirtualKeyCode::LBracket | VirtualKeyCode::RBracket)),
+            ..
+          },
+        ..
+      } => {
+        let step = if *keycode == VirtualKeyCode::LBracket {
+          -GAMMA_STEP
+        } else {
+          GAMMA_STEP
+        };
+        let gamma = self.upscaler.gamma() + step;
+        self.upscaler.set_gamma(&self.queue, gamma);
+        log::info!("Gamma: {:.1}", self.upscaler.gamma());
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode:
+              Some(keycode @ (VirtualKeyCode::Semicolon | VirtualKeyCode::Apostrophe)),
+            ..
+          },
+        ..
+      } => {
+        let exposure = if *keycode == VirtualKeyCode::Semicolon {
+          self.upscaler.exposure() / EXPOSURE_STEP
+        } else {
+          self.upscaler.exposure() * EXPOSURE_STEP
+        };
+        self.upscaler.set_exposure(&self.queue, exposure);
+        log::info!("Exposure: {:.2}", self.upscaler.exposure());
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::V),
+            ..
+          },
+        ..
+      } => {
+        self.split_screen = !self.split_screen;
+        self.update_camera_aspects();
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::C),
+            ..
+          },
+        ..
+      } => {
+        self.show_scissor_demo = !self.show_scissor_demo;
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::O),
+            ..
+          },
+        ..
+      } => {
+        self.toggle_redraw_mode();
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Q),
+            ..
+          },
+        ..
+      } => {
+        // While grabbed, moving the mouse orbits the camera.
+        self.set_cursor_grab(window, !self.cursor_grabbed);
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::H),
+            ..
+          },
+        ..
+      } => {
+        // In the browser this sets the canvas' CSS cursor, so the cursor is
+        // only hidden while it's over the canvas.
+        self.cursor_visible = !self.cursor_visible;
+        window.set_cursor_visible(self.cursor_visible);
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::X),
+            ..
+          },
+        ..
+      } => {
+        self.set_msaa_samples(msaa::next(
+          self.app_config.msaa_samples,
+          &self.msaa_sample_counts,
+        ));
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::F2),
+            ..
+          },
+        ..
+      } => {
+        self.toggle_normals();
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::U),
+            ..
+          },
+        ..
+      } => {
+        self.cycle_culling(self.keyboard.modifiers().shift());
+        InputResponse::REDRAW
+      }
+      // Shows best on the ground far off, looked at from close to it.
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::I),
+            ..
+          },
+        ..
+      } => {
+        let anisotropy = if self.app_config.anisotropy >= texture::MAX_ANISOTROPY {
+          1
+        } else {
+          self.app_config.anisotropy * 2
+        };
+        self.set_anisotropy(anisotropy);
+        InputResponse::REDRAW
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::K),
+            ..
+          },
+        ..
+      } => {
+        self.skin_paused = !self.skin_paused;
+        InputResponse::REDRAW
+      }
+      #[cfg(feature = "ffmpeg")]
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::F9),
+            ..
+          },
+        ..
+      } => {
+        self.toggle_video();
+        InputResponse::REDRAW
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::F8),
+            ..
+          },
+        ..
+      } if self.gif_recorder.is_some() => {
+        self.save_gif();
+        InputResponse::CONSUMED
+      }
+      WindowEvent::KeyboardInput {
+        input:
+          KeyboardInput {
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::J),
+            ..
+          },
+        ..
+      } if self.noise.is_some() => {
+        if self.keyboard.modifiers().shift() {
+          self.noise_tileable = !self.noise_tileable;
+          self.noise_stale = true;
+        } else {
+          self.noise_preview = !self.noise_preview;
+          // Different noise every time it's shown.
+          if self.noise_preview {
+            self.noise_seed = self.noise_seed.wrapping_add(1);
+            self.noise_stale = true;
+          }
+        }
+        InputResponse::REDRAW
+      }
+      // Held movement keys count as animating, so the camera moving needs no
+      // redraw of its own.
+      _ if self.camera_controller.process_events(event) => InputResponse::CONSUMED,
+      // `update` may act on any other new press through
+      // `was_pressed_this_frame`, so draw a frame for it to run in.
+      WindowEvent::KeyboardInput {
+        input: KeyboardInput {
+          state: ElementState::Pressed,
+          ..
+        },
+        ..
+      } => InputResponse {
+        consumed: false,
+        redraw: true,
+      },
+      _ => InputResponse::IGNORED,
+    }
+  }
+
+  /// Runs the action of a chord `event` completed, if any.
+  fn chord_action(&mut self, event: &WindowEvent) -> Option<InputResponse> {
+    if !matches!(
+      event,
+      WindowEvent::KeyboardInput { .. } | WindowEvent::ModifiersChanged(_)
+    ) {
+      return None;
+    }
+    // Check every chord, even after one fired, so released ones can reset.
+    let screenshot = self.keyboard.chord_fired(SCREENSHOT_CHORD);
+    let reset = self.keyboard.chord_fired(RESET_CAMERA_CHORD);
+    if screenshot {
+      self.screenshot_requested = true;
+      Some(InputResponse::REDRAW)
+    } else if reset {
+      self.reset_camera();
+      Some(InputResponse::REDRAW)
+    } else {
+      None
+    }
+  }
+
+  /// Feeds the recorded events that are due through `input`. Returns when the
+  /// next one is due.
+  #[cfg(feature = "record")]
+  fn replay_input(&mut self, window: &Window) -> Option<instant::Instant> {
+    let replay = self.replay.as_mut()?;
+    let events = replay.due_events();
+    let next = replay.next_event_time();
+    for event in &events {
+      if self.input(window, event).redraw {
+        self.mark_dirty();
+      }
+    }
+    next
+  }
+
+  /// N/M lower/raise metallic and ,/. lower/raise roughness so their effect
+  /// on the BRDF can be seen live.
+  fn scrub_material(&mut self, keycode: VirtualKeyCode) -> bool {
+    const STEP: f32 = 0.05;
+    let params = &mut self.material.params;
+    match keycode {
+      VirtualKeyCode::N => params.metallic = (params.metallic - STEP).max(0.0),
+      VirtualKeyCode::M => params.metallic = (params.metallic + STEP).min(1.0),
+      VirtualKeyCode::Comma => params.roughness = (params.roughness - STEP).max(0.0),
+      VirtualKeyCode::Period => params.roughness = (params.roughness + STEP).min(1.0),
+      _ => return false,
+    }
+    log::info!(
+      "Material metallic: {:.2}, roughness: {:.2}",
+      params.metallic,
+      params.roughness
+    );
+    self.material.update(&self.queue);
+    true
+  }
+
+  /// Swaps the pentagon between plain white and position based vertex colors.
+  fn toggle_vertex_gradient(&mut self) {
+    self.vertex_gradient = !self.vertex_gradient;
+    let vertices = if self.vertex_gradient {
+      Vertex::with_position_colors(VERTICES)
+    } else {
+      VERTICES.to_vec()
+    };
+    self.queue.write_buffer(
+      &self.instance_mesh.vertex_buffer,
+      0,
+      bytemuck::cast_slice(&vertices),
+    );
+  }
+
+  fn write_camera_uniform(&mut self) {
+    self.camera_binding.update(&self.queue, &self.camera);
+  }
+
+  fn save_camera(&self, slot: u32) {
+    match camera_slots::save(slot, &self.camera.to_bytes()) {
+      Ok(()) => log::info!("Saved camera to slot {}", slot),
+      Err(e) => log::error!("Couldn't save camera: {:?}", e),
+    }
+  }
+
+  /// Moves the camera back to where it started.
+  fn reset_camera(&mut self) {
+    self.camera.eye = CAMERA_HOME_EYE;
+    self.camera.target = CAMERA_HOME_TARGET;
+    self.camera.up = cgmath::Vector3::unit_y();
+    self.write_camera_uniform();
+  }
+
+  /// Loads a file dropped onto the window: images become the diffuse
+  /// texture, `.glb` models the skinned model and `.obj` ones the static
+  /// model, which the camera then frames.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn load_dropped_file(&mut self, path: &std::path::Path) {
+    let extension = path
+      .extension()
+      .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+      // Swapped in by `upload_loaded_textures` once it's decoded.
+      Some("png" | "jpg" | "jpeg") => self.texture_loader.load(&path.to_string_lossy()),
+      Some("glb") => {
+        let model = std::fs::read(path)
+          .map_err(anyhow::Error::from)
+          .and_then(|bytes| {
+            SkinnedModel::from_glb(&self.device, &self.joint_bind_group_layout, &bytes)
+          });
+        match model {
+          Ok(model) => {
+            log::info!("Loaded model {}", path.display());
+            self.skinned_model = model;
+            self.frame_skinned_model();
+          }
+          Err(e) => log::error!("Failed to load model {}: {:?}", path.display(), e),
+        }
+      }
+      Some("obj") => match Model::load(
+        &self.device,
+        &self.queue,
+        &self.material_bind_group_layout,
+        path,
+      ) {
+        Ok(model) => {
+          log::info!("Loaded model {}", path.display());
+          let radius = model.radius;
+          self.obj_model = Some(model);
+          self.frame_obj_model(radius);
+        }
+        Err(e) => log::error!("Failed to load model {}: {:?}", path.display(), e),
+      },
+      _ => log::warn!(
+        "Can't load {}, drop a .png, .jpg, .glb or .obj file",
+        path.display()
+      ),
+    }
+  }
+
+  /// Points the camera at the skinned model, from as far and as high as it
+  /// starts out from the scene's center.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn frame_skinned_model(&mut self) {
+    self.camera.target = cgmath::Point3::from_vec(self.skinned_instance.position);
+    self.camera.eye = self.camera.target + (CAMERA_HOME_EYE - CAMERA_HOME_TARGET);
+    self.camera.up = cgmath::Vector3::unit_y();
+    self.write_camera_uniform();
+  }
+
+  /// Points the camera at the static model from the direction it starts out
+  /// looking at the scene from, just far enough for a sphere of `radius`
+  /// around it to fit the view.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn frame_obj_model(&mut self, radius: f32) {
+    let half_fovy = cgmath::Rad::from(cgmath::Deg(self.camera.fovy / 2.0));
+    let distance = radius.max(0.1) / half_fovy.0.sin();
+    self.camera.target = cgmath::Point3::from_vec(OBJ_MODEL_POSITION);
+    self.camera.eye =
+      self.camera.target + (CAMERA_HOME_EYE - CAMERA_HOME_TARGET).normalize_to(distance);
+    self.camera.up = cgmath::Vector3::unit_y();
+    self.write_camera_uniform();
+  }
+
+  fn restore_camera(&mut self, slot: u32) {
+    let camera =
+      camera_slots::load(slot).and_then(|bytes| Camera::from_bytes(&bytes, &self.camera));
+    match camera {
+      Ok(camera) => {
+        self.camera = camera;
+        self.write_camera_uniform();
+        log::info!("Restored camera from slot {}", slot);
+      }
+      Err(e) => log::error!("Couldn't restore camera: {:?}", e),
+    }
+  }
+
+  /// The cursor in normalized device coordinates: -1 to 1 left to right and
+  /// bottom to top, like clip space. The middle of the window until the
+  /// cursor first moves over it.
+  pub fn cursor_ndc(&self) -> [f32; 2] {
+    match self.cursor_position {
+      Some(position) => {
+        let x = position.x / self.size.width as f64 * 2.0 - 1.0;
+        let y = 1.0 - position.y / self.size.height as f64 * 2.0;
+        [x.clamp(-1.0, 1.0) as f32, y.clamp(-1.0, 1.0) as f32]
+      }
+      None => [0.0, 0.0],
+    }
+  }
+
+  /// Renders the object ids into the picking target and reads back the one
+  /// under the cursor. The result is picked up in `update`.
+  fn pick(&mut self) {
+    let position = match self.cursor_position {
+      Some(position) => position,
+      None => return,
+    };
+    if self.picker.is_pending() {
+      return;
+    }
+    // The surface is smaller than the window if it had to be clamped.
+    let x = position.x * self.config.width as f64 / self.size.width as f64;
+    let y = position.y * self.config.height as f64 / self.size.height as f64;
+    let x = (x.max(0.0) as u32).min(self.config.width - 1);
+    let y = (y.max(0.0) as u32).min(self.config.height - 1);
+
+    // Only the view under the cursor is drawn, with its camera, where it's
+    // shown.
+    let (width, height) = (self.config.width, self.config.height);
+    let left = width / 2;
+    let (camera_binding, viewport) = match self.split_screen {
+      true if x >= left => (
+        &self.split_view.binding,
+        Rect::new(left as i32, 0, width - left, height),
+      ),
+      true => (&self.camera_binding, Rect::new(0, 0, left, height)),
+      false => (&self.camera_binding, Rect::new(0, 0, width, height)),
+    };
+
+    let mut encoder = self
+      .device
+      .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Picking Encoder"),
+      });
+
+    {
+      let mut id_pass = self.picker.begin_pass(&mut encoder);
+      id_pass.set_viewport(
+        viewport.x as f32,
+        viewport.y as f32,
+        viewport.width as f32,
+        viewport.height as f32,
+        0.0,
+        1.0,
+      );
+      id_pass.set_bind_group(0, &camera_binding.bind_group, &[]);
+
+      // Everything else only hides the objects behind it.
+      self.picker.bind_occluders(&mut id_pass);
+      self
+        .ground_mesh
+        .draw_instanced(&mut id_pass, self.ground_instance_buffer.slice(..), 0..1);
+      if let Some(model) = &self.obj_model {
+        for mesh in &model.meshes {
+          mesh
+            .mesh
+            .draw_instanced(&mut id_pass, self.obj_instance_buffer.slice(..), 0..1);
+        }
+      }
+      self.picker.bind_skinned_occluders(&mut id_pass);
+      self
+        .skinned_model
+        .draw(&mut id_pass, 1, self.skinned_instance_buffer.slice(..));
+
+      self.picker.bind_objects(&mut id_pass);
+      self.instance_mesh.draw_instanced(
+        &mut id_pass,
+        self.instance_buffer.slice(..),
+        0..self.instances.len() as u32,
+      );
+    }
+    self.picker.copy_pixel(&mut encoder, x, y);
+
+    self.queue.submit(std::iter::once(encoder.finish()));
+    self.picker.request_readback(&self.device);
+  }
+
+  /// Requests a redraw in on-demand mode. Call after changing anything that
+  /// shows up on screen.
+  fn mark_dirty(&mut self) {
+    self.dirty = true;
+  }
+
+  /// Whether something changes every frame without any input.
+  fn is_animating(&self) -> bool {
+    (self.skinned_model.is_animated() && !self.skin_paused)
+      || self.turntable
+      || self.show_scissor_demo
+      || self.camera_controller.is_moving()
+      || self.picker.is_pending()
+      || self.texture_loader.is_pending()
+      || self.screenshot_requested
+      // The video assumes a frame every 1/`VIDEO_FPS` seconds.
+      || self.is_recording_video()
+  }
+
+  /// Whether to draw frames back to back, without waiting in between.
+  fn redraws_continuously(&self) -> bool {
+    // The benchmarks measure how fast frames can be drawn, so keep drawing.
+    self.app_config.redraw_mode == RedrawMode::Continuous
+      || self.benchmark
+      || self.frame_bench.is_some()
+  }
+
+  fn needs_redraw(&self) -> bool {
+    #[cfg(target_arch = "wasm32")]
+    if self.context_lost {
+      return false;
+    }
+    self.redraws_continuously() || self.dirty || self.is_animating()
+  }
+
+  /// Counts a presented frame towards `Config::bench_frames`. Returns true
+  /// once they were all drawn and the results printed.
+  fn bench_frame_finished(&mut self) -> bool {
+    let bench = match &mut self.frame_bench {
+      Some(bench) => bench,
+      None => return false,
+    };
+    if !bench.frame_finished() {
+      return false;
+    }
+    println!("{}", bench.summary());
+    true
+  }
+
+  fn set_focused(&mut self, window: &Window, focused: bool) {
+    self.mark_dirty();
+    self.focused = focused;
+    if !focused && self.cursor_grabbed {
+      // Most platforms release the grab themselves, but not all of them.
+      self.set_cursor_grab(window, false);
+    }
+    if focused {
+      // Pick up where we left off rather than jumping ahead by however long
+      // the window was in the background.
+      self.last_update = self.now();
+      self.frame_timer = FrameTimer::new(self.last_update);
+    } else {
+      // Keys let go of while in the background never send a release.
+      self.keyboard.release_all();
+    }
+  }
+
+  /// Raw input that doesn't go through the window. Mouse motion here is
+  /// unaccelerated and keeps coming when the cursor is stuck at the edge of
+  /// the screen, which is what mouse look needs. It also arrives while other
+  /// windows have focus, so it's only used while the cursor is grabbed.
+  fn device_event(&mut self, event: &DeviceEvent) {
+    if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+      if self.is_mouse_looking() {
+        self.camera_controller.process_mouse_motion(*dx, *dy);
+      }
+    }
+  }
+
+  /// Hands the gamepad's sticks to the camera controller.
+  #[cfg(target_arch = "wasm32")]
+  fn poll_gamepad(&mut self) {
+    if self.gamepad_connected {
+      self
+        .camera_controller
+        .set_gamepad(web::gamepad_input().unwrap_or_default());
+    }
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  fn gamepads_changed(&mut self) {
+    let connected = web::gamepad_input().is_some();
+    if connected != self.gamepad_connected {
+      log::info!(
+        "Gamepad {}",
+        if connected {
+          "connected"
+        } else {
+          "disconnected"
+        }
+      );
+    }
+    self.gamepad_connected = connected;
+    if !connected {
+      self.camera_controller.set_gamepad(Default::default());
+    }
+  }
+
+  fn is_mouse_looking(&self) -> bool {
+    cfg_if::cfg_if! {
+      if #[cfg(target_arch = "wasm32")] {
+        // The browser reports mouse movement without a lock too.
+        self.cursor_grabbed && self.pointer_locked
+      } else {
+        self.cursor_grabbed
+      }
+    }
+  }
+
+  /// Keeps the cursor inside the window. What that means depends on the
+  /// platform: Windows, X11 and Wayland confine it to the window, macOS locks
+  /// it in place and the web uses the pointer lock API, which browsers only
+  /// allow while handling input. If Q didn't get the lock there, clicking the
+  /// canvas asks again. winit 0.26 can't ask for confined or locked, and
+  /// mobile can't grab at all, so a failed grab is only logged.
+  fn set_cursor_grab(&mut self, window: &Window, grab: bool) {
+    match window.set_cursor_grab(grab) {
+      Ok(()) => self.cursor_grabbed = grab,
+      Err(e) => log::warn!("Couldn't change the cursor grab: {:?}", e),
+    }
+  }
+
+  /// Follows the browser locking and unlocking the pointer. Once it unlocks
+  /// it, say after Escape, mouse look is over.
+  #[cfg(target_arch = "wasm32")]
+  fn set_pointer_locked(&mut self, locked: bool) {
+    self.pointer_locked = locked;
+    if !locked {
+      self.cursor_grabbed = false;
+    }
+  }
+
+  fn is_paused(&self) -> bool {
+    self.app_config.pause_when_unfocused && !self.focused && self.frame_bench.is_none()
+  }
+
+  /// Minimum time between redraws, or `None` to redraw as fast as the
+  /// present mode allows.
+  fn redraw_interval(&self) -> Option<std::time::Duration> {
+    if self.is_paused() {
+      // Still present now and then, some compositors show stale or black
+      // contents for windows that stop presenting.
+      return Some(UNFOCUSED_REDRAW_INTERVAL);
+    }
+    self
+      .frame_cap
+      .map(|fps| std::time::Duration::from_secs_f64(1.0 / fps.max(1) as f64))
+  }
+
+  /// Switches between vsync and presenting as fast as possible, logging the
+  /// frame rate while uncapped.
+  fn toggle_benchmark(&mut self) {
+    self.benchmark = !self.benchmark;
+    let present_mode = present_mode(
+      self.app_config.vsync && !self.benchmark,
+      self.app_config.tearing,
+    );
+    self
+      .reconfigure_surface(|config| config.present_mode = present_mode)
+      .expect("Any present mode is valid");
+    self.frame_timer = FrameTimer::new(self.now());
+    log::info!("Present mode: {:?}", self.config.present_mode);
+  }
+
+  fn toggle_frame_cap(&mut self) {
+    self.frame_cap = match self.frame_cap {
+      Some(_) => None,
+      None => Some(self.app_config.frame_cap.unwrap_or(DEFAULT_FRAME_CAP)),
+    };
+    log::info!("Frame cap: {:?}", self.frame_cap);
+  }
+
+  fn toggle_redraw_mode(&mut self) {
+    self.app_config.redraw_mode = match self.app_config.redraw_mode {
+      RedrawMode::Continuous => RedrawMode::OnDemand,
+      RedrawMode::OnDemand => RedrawMode::Continuous,
+    };
+    log::info!("Redraw mode: {:?}", self.app_config.redraw_mode);
+  }
+
+  /// The time animations are at. With a fixed time step that's the number
+  /// of updates times the step, so frames don't depend on the clock at all.
+  fn now(&self) -> instant::Instant {
+    match self.app_config.fixed_time_step {
+      Some(step) => self.clock_start + step * self.frames_updated,
+      None => instant::Instant::now(),
+    }
+  }
+
+  fn update(&mut self) {
+    self.frames_updated += 1;
+    let now = self.now();
+    let dt = match self.app_config.fixed_time_step {
+      Some(step) => step.as_secs_f32(),
+      // After idling in on-demand mode the gap since the last update can be
+      // long, don't let animations jump ahead by it.
+      None => (now - self.last_update).as_secs_f32().min(MAX_FRAME_TIME),
+    };
+    self.last_update = now;
+    if self.is_paused() {
+      self.keyboard.end_frame();
+      return;
+    }
+
+    if self.keyboard.was_pressed_this_frame(VirtualKeyCode::P) {
+      self.screenshot_requested = true;
+    }
+
+    if let Some(fps) = self.frame_timer.tick(now) {
+      if self.benchmark {
+        log::info!(
+          "{:.1} fps ({:.2} ms), {}x MSAA, culled {} of {} instances, {} state changes ({} saved by sorting)",
+          fps,
+          1000.0 / fps,
+          self.app_config.msaa_samples,
+          self.instances.len() - self.visible_instances.len(),
+          self.instances.len(),
+          self.draw_stats.get().switches,
+          self.draw_stats.get().saved()
+        );
+      }
+    }
+    if let (Some(dynamic_resolution), Some(average_frame_time)) = (
+      &mut self.dynamic_resolution,
+      self.frame_timer.average_frame_time(),
+    ) {
+      let render_scale = dynamic_resolution.adjust(average_frame_time, self.render_scale);
+      if render_scale != self.render_scale {
+        self.set_render_scale(render_scale);
+        self.frame_timer.reset_average();
+      }
+    }
+
+    self.upload_loaded_textures();
+    if !self.loaded && !self.texture_loader.is_pending() {
+      self.loaded = true;
+      log::info!(
+        "Loaded after {:.2} s",
+        (now - self.clock_start).as_secs_f32()
+      );
+    }
+
+    if let Some(picked) = self.picker.poll_result() {
+      self.selected = picked.map(|id| id as usize);
+      match self.selected {
+        Some(index) => log::info!("Picked instance {}", index),
+        None => log::info!("Picked background"),
+      }
+    }
+
+    self.camera_controller.update_camera(&mut self.camera, dt);
+    self.write_camera_uniform();
+    self.cull_instances();
+    self.update_texture_lods();
+    if self.split_screen {
+      self.split_view.update(&self.queue);
+    }
+    self.shadow_map.update_light(&self.queue, &self.light);
+    let skin_dt = if self.skin_paused { 0.0 } else { dt };
+    self.skinned_model.update(&self.queue, skin_dt);
+    if self.show_scissor_demo {
+      self.scissor_demo.update(&self.queue, dt);
+    }
+
+    if self.turntable {
+      let rotation =
+        cgmath::Quaternion::from_angle_y(cgmath::Deg(self.app_config.turntable_speed * dt));
+      self.skinned_instance.rotation = (rotation * self.skinned_instance.rotation).normalize();
+      self.queue.write_buffer(
+        &self.skinned_instance_buffer,
+        0,
+        bytemuck::cast_slice(&[self.skinned_instance.to_raw()]),
+      );
+    }
+
+    // Taken out for the call, so the callback can have all of `self`.
+    if let Some(mut callback) = self.update_callback.take() {
+      callback(self, dt);
+      if self.update_callback.is_none() {
+        self.update_callback = Some(callback);
+      }
+    }
+
+    self.keyboard.end_frame();
+  }
+
+  /// Uploads the textures that finished loading. They're bound in place of
+  /// the placeholder by `update_texture_lods`, before `render` records
+  /// anything, so a frame only ever binds textures whose upload was already
+  /// queued, and the queue runs the upload before the frame's commands.
+  fn upload_loaded_textures(&mut self) {
+    let loaded = self.texture_loader.finished();
+    self.upload_textures(loaded);
+  }
+
+  fn upload_textures(&mut self, loaded: Vec<(String, image::DynamicImage)>) {
+    // The diffuse texture is the only one loaded this way so far.
+    for (path, image) in loaded {
+      match LodTexture::from_image(&self.device, &self.queue, &image, &path) {
+        Ok(mut texture) => {
+          log::info!("Loaded texture {}", path);
+          texture.set_anisotropy(&self.device, self.app_config.anisotropy);
+          self.diffuse_texture = Some(texture);
+          self.material_quality = None;
+          self.ground_quality = None;
+        }
+        Err(e) => log::error!("Failed to upload texture {}: {:?}", path, e),
+      }
+    }
+  }
+
+  /// Uploads the instances inside the camera's view to
+  /// `visible_instance_buffer`.
+  fn cull_instances(&mut self) {
+    let visible = if self.split_screen {
+      // The second view looks from elsewhere, keep everything for it.
+      (0..self.instances.len()).collect()
+    } else {
+      culling::visible_instances(
+        &frustum::Frustum::new(self.camera.frustum_planes()),
+        &self.instances,
+        self.instance_radius,
+      )
+    };
+    if visible == self.visible_instances {
+      return;
+    }
+    let instance_data = visible
+      .iter()
+      .map(|&index| self.instances[index].to_raw())
+      .collect::<Vec<_>>();
+    self.queue.write_buffer(
+      &self.visible_instance_buffer,
+      0,
+      bytemuck::cast_slice(&instance_data),
+    );
+    self.visible_instances = visible;
+  }
+
+  /// Binds the diffuse texture at the quality each object's distance to the
+  /// camera calls for. The instances count as one object, since they share a
+  /// material and a draw call.
+  fn update_texture_lods(&mut self) {
+    let diffuse_texture = match &self.diffuse_texture {
+      Some(texture) => texture,
+      None => return,
+    };
+    let eye = self.camera.eye.to_vec();
+    let instances_distance = self
+      .instances
+      .iter()
+      .map(|instance| instance.position.distance(eye))
+      .fold(f32::INFINITY, f32::min);
+    let nearest_ground_point = cgmath::Vector3::new(
+      eye.x.clamp(-GROUND_HALF_EXTENT, GROUND_HALF_EXTENT),
+      GROUND_HEIGHT,
+      eye.z.clamp(-GROUND_HALF_EXTENT, GROUND_HALF_EXTENT),
+    );
+    let ground_distance = nearest_ground_point.distance(eye);
+
+    for (material, bound, distance) in [
+      (
+        &mut self.material,
+        &mut self.material_quality,
+        instances_distance,
+      ),
+      (
+        &mut self.ground_material,
+        &mut self.ground_quality,
+        ground_distance,
+      ),
+    ] {
+      let quality = Quality::for_distance(distance);
+      if *bound != Some(quality) {
+        material.set_textures(
+          &self.device,
+          &self.material_bind_group_layout,
+          diffuse_texture.get(quality),
+          &self.white_texture,
+        );
+        *bound = Some(quality);
+      }
+    }
+  }
+
+  fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+    self.dirty = false;
+    debug_assert_eq!(self.frame_graph.size(), Some(self.scene_size()));
+    let (frame, view) = match &self.output {
+      Output::Surface(surface) => {
+        let frame = surface.get_current_texture()?;
+        let view = frame
+          .texture
+          .create_view(&wgpu::TextureViewDescriptor::default());
+        (Some(frame), view)
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      Output::Offscreen(target) => (None, target.create_view()),
+    };
+
+    if !self.loaded {
+      // Screenshots and recordings wait for the scene.
+      self
+        .queue
+        .submit(std::iter::once(self.encode_loading(&view)));
+      if let Some(frame) = frame {
+        frame.present();
+      }
+      return Ok(());
+    }
+
+    let screenshot_requested = self.screenshot_requested;
+    let gif_frame_due = self.gif_frame_due();
+    let readback_due = gif_frame_due || self.is_recording_video();
+    let (shadow_commands, frame_commands) = self.encode_frame(&view);
+    self.noise_stale = false;
+    self.screenshot_requested = false;
+    // The scene samples the shadow map, so the shadow pass goes first.
+    self
+      .queue
+      .submit(std::iter::once(shadow_commands).chain(frame_commands));
+
+    if readback_due {
+      #[cfg(not(target_arch = "wasm32"))]
+      self.record_frame(gif_frame_due);
+    }
+    if screenshot_requested {
+      match self
+        .screenshot
+        .save(&self.device, &self.app_config.screenshot_dir)
+      {
+        Ok(path) => log::info!("Saved screenshot to {}", path),
+        Err(e) => log::warn!("Failed to save screenshot: {:?}", e),
+      }
+    }
+    if let Some(frame) = frame {
+      frame.present();
+    }
+
+    Ok(())
+  }
+
+  /// Clears `view` to the clear color, pulsing brighter and back every
+  /// `LOADING_PULSE_PERIOD`, to show something's happening while loading.
+  fn encode_loading(&self, view: &wgpu::TextureView) -> wgpu::CommandBuffer {
+    let seconds = (self.now() - self.clock_start).as_secs_f64();
+    let pulse = 0.5 - 0.5 * (seconds * std::f64::consts::TAU / LOADING_PULSE_PERIOD).cos();
+    let clear = self.app_config.clear_color;
+    let brighten = |channel: f64| channel + (LOADING_PULSE_COLOR - channel) * pulse;
+    let color = wgpu::Color {
+      r: brighten(clear.r),
+      g: brighten(clear.g),
+      b: brighten(clear.b),
+      a: clear.a,
+    };
+
+    let mut encoder = self
+      .device
+      .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Loading Encoder"),
+      });
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Loading Pass"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Clear(color),
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: None,
+    });
+    encoder.finish()
+  }
+
+  /// Records the frame into command buffers to submit in order, the shadow
+  /// pass's and then those of `frame_graph`. They only share buffers the
+  /// passes read, so with `Config::parallel_encoding` the shadow pass is
+  /// recorded on a thread of its own while this one records the rest. Either
+  /// way the commands are the same, and take effect in the order they're
+  /// submitted in.
+  fn encode_frame(
+    &self,
+    view: &wgpu::TextureView,
+  ) -> (wgpu::CommandBuffer, Vec<wgpu::CommandBuffer>) {
+    let (device, shadow_map, casters) = (&self.device, &self.shadow_map, self.shadow_casters());
+    let encode_shadows = move || encode_shadow_pass(device, shadow_map, &casters);
+    let imported = [(SCENE, self.upscaler.scene_view()), (OUTPUT, view)];
+    let encode_graph = || self.frame_graph.execute(self, &self.device, &imported);
+    #[cfg(not(target_arch = "wasm32"))]
+    if self.app_config.parallel_encoding {
+      return std::thread::scope(|scope| {
+        let shadows = scope.spawn(encode_shadows);
+        let frame = encode_graph();
+        let shadows = shadows
+          .join()
+          .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        (shadows, frame)
+      });
+    }
+    (encode_shadows(), encode_graph())
+  }
+
+  /// What the shadow pass draws: the ground and every instance, culled or
+  /// not, since shadows can fall into view from outside it.
+  fn shadow_casters(&self) -> Vec<ShadowCaster<'_>> {
+    let mut casters = vec![
+      ShadowCaster {
+        mesh: &self.ground_mesh,
+        instance_buffer: &self.ground_instance_buffer,
+        num_instances: 1,
+      },
+      ShadowCaster {
+        mesh: &self.instance_mesh,
+        instance_buffer: &self.instance_buffer,
+        num_instances: self.instances.len() as u32,
+      },
+    ];
+    if let Some(model) = &self.obj_model {
+      casters.extend(model.meshes.iter().map(|mesh| ShadowCaster {
+        mesh: &mesh.mesh,
+        instance_buffer: &self.obj_instance_buffer,
+        num_instances: 1,
+      }));
+    }
+    casters
+  }
+
+  fn encode_noise(&self, encoder: &mut wgpu::CommandEncoder, _views: &PassViews) {
+    if let Some(noise) = &self.noise {
+      if self.noise_stale {
+        noise.generate(encoder, &self.queue, self.noise_seed, self.noise_tileable);
+      }
+    }
+  }
+
+  /// Clears the scene to the background, if there is one. The scene pass
+  /// clears it otherwise.
+  fn encode_background(&self, encoder: &mut wgpu::CommandEncoder, views: &PassViews) {
+    if let Some(background) = &self.background {
+      let (color_view, _) = self.scene_color_target(views);
+      background.draw(encoder, color_view, self.app_config.clear_color);
+    }
+  }
+
+  fn encode_scene_pass(&self, encoder: &mut wgpu::CommandEncoder, views: &PassViews) {
+    let (color_view, resolve_target) = self.scene_color_target(views);
+    let color_load = match &self.background {
+      Some(_) => wgpu::LoadOp::Load,
+      None => wgpu::LoadOp::Clear(self.app_config.clear_color),
+    };
+
+    let mut draw_stats = DrawStats::default();
+    {
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+          view: color_view,
+          resolve_target,
+          ops: wgpu::Operations {
+            load: color_load,
+            store: true,
+          },
+        }],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+          view: views.get(DEPTH),
+          depth_ops: Some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(self.app_config.depth_clear),
+            store: true,
+          }),
+          stencil_ops: Some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(0),
+            store: true,
+          }),
+        }),
+      });
+      let (width, height) = self.scene_size();
+      let target = Rect::new(0, 0, width, height);
+      if self.split_screen {
+        // Both halves share the depth buffer, the viewport and scissor keep
+        // each one's depth and color writes on its own side.
+        let left = width / 2;
+        let halves = [
+          (
+            &self.camera_binding.bind_group,
+            Rect::new(0, 0, left, height),
+          ),
+          (
+            &self.split_view.binding.bind_group,
+            Rect::new(left as i32, 0, width - left, height),
+          ),
+        ];
+        for (camera_bind_group, viewport) in halves {
+          render_pass.set_viewport(
+            viewport.x as f32,
+            viewport.y as f32,
+            viewport.width as f32,
+            viewport.height as f32,
+            0.0,
+            1.0,
+          );
+          scissor::set_scissor_rect(&mut render_pass, &viewport, &target);
+          draw_stats += self.draw_scene(&mut render_pass, camera_bind_group, &viewport);
+        }
+      } else {
+        draw_stats += self.draw_scene(&mut render_pass, &self.camera_binding.bind_group, &target);
+      }
+      // Only `set_render_callback` needs it mutably, which can't happen
+      // while the pass holds `self`.
+      if let Some(callback) = self.render_callback.borrow_mut().as_mut() {
+        render_pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+        render_pass.set_scissor_rect(0, 0, width, height);
+        callback(&mut render_pass, self);
+      }
+    }
+    self.draw_stats.set(draw_stats);
+  }
+
+  /// Stretches the scene over the output, applying the exposure and gamma.
+  fn encode_present(&self, encoder: &mut wgpu::CommandEncoder, views: &PassViews) {
+    self.upscaler.draw(encoder, views.get(OUTPUT));
+  }
+
+  fn encode_noise_preview(&self, encoder: &mut wgpu::CommandEncoder, views: &PassViews) {
+    if let Some(noise) = &self.noise {
+      if self.noise_preview {
+        noise.draw_preview(
+          encoder,
+          views.get(OUTPUT),
+          self.config.width,
+          self.config.height,
+        );
+      }
+    }
+  }
+
+  /// Copies the scene out for a screenshot or recording that needs this
+  /// frame, and drawing without a window, the output to be read back.
+  fn encode_captures(&self, encoder: &mut wgpu::CommandEncoder, views: &PassViews) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Output::Offscreen(target) = &self.output {
+      target.capture(encoder);
+    }
+    if self.screenshot_requested || self.gif_frame_due() || self.is_recording_video() {
+      match self.scene_color_target(views) {
+        (msaa_view, Some(_)) => self.screenshot.capture(encoder, msaa_view),
+        (_, None) => self
+          .screenshot
+          .capture_texture(encoder, self.upscaler.scene_texture()),
+      }
+    }
+  }
+
+  /// What the scene pass draws into and what it resolves that into, if
+  /// it's multisampled. It's the upscaler's scene texture in the end.
+  fn scene_color_target<'a>(
+    &self,
+    views: &PassViews<'a>,
+  ) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+    if self.app_config.msaa_samples > 1 {
+      (views.get(MSAA), Some(views.get(SCENE)))
+    } else {
+      (views.get(SCENE), None)
+    }
+  }
+
+  /// Whether to read this frame back for the GIF recorder.
+  fn gif_frame_due(&self) -> bool {
+    cfg_if::cfg_if! {
+      if #[cfg(target_arch = "wasm32")] {
+        false
+      } else {
+        self
+          .gif_recorder
+          .as_ref()
+          .is_some_and(|gif| gif.is_due(self.now()))
+      }
+    }
+  }
+
+  fn is_recording_video(&self) -> bool {
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "ffmpeg")] {
+        self.video_recorder.is_some()
+      } else {
+        false
+      }
+    }
+  }
+
+  /// Hands the frame `render` just captured to the video recorder, and to
+  /// the GIF recorder if `gif`.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn record_frame(&mut self, gif: bool) {
+    let now = self.now();
+    let pixels = match self.screenshot.read(&self.device) {
+      Ok(pixels) => pixels,
+      Err(e) => {
+        log::warn!("Failed to read back the frame: {:?}", e);
+        return;
+      }
+    };
+    let (width, height) = self.screenshot.size();
+
+    if let Some(gif_recorder) = self.gif_recorder.as_mut().filter(|_| gif) {
+      gif_recorder.push(&pixels, width, height, now);
+    }
+    #[cfg(feature = "ffmpeg")]
+    if let Some(video) = &mut self.video_recorder {
+      let pushed = if video.has_size(width, height) {
+        video.push(&pixels)
+      } else {
+        Err(anyhow::anyhow!("The scene was resized"))
+      };
+      if let Err(e) = pushed {
+        log::warn!("Stopped recording video: {:?}", e);
+        self.stop_video();
+      }
+    }
+  }
+
+  /// Starts recording a video into the screenshot directory, or stops the
+  /// one being recorded.
+  #[cfg(feature = "ffmpeg")]
+  fn toggle_video(&mut self) {
+    if self.video_recorder.is_some() {
+      self.stop_video();
+      return;
+    }
+    if !self.ffmpeg_available {
+      log::warn!("Can't record video, ffmpeg isn't on the PATH");
+      return;
+    }
+
+    let (width, height) = self.screenshot.size();
+    let started = screenshot::timestamped_path(&self.app_config.screenshot_dir, "video", "mp4")
+      .and_then(|path| video_recording::VideoRecorder::start(path, width, height));
+    match started {
+      Ok(video) => {
+        log::info!("Recording video, F9 stops");
+        self.video_recorder = Some(video);
+      }
+      Err(e) => log::warn!("Failed to start recording video: {:?}", e),
+    }
+  }
+
+  #[cfg(feature = "ffmpeg")]
+  fn stop_video(&mut self) {
+    if let Some(video) = self.video_recorder.take() {
+      match video.finish() {
+        Ok((path, frames)) => log::info!("Saved {} frames of video to {}", frames, path.display()),
+        Err(e) => log::warn!("Failed to save video: {:?}", e),
+      }
+    }
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  fn save_gif(&self) {
+    if let Some(gif) = &self.gif_recorder {
+      let saved = screenshot::timestamped_path(&self.app_config.screenshot_dir, "clip", "gif")
+        .and_then(|path| gif.save(&path).map(|()| path));
+      match saved {
+        Ok(path) => log::info!("Saved GIF to {}", path.display()),
+        Err(e) => log::warn!("Failed to save GIF: {:?}", e),
+      }
+    }
+  }
+
+  /// Draws everything in the scene pass as seen through `camera_bind_group`
+  /// into `viewport`.
+  fn draw_scene<'a>(
+    &'a self,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'a wgpu::BindGroup,
+    viewport: &Rect,
+  ) -> DrawStats {
+    let scene_bind_groups = |material: &'a Material| {
+      vec![
+        &material.bind_group,
+        camera_bind_group,
+        &self.shadow_map.bind_group,
+        &self.environment.bind_group,
+      ]
+    };
+    let mut draws = DrawQueue::new();
+
+    draws.submit_draw(self.ground_mesh.draw_item(
+      &self.render_pipeline,
+      scene_bind_groups(&self.ground_material),
+      self.ground_instance_buffer.slice(..),
+      0..1,
+      0,
+    ));
+
+    // The skinned pipeline shares the material, camera and light groups and
+    // only swaps the environment for the joint matrices.
+    let mut skinned_bind_groups = scene_bind_groups(&self.ground_material);
+    skinned_bind_groups.pop();
+    draws.submit_draw(self.skinned_model.draw_item(
+      &self.skinned_pipeline,
+      skinned_bind_groups,
+      self.skinned_instance_buffer.slice(..),
+    ));
+
+    if let Some(model) = &self.obj_model {
+      draws.draw_model_instanced(
+        model,
+        &self.render_pipeline,
+        scene_bind_groups,
+        self.obj_instance_buffer.slice(..),
+        0..1,
+      );
+    }
+
+    let num_instances = self.visible_instances.len() as u32;
+    let selected = self
+      .selected
+      .and_then(|selected| {
+        self
+          .visible_instances
+          .iter()
+          .position(|&index| index == selected)
+      })
+      .map(|selected| selected as u32);
+    let instance_draw = |instances, stencil_reference| {
+      self.instance_mesh.draw_item(
+        &self.render_pipeline,
+        scene_bind_groups(&self.material),
+        self.visible_instance_buffer.slice(..),
+        instances,
+        stencil_reference,
+      )
+    };
+    match selected {
+      Some(selected) => {
+        // Split the instanced draw so only the selected object marks the stencil.
+        draws.submit_draw(instance_draw(0..selected, 0));
+        draws.submit_draw(instance_draw(selected + 1..num_instances, 0));
+        draws.submit_draw(instance_draw(
+          selected..selected + 1,
+          outline::SELECTED_STENCIL_REFERENCE,
+        ));
+      }
+      None => draws.submit_draw(instance_draw(0..num_instances, 0)),
+    }
+
+    let stats = draws.flush(render_pass);
+
+    // These rely on state the queue doesn't know about, the scissor rect and
+    // the stencil written by the draws above.
+    if self.show_scissor_demo {
+      render_pass.set_pipeline(&self.render_pipeline);
+      render_pass.set_bind_group(1, camera_bind_group, &[]);
+      render_pass.set_bind_group(2, &self.shadow_map.bind_group, &[]);
+      render_pass.set_bind_group(3, &self.environment.bind_group, &[]);
+      render_pass.set_stencil_reference(0);
+      self.scissor_demo.draw(
+        render_pass,
+        &self.ground_material,
+        viewport,
+        self.cursor_ndc(),
+      );
+    }
+
+    if let Some(selected) = selected {
+      self.outline.bind(render_pass);
+      render_pass.set_bind_group(0, camera_bind_group, &[]);
+      self.instance_mesh.draw_instanced(
+        render_pass,
+        self.visible_instance_buffer.slice(..),
+        selected..selected + 1,
+      );
+    }
+
+    stats
+  }
+}
+
+/// A mesh the shadow pass draws, with the instances to draw it at.
+struct ShadowCaster<'a> {
+  mesh: &'a Mesh,
+  instance_buffer: &'a wgpu::Buffer,
+  num_instances: u32,
+}
+
+/// Records the shadow pass over `casters`. Takes what it needs instead of
+/// the `State`, which can't be shared with another thread.
+fn encode_shadow_pass(
+  device: &wgpu::Device,
+  shadow_map: &ShadowMap,
+  casters: &[ShadowCaster],
+) -> wgpu::CommandBuffer {
+  let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+    label: Some("Shadow Encoder"),
+  });
+  {
+    let mut shadow_pass = shadow_map.begin_pass(&mut encoder);
+    for caster in casters {
+      caster.mesh.draw_instanced(
+        &mut shadow_pass,
+        caster.instance_buffer.slice(..),
+        0..caster.num_instances,
+      );
+    }
+  }
+  encoder.finish()
+}
+
+/// Creates a new `State` for `window` in the background, to wait on the
+/// adapter and device, and puts it in `slot`. Sends
+/// `UserEvent::StateRecreated` once it's there.
+#[cfg(target_arch = "wasm32")]
+fn recreate_state(
+  window: std::rc::Rc<Window>,
+  app_config: Config,
+  slot: std::rc::Rc<std::cell::RefCell<Option<State>>>,
+  proxy: winit::event_loop::EventLoopProxy<UserEvent>,
+) {
+  wasm_bindgen_futures::spawn_local(async move {
+    let state = match State::new(Target::Window(&window), app_config).await {
+      Ok(state) => state,
+      Err(e) => {
+        log::error!(
+          "Couldn't recreate the context: {:?}",
+          anyhow::Error::from(e)
+        );
+        return;
+      }
+    };
+    *slot.borrow_mut() = Some(state);
+    // Fails only once the event loop is gone.
+    let _ = proxy.send_event(UserEvent::StateRecreated);
+  });
+}
+
+/// An adapter that can draw to `target`, with the surface for its window.
+/// Tries, logging each attempt, the adapter at `adapter_index` if the
+/// config picks one that can, then whichever wgpu prefers on the configured
+/// backends, then on any backend, then wgpu's fallback adapter, which is
+/// what gets VMs and CI machines without a GPU going. Headless, a software
+/// adapter is tried first, which needs no display and draws the same on
+/// every machine, but any adapter will do.
+async fn request_adapter(
+  target: &Target<'_>,
+  app_config: &Config,
+) -> Result<(wgpu::Adapter, Option<wgpu::Surface>), Error> {
+  let headless = !matches!(target, Target::Window(_));
+  let mut attempts = Vec::new();
+  if headless {
+    attempts.push(("a software adapter", app_config.backends, true));
+  }
+  attempts.push(("the configured backends", app_config.backends, false));
+  if app_config.backends != wgpu::Backends::all() {
+    attempts.push(("any backend", wgpu::Backends::all(), false));
+  }
+  if !headless {
+    attempts.push(("the fallback adapter", wgpu::Backends::all(), true));
+  }
+
+  // A surface only works with adapters of the instance it was made by.
+  let instance_and_surface = |backends| {
+    let instance = wgpu::Instance::new(backends);
+    let surface = match target {
+      Target::Window(window) => Some(unsafe { instance.create_surface(*window) }),
+      #[cfg(not(target_arch = "wasm32"))]
+      Target::Headless(_) => None,
+    };
+    (instance, surface)
+  };
+
+  #[cfg(not(target_arch = "wasm32"))]
+  if let Some(index) = app_config.adapter_index {
+    let (instance, surface) = instance_and_surface(app_config.backends);
+    if let Some(adapter) =
+      adapter::by_index(&instance, app_config.backends, index, surface.as_ref())
+    {
+      return Ok((adapter, surface));
+    }
+  }
+  for (description, backends, force_fallback_adapter) in attempts {
+    let (instance, surface) = instance_and_surface(backends);
+    let adapter = instance
+      .request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: app_config.power_preference,
+        compatible_surface: surface.as_ref(),
+        force_fallback_adapter,
+      })
+      .await;
+    match adapter {
+      Some(adapter) => {
+        log::info!(
+          "Trying {} ({:?}): found {}",
+          description,
+          backends,
+          adapter::describe(&adapter.get_info())
+        );
+        return Ok((adapter, surface));
+      }
+      None => log::warn!("Trying {} ({:?}): none found", description, backends),
+    }
+  }
+  Err(Error::NoAdapter)
+}
+
+/// Formats the surface can be configured with.
+///
+/// wgpu 0.12 only tells us the surface's preferred format, so that's the
+/// only one we can be sure of. Swap this for `get_supported_formats` once
+/// it's available.
+fn supported_surface_formats(
+  surface: &wgpu::Surface,
+  adapter: &wgpu::Adapter,
+) -> Vec<wgpu::TextureFormat> {
+  surface.get_preferred_format(adapter).into_iter().collect()
+}
+
+/// Picks the first of `preferences` the surface supports in `color_space`,
+/// falling back to any supported format in it and then to the first format
+/// the surface supports at all. `None` if it supports none.
+fn choose_surface_format(
+  surface: &wgpu::Surface,
+  adapter: &wgpu::Adapter,
+  preferences: &[wgpu::TextureFormat],
+  color_space: ColorSpace,
+) -> Option<wgpu::TextureFormat> {
+  let supported = supported_surface_formats(surface, adapter);
+  let srgb = color_space == ColorSpace::Srgb;
+  let matching = supported
+    .iter()
+    .filter(|format| format.describe().srgb == srgb)
+    .collect::<Vec<_>>();
+
+  let format = preferences
+    .iter()
+    .find(|format| matching.contains(format))
+    .or_else(|| matching.first().copied());
+  match format {
+    Some(format) => Some(*format),
+    None => {
+      let fallback = supported.first().copied();
+      if let Some(fallback) = fallback {
+        log::warn!(
+          "No {:?} surface format is supported, using {:?}",
+          color_space,
+          fallback
+        );
+      }
+      fallback
+    }
+  }
+}
+
+/// Whether nearer fragments are expected to have greater depth.
+fn is_reverse_z(depth_compare: wgpu::CompareFunction) -> bool {
+  matches!(
+    depth_compare,
+    wgpu::CompareFunction::Greater | wgpu::CompareFunction::GreaterEqual
+  )
+}
+
+/// Warns about depth setups where the clear value doesn't sit behind
+/// everything, so the first thing drawn would fail or never be replaced.
+fn validate_depth_setup(depth_clear: f32, depth_compare: wgpu::CompareFunction) {
+  use wgpu::CompareFunction::*;
+  let expected_clear = match depth_compare {
+    Less | LessEqual => 1.0,
+    Greater | GreaterEqual => 0.0,
+    _ => {
+      log::warn!(
+        "Depth compare {:?} doesn't sort fragments by distance",
+        depth_compare
+      );
+      return;
+    }
+  };
+  if depth_clear != expected_clear {
+    log::warn!(
+      "Depth compare {:?} should be paired with a depth clear of {}, not {}",
+      depth_compare,
+      expected_clear,
+      depth_clear
+    );
+  }
+}
+
+/// The happy tree, scaled down to icon size. `None` on the web, where the
+/// page's favicon is used, or if the image can't be decoded.
+fn load_window_icon() -> Option<winit::window::Icon> {
+  if cfg!(target_arch = "wasm32") {
+    return None;
+  }
+  let icon = image::load_from_memory(include_bytes!("../happy-tree.png"))
+    .map_err(anyhow::Error::from)
+    .and_then(|image| {
+      let image = image
+        .resize(
+          WINDOW_ICON_SIZE,
+          WINDOW_ICON_SIZE,
+          image::imageops::FilterType::Triangle,
+        )
+        .to_rgba8();
+      let (width, height) = image.dimensions();
+      Ok(winit::window::Icon::from_rgba(
+        image.into_raw(),
+        width,
+        height,
+      )?)
+    });
+  match icon {
+    Ok(icon) => Some(icon),
+    Err(e) => {
+      log::warn!("Failed to load the window icon: {:?}", e);
+      None
+    }
+  }
+}
+
+/// Width and height of the window icon in pixels.
+const WINDOW_ICON_SIZE: u32 = 64;
+
+fn load_background(path: &str) -> anyhow::Result<Vec<u8>> {
+  cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+      let _ = path;
+      Ok(include_bytes!("../background.png").to_vec())
+    } else {
+      Ok(std::fs::read(path)?)
+    }
+  }
+}
+
+fn load_heightmap(path: &str) -> anyhow::Result<Vec<u8>> {
+  cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+      let _ = path;
+      Ok(include_bytes!("../heightmap.png").to_vec())
+    } else {
+      Ok(std::fs::read(path)?)
+    }
+  }
+}
+
+/// How often a paused window is still redrawn.
+const UNFOCUSED_REDRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Longest time step animations are advanced by in one update, in seconds.
+const MAX_FRAME_TIME: f32 = 0.1;
+
+/// How often to wake for the next frame while something animates in
+/// on-demand mode without a frame cap.
+const ANIMATION_FRAME_INTERVAL: std::time::Duration = std::time::Duration::from_micros(16_667);
+
+/// Frame cap L switches to when `Config::frame_cap` isn't set.
+const DEFAULT_FRAME_CAP: u32 = 60;
+
+/// Seconds the loading pulse takes to brighten and fade back.
+const LOADING_PULSE_PERIOD: f64 = 1.5;
+/// Linear gray the loading pulse brightens the clear color to.
+const LOADING_PULSE_COLOR: f64 = 0.3;
+
+/// How much -/= change the render scale by.
+const RENDER_SCALE_STEP: f32 = 0.05;
+
+/// How much [ and ] change the gamma by.
+const GAMMA_STEP: f32 = 0.1;
+
+/// What ; and ' divide and multiply the exposure by.
+const EXPOSURE_STEP: f32 = 1.25;
+
+/// Loaded in the background, relative to the working directory natively and
+/// to the page on the web.
+const DIFFUSE_TEXTURE_PATH: &str = "happy-tree.png";
+
+/// Saves the current frame like P does.
+const SCREENSHOT_CHORD: Chord = Chord::new(ModifiersState::CTRL, VirtualKeyCode::S);
+const RESET_CAMERA_CHORD: Chord = Chord::new(ModifiersState::CTRL, VirtualKeyCode::R);
+
+/// Where the camera starts out, and where Ctrl+R puts it back.
+const CAMERA_HOME_EYE: cgmath::Point3<f32> = cgmath::Point3::new(0.0, 1.5, 3.5);
+const CAMERA_HOME_TARGET: cgmath::Point3<f32> = cgmath::Point3::new(0.0, 0.0, 0.0);
+
+/// Mixed over the frame while a file is dragged over the window.
+const DROP_TINT: [f32; 4] = [0.2, 0.4, 1.0, 0.25];
+
+/// Fills the screen with the window, or with the canvas on the web, or goes
+/// back to the size it had before. Browsers only allow fullscreen while the
+/// page handles input, so winit requests it once the key is let go of.
+fn toggle_fullscreen(window: &Window) {
+  let fullscreen = match window.fullscreen() {
+    Some(_) => None,
+    None => Some(Fullscreen::Borderless(None)),
+  };
+  window.set_fullscreen(fullscreen);
+}
+
+/// Fifo with vsync, otherwise Immediate if `tearing` is allowed or Mailbox
+/// if not. wgpu 0.12 has no way to ask which present modes the surface
+/// supports, but configuring one with a mode it doesn't falls back to Fifo,
+/// with a warning. Fifo is the one every surface supports, WebGL only has
+/// that one.
+fn present_mode(vsync: bool, tearing: bool) -> wgpu::PresentMode {
+  match (vsync, tearing) {
+    (true, _) => wgpu::PresentMode::Fifo,
+    (false, true) => wgpu::PresentMode::Immediate,
+    (false, false) => wgpu::PresentMode::Mailbox,
+  }
+}
+
+/// `size` scaled down, keeping its aspect ratio, until neither side is over
+/// `max_dimension`, the largest texture the device can create. Some window
+/// managers maximize across monitors to more than that, which the surface
+/// can't be configured with. The smaller surface is stretched over the
+/// window, undistorted since the aspect ratio is kept.
+fn clamp_surface_size(
+  size: winit::dpi::PhysicalSize<u32>,
+  max_dimension: u32,
+) -> winit::dpi::PhysicalSize<u32> {
+  let largest = size.width.max(size.height);
+  if largest <= max_dimension {
+    return size;
+  }
+  let scale = max_dimension as f64 / largest as f64;
+  let clamp = |length: u32| ((length as f64 * scale).round() as u32).clamp(1, max_dimension);
+  let clamped = winit::dpi::PhysicalSize::new(clamp(size.width), clamp(size.height));
+  log::warn!(
+    "{}x{} is larger than the device's {} pixel textures, rendering at {}x{} instead",
+    size.width,
+    size.height,
+    max_dimension,
+    clamped.width,
+    clamped.height
+  );
+  clamped
+}
+
+/// `anisotropy` lowered to `max_anisotropy`, with a warning if it had to be.
+fn clamp_anisotropy(anisotropy: u8, max_anisotropy: u8) -> u8 {
+  if anisotropy > max_anisotropy {
+    log::warn!(
+      "The adapter takes at most {} anisotropic taps, using that instead of {}",
+      max_anisotropy,
+      anisotropy
+    );
+    return max_anisotropy;
+  }
+  anisotropy
+}
+
+// What the frame graph's passes read and write.
+/// Drawn by the shadow pass before the graph runs.
+const SHADOW_MAP: &str = "shadow_map";
+/// The noise texture, generated again when it's stale.
+const NOISE: &str = "noise";
+/// The upscaler's texture the scene ends up in, see `scene_color_target`.
+const SCENE: &str = "scene";
+/// What the scene pass draws into with MSAA, at the sample count.
+const MSAA: &str = "msaa";
+const DEPTH: &str = "depth";
+/// The surface texture or, without a window, the offscreen target.
+const OUTPUT: &str = "output";
+
+/// The passes after the shadow pass, and the targets only they use at
+/// `sample_count`. Rebuilt when the sample count changes.
+fn frame_graph(format: wgpu::TextureFormat, sample_count: u32) -> RenderGraph<State> {
+  let mut graph = RenderGraph::new();
+  for name in [SHADOW_MAP, NOISE, SCENE, OUTPUT] {
+    graph.import(name);
+  }
+  graph.add_transient(
+    DEPTH,
+    Transient {
+      format: texture::Texture::DEPTH_FORMAT,
+      sample_count,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    },
+  );
+  // Without MSAA the scene is drawn straight into the upscaler.
+  let (color, scene_writes) = if sample_count > 1 {
+    graph.add_transient(
+      MSAA,
+      Transient {
+        format,
+        sample_count,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+      },
+    );
+    (MSAA, vec![MSAA, SCENE, DEPTH])
+  } else {
+    (SCENE, vec![SCENE, DEPTH])
+  };
+
+  graph.add_pass("Noise", &[], &[NOISE], State::encode_noise);
+  graph.add_pass("Background", &[], &[color], State::encode_background);
+  graph.add_pass(
+    "Scene",
+    &[SHADOW_MAP],
+    &scene_writes,
+    State::encode_scene_pass,
+  );
+  graph.add_pass("Present", &[SCENE], &[OUTPUT], State::encode_present);
+  graph.add_pass(
+    "Noise Preview",
+    &[NOISE],
+    &[OUTPUT],
+    State::encode_noise_preview,
+  );
+  graph.add_pass("Captures", &[color, OUTPUT], &[], State::encode_captures);
+  if let Err(e) = graph.compile() {
+    panic!("The frame graph is invalid: {}", e);
+  }
+  graph
+}
+
+/// A vertex of the scene's static meshes, read by the scene shaders at
+/// locations 0 to 3 in field order.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+  pub position: [f32; 3],
+  pub tex_coords: [f32; 2],
+  pub normal: [f32; 3],
+  pub color: [f32; 4],
+}
+
+impl Vertex {
+  // vertex_attr_array! derives each offset from the sizes of the attributes
+  // before it, so this stays in sync with the field order above.
+  const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+    0 => Float32x3,
+    1 => Float32x2,
+    2 => Float32x3,
+    3 => Float32x4,
+  ];
+
+  /// Returns a copy of `vertices` colored by position, red to green along X
+  /// and dark to blue along Y, for a quick gradient over any mesh.
+  fn with_position_colors(vertices: &[Vertex]) -> Vec<Vertex> {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for vertex in vertices {
+      for axis in 0..3 {
+        min[axis] = min[axis].min(vertex.position[axis]);
+        max[axis] = max[axis].max(vertex.position[axis]);
+      }
+    }
+
+    let normalize = |value: f32, axis: usize| {
+      let extent = max[axis] - min[axis];
+      if extent > 0.0 {
+        (value - min[axis]) / extent
+      } else {
+        0.5
+      }
+    };
+
+    vertices
+      .iter()
+      .map(|vertex| {
+        let x = normalize(vertex.position[0], 0);
+        let y = normalize(vertex.position[1], 1);
+        Vertex {
+          color: [1.0 - x, x, y, 1.0],
+          ..*vertex
+        }
+      })
+      .collect()
+  }
+}
+
+impl mesh::Vertex for Vertex {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    use std::mem;
+
+    wgpu::VertexBufferLayout {
+      array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+      step_mode: wgpu::VertexStepMode::Vertex,
+      attributes: &Self::ATTRIBS,
+    }
+  }
+}
+
+const VERTICES: &[Vertex] = &[
+  Vertex {
+    position: [-0.0868241, 0.49240386, 0.0],
+    tex_coords: [0.4131759, 1.0 - 0.99240386],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  }, // A
+  Vertex {
+    position: [-0.49513406, 0.06958647, 0.0],
+    tex_coords: [0.0048659444, 1.0 - 0.56958647],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  }, // B
+  Vertex {
+    position: [-0.21918549, -0.44939706, 0.0],
+    tex_coords: [0.28081453, 1.0 - 0.05060294],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  }, // C
+  Vertex {
+    position: [0.35966998, -0.3473291, 0.0],
+    tex_coords: [0.85967, 1.0 - 0.1526709],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  }, // D
+  Vertex {
+    position: [0.44147372, 0.2347359, 0.0],
+    tex_coords: [0.9414737, 1.0 - 0.7347359],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  }, // E
+];
+
+const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+
+// A flat patch at the bottom of happy-tree.png gives the ground a solid color.
+const GROUND_TEX_COORDS: [f32; 2] = [0.02, 0.98];
+const GROUND_HALF_EXTENT: f32 = 2.5;
+const GROUND_HEIGHT: f32 = -0.5;
+
+/// Where the model from `Config::obj` or a dropped .obj file stands, to the
+/// right of the instances.
+const OBJ_MODEL_POSITION: cgmath::Vector3<f32> = cgmath::Vector3::new(2.2, GROUND_HEIGHT, 0.0);
+
+/// The ground at `GROUND_HEIGHT` showing only `GROUND_TEX_COORDS`. It's the
+/// terrain from `Config::heightmap` if there is one, a single quad otherwise.
+fn ground_mesh(app_config: &Config) -> (Vec<Vertex>, Vec<u16>) {
+  let terrain = app_config.heightmap.as_deref().and_then(|path| {
+    let terrain = load_heightmap(path).and_then(|bytes| {
+      let heightmap = image::load_from_memory(&bytes)?;
+      terrain::from_heightmap(
+        &heightmap,
+        app_config.terrain_spacing,
+        app_config.terrain_amplitude,
+      )
+    });
+    match terrain {
+      Ok(terrain) => Some(terrain),
+      Err(e) => {
+        log::warn!("Failed to load heightmap {}: {:?}", path, e);
+        None
+      }
+    }
+  });
+  let (mut vertices, indices) = terrain.unwrap_or_else(|| {
+    let extent = 2.0 * GROUND_HALF_EXTENT;
+    mesh::grid(extent, extent, 1, 1)
+  });
+  for vertex in &mut vertices {
+    vertex.position[1] += GROUND_HEIGHT;
+    vertex.tex_coords = GROUND_TEX_COORDS;
+  }
+  (vertices, indices)
+}
+
+const NUM_INSTANCES_PER_ROW: u32 = 3;
+const INSTANCE_SPACING: f32 = 1.0;
+
+/// Fully saturated color for a hue in [0, 1), used to tint the instance grid.
+fn rainbow(hue: f32) -> [f32; 4] {
+  let [r, g, b] = color::hsv_to_rgb([hue, 1.0, 1.0]);
+  [r, g, b, 1.0]
+}
+
+struct Instance {
+  position: cgmath::Vector3<f32>,
+  rotation: cgmath::Quaternion<f32>,
+  tint: [f32; 4],
+}
+
+impl Instance {
+  fn to_raw(&self) -> InstanceRaw {
+    InstanceRaw {
+      model: (cgmath::Matrix4::from_translation(self.position)
+        * cgmath::Matrix4::from(self.rotation))
+      .into(),
+      tint: self.tint,
+    }
+  }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceRaw {
+  model: [[f32; 4]; 4],
+  tint: [f32; 4],
+}
+
+impl InstanceRaw {
+  // Vertex attributes use locations 0-3, so instance data starts at 5 to
+  // leave some room. A mat4 takes up 4 slots, so the tint follows at 9.
+  const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+    5 => Float32x4,
+    6 => Float32x4,
+    7 => Float32x4,
+    8 => Float32x4,
+    9 => Float32x4,
+  ];
+
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    use std::mem;
+
+    wgpu::VertexBufferLayout {
+      array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+      // Step once per instance rather than per vertex.
+      step_mode: wgpu::VertexStepMode::Instance,
+      attributes: &Self::ATTRIBS,
+    }
+  }
+}
+
+/// A second, fixed camera shown on the right half in split-screen mode.
+struct SplitView {
+  binding: CameraBinding,
+  camera: Camera,
+}
+
+impl SplitView {
+  fn new(
+    device: &wgpu::Device,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+    camera: Camera,
+  ) -> Self {
+    Self {
+      binding: CameraBinding::new(
+        device,
+        camera_bind_group_layout,
+        &camera,
+        "Split View Camera",
+      ),
+      camera,
     }
   }
 
-  fn update_view_proj(&mut self, camera: &Camera) {
-    self.view_proj = camera.build_view_projection_matrix().into()
+  fn update(&mut self, queue: &wgpu::Queue) {
+    self.binding.update(queue, &self.camera);
   }
 }
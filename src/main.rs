@@ -1,5 +1,6 @@
 use wgpu_tutorial::run;
 
-fn main() {
-  pollster::block_on(run());
+fn main() -> anyhow::Result<()> {
+  pollster::block_on(run())?;
+  Ok(())
 }
@@ -0,0 +1,174 @@
+use wgpu::util::DeviceExt;
+
+use crate::texture;
+
+/// Metallic-roughness material parameters. These multiply the material's
+/// textures, following the glTF convention.
+#[derive(Debug, Copy, Clone)]
+pub struct MaterialParams {
+  pub base_color: [f32; 4],
+  pub metallic: f32,
+  pub roughness: f32,
+  /// How much of the environment is mirrored, from 0.0 (none) to 1.0 (a
+  /// perfect mirror).
+  pub reflectivity: f32,
+}
+
+impl Default for MaterialParams {
+  fn default() -> Self {
+    Self {
+      base_color: [1.0; 4],
+      metallic: 0.0,
+      roughness: 0.5,
+      reflectivity: 0.0,
+    }
+  }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+  base_color: [f32; 4],
+  metallic: f32,
+  roughness: f32,
+  reflectivity: f32,
+  _padding: f32,
+}
+
+impl MaterialUniform {
+  fn new(params: &MaterialParams) -> Self {
+    Self {
+      base_color: params.base_color,
+      metallic: params.metallic.clamp(0.0, 1.0),
+      // Fully smooth surfaces turn the GGX highlight into a single point, so
+      // keep a little roughness.
+      roughness: params.roughness.clamp(0.04, 1.0),
+      reflectivity: params.reflectivity.clamp(0.0, 1.0),
+      _padding: 0.0,
+    }
+  }
+}
+
+/// A base color texture, a metallic-roughness texture and the parameters
+/// scaling them.
+pub struct Material {
+  pub bind_group: wgpu::BindGroup,
+  buffer: wgpu::Buffer,
+  label: String,
+  pub params: MaterialParams,
+}
+
+impl Material {
+  pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let [base_color_entry, sampler_entry] = texture::Texture::layout_entries(0);
+    // Sampled with the base color's sampler.
+    let [metallic_roughness_entry, _] = texture::Texture::layout_entries(3);
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[
+        base_color_entry,
+        sampler_entry,
+        wgpu::BindGroupLayoutEntry {
+          binding: 2,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        },
+        // Roughness in green and metallic in blue, as in glTF.
+        metallic_roughness_entry,
+      ],
+      label: Some("material_bind_group_layout"),
+    })
+  }
+
+  /// `metallic_roughness_texture` should be a linear (non-sRGB) texture.
+  /// Pass a 1x1 white texture when the material doesn't have one, so the
+  /// parameters are used as-is.
+  pub fn new(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    base_color_texture: &texture::Texture,
+    metallic_roughness_texture: &texture::Texture,
+    params: MaterialParams,
+    label: &str,
+  ) -> Self {
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some(label),
+      contents: bytemuck::cast_slice(&[MaterialUniform::new(&params)]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let bind_group = Self::create_bind_group(
+      device,
+      layout,
+      base_color_texture,
+      metallic_roughness_texture,
+      &buffer,
+      label,
+    );
+
+    Self {
+      bind_group,
+      buffer,
+      label: label.to_owned(),
+      params,
+    }
+  }
+
+  fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    base_color_texture: &texture::Texture,
+    metallic_roughness_texture: &texture::Texture,
+    buffer: &wgpu::Buffer,
+    label: &str,
+  ) -> wgpu::BindGroup {
+    let [base_color_entry, sampler_entry] = base_color_texture.bind_group_entries(0);
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout,
+      entries: &[
+        base_color_entry,
+        sampler_entry,
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+          binding: 3,
+          resource: wgpu::BindingResource::TextureView(&metallic_roughness_texture.view),
+        },
+      ],
+      label: Some(label),
+    })
+  }
+
+  /// Swaps in new textures, keeping the parameters.
+  pub fn set_textures(
+    &mut self,
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    base_color_texture: &texture::Texture,
+    metallic_roughness_texture: &texture::Texture,
+  ) {
+    self.bind_group = Self::create_bind_group(
+      device,
+      layout,
+      base_color_texture,
+      metallic_roughness_texture,
+      &self.buffer,
+      &self.label,
+    );
+  }
+
+  /// Uploads any changes made to `params`.
+  pub fn update(&self, queue: &wgpu::Queue) {
+    queue.write_buffer(
+      &self.buffer,
+      0,
+      bytemuck::cast_slice(&[MaterialUniform::new(&self.params)]),
+    );
+  }
+}
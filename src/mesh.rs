@@ -0,0 +1,320 @@
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::draw_queue::DrawItem;
+use crate::Vertex as SceneVertex;
+
+/// A vertex type meshes can be made of, laid out for the pipelines that
+/// draw them by `desc`.
+pub trait Vertex: bytemuck::Pod {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
+}
+
+/// An index type meshes can be made of.
+pub trait Index: bytemuck::Pod {
+  const FORMAT: wgpu::IndexFormat;
+}
+
+impl Index for u16 {
+  const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+}
+
+impl Index for u32 {
+  const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+}
+
+/// Vertices and indices uploaded for drawing, with the per instance data in
+/// another buffer bound at slot 1.
+pub struct Mesh {
+  /// Can be written again, to change the vertices in place.
+  pub vertex_buffer: wgpu::Buffer,
+  index_buffer: wgpu::Buffer,
+  index_format: wgpu::IndexFormat,
+  num_indices: u32,
+}
+
+impl Mesh {
+  pub fn new<V: Vertex, I: Index>(
+    device: &wgpu::Device,
+    label: &str,
+    vertices: &[V],
+    indices: &[I],
+  ) -> Self {
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some(&format!("{} Vertex Buffer", label)),
+      contents: bytemuck::cast_slice(vertices),
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some(&format!("{} Index Buffer", label)),
+      contents: bytemuck::cast_slice(indices),
+      usage: wgpu::BufferUsages::INDEX,
+    });
+    Self {
+      vertex_buffer,
+      index_buffer,
+      index_format: I::FORMAT,
+      num_indices: indices.len() as u32,
+    }
+  }
+
+  /// Draws `instances` from `instance_buffer`, all in one call, with
+  /// whatever pipeline and bind groups `render_pass` has set. The buffer
+  /// holds one `InstanceRaw` per instance for the scene pipelines.
+  pub fn draw_instanced<'a>(
+    &'a self,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    instance_buffer: wgpu::BufferSlice<'a>,
+    instances: Range<u32>,
+  ) {
+    render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+    render_pass.set_vertex_buffer(1, instance_buffer);
+    render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+    render_pass.draw_indexed(0..self.num_indices, 0, instances);
+  }
+
+  /// The same draw as `draw_instanced`, for a `DrawQueue`.
+  pub fn draw_item<'a>(
+    &'a self,
+    pipeline: &'a wgpu::RenderPipeline,
+    bind_groups: Vec<&'a wgpu::BindGroup>,
+    instance_buffer: wgpu::BufferSlice<'a>,
+    instances: Range<u32>,
+    stencil_reference: u32,
+  ) -> DrawItem<'a> {
+    DrawItem {
+      pipeline,
+      bind_groups,
+      vertex_buffers: vec![self.vertex_buffer.slice(..), instance_buffer],
+      index_buffer: self.index_buffer.slice(..),
+      index_format: self.index_format,
+      indices: 0..self.num_indices,
+      instances,
+      stencil_reference,
+    }
+  }
+}
+
+// Every shape winds its triangles counter-clockwise seen from the side its
+// normals point to, which is the scene pipeline's front face.
+
+fn vertex(position: [f32; 3], normal: [f32; 3], tex_coords: [f32; 2]) -> SceneVertex {
+  SceneVertex {
+    position,
+    tex_coords,
+    normal,
+    color: [1.0; 4],
+  }
+}
+
+/// A `width` by `depth` rectangle in the XZ plane facing +Y, centered on the
+/// origin and split into `columns` by `rows` quads. The texture is stretched
+/// over all of it, with v growing towards +Z.
+pub fn grid(width: f32, depth: f32, columns: u16, rows: u16) -> (Vec<SceneVertex>, Vec<u16>) {
+  let columns = columns.max(1);
+  let rows = rows.max(1);
+  assert!(
+    (columns as u32 + 1) * (rows as u32 + 1) <= u16::MAX as u32 + 1,
+    "Too many vertices for u16 indices"
+  );
+
+  let mut vertices = Vec::new();
+  for row in 0..=rows {
+    for column in 0..=columns {
+      let u = column as f32 / columns as f32;
+      let v = row as f32 / rows as f32;
+      vertices.push(vertex(
+        [(u - 0.5) * width, 0.0, (v - 0.5) * depth],
+        [0.0, 1.0, 0.0],
+        [u, v],
+      ));
+    }
+  }
+
+  let stride = columns + 1;
+  let mut indices = Vec::new();
+  for row in 0..rows {
+    for column in 0..columns {
+      let corner = row * stride + column;
+      let (right, below) = (corner + 1, corner + stride);
+      indices.extend_from_slice(&[corner, below, below + 1, corner, below + 1, right]);
+    }
+  }
+  (vertices, indices)
+}
+
+/// A 1 by 1 `grid` with both sides split into `subdivisions`.
+pub fn plane(subdivisions: u16) -> (Vec<SceneVertex>, Vec<u16>) {
+  grid(1.0, 1.0, subdivisions, subdivisions)
+}
+
+/// A cube with sides of 1 centered on the origin. Every face has its own
+/// vertices, so the normals stay flat, and the whole texture.
+pub fn cube() -> (Vec<SceneVertex>, Vec<u16>) {
+  // The normal, then two directions along the face with u x v = normal.
+  const FACES: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+  ];
+  // Steps along u and v to each corner, and its texture coordinates.
+  const CORNERS: [(f32, f32, [f32; 2]); 4] = [
+    (-0.5, -0.5, [0.0, 1.0]),
+    (0.5, -0.5, [1.0, 1.0]),
+    (0.5, 0.5, [1.0, 0.0]),
+    (-0.5, 0.5, [0.0, 0.0]),
+  ];
+
+  let mut vertices = Vec::new();
+  let mut indices = Vec::new();
+  for (normal, u, v) in FACES {
+    let first = vertices.len() as u16;
+    for (along_u, along_v, tex_coords) in CORNERS {
+      let position = [0, 1, 2].map(|i| normal[i] * 0.5 + u[i] * along_u + v[i] * along_v);
+      vertices.push(vertex(position, normal, tex_coords));
+    }
+    indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+  }
+  (vertices, indices)
+}
+
+/// A sphere with a radius of 0.5 centered on the origin, made of `rings`
+/// bands from pole to pole, each split into `sectors`. The texture wraps
+/// around it once, with u growing towards +Z from +X.
+pub fn uv_sphere(rings: u16, sectors: u16) -> (Vec<SceneVertex>, Vec<u16>) {
+  let rings = rings.max(2);
+  let sectors = sectors.max(3);
+  assert!(
+    (rings as u32 + 1) * (sectors as u32 + 1) <= u16::MAX as u32 + 1,
+    "Too many vertices for u16 indices"
+  );
+
+  let mut vertices = Vec::new();
+  for ring in 0..=rings {
+    // The seam gets two columns of vertices, one with u = 0 and one with 1.
+    for sector in 0..=sectors {
+      let u = sector as f32 / sectors as f32;
+      let v = ring as f32 / rings as f32;
+      let (polar, azimuth) = (v * PI, u * 2.0 * PI);
+      let normal = [
+        polar.sin() * azimuth.cos(),
+        polar.cos(),
+        polar.sin() * azimuth.sin(),
+      ];
+      vertices.push(vertex(normal.map(|n| n * 0.5), normal, [u, v]));
+    }
+  }
+
+  let stride = sectors + 1;
+  let mut indices = Vec::new();
+  for ring in 0..rings {
+    for sector in 0..sectors {
+      let corner = ring * stride + sector;
+      let (next, below) = (corner + 1, corner + stride);
+      // Both poles are a row of vertices in the same place, skip the
+      // triangles that would have no area there.
+      if ring != 0 {
+        indices.extend_from_slice(&[corner, next, below + 1]);
+      }
+      if ring != rings - 1 {
+        indices.extend_from_slice(&[corner, below + 1, below]);
+      }
+    }
+  }
+  (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Checks every index is in range and every triangle winds
+  /// counter-clockwise seen from where its vertices' normals point, with
+  /// unit normals and texture coordinates in 0..1.
+  fn assert_consistent((vertices, indices): &(Vec<SceneVertex>, Vec<u16>)) {
+    assert_eq!(indices.len() % 3, 0);
+    for vertex in vertices {
+      let [x, y, z] = vertex.normal;
+      assert!(((x * x + y * y + z * z).sqrt() - 1.0).abs() < 1e-5);
+      assert!(vertex.tex_coords.iter().all(|t| (0.0..=1.0).contains(t)));
+    }
+    for triangle in indices.chunks(3) {
+      let [a, b, c] = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+      let ab = [0, 1, 2].map(|i| b.position[i] - a.position[i]);
+      let ac = [0, 1, 2].map(|i| c.position[i] - a.position[i]);
+      let face_normal = [
+        ab[1] * ac[2] - ab[2] * ac[1],
+        ab[2] * ac[0] - ab[0] * ac[2],
+        ab[0] * ac[1] - ab[1] * ac[0],
+      ];
+      let facing: f32 = (0..3)
+        .map(|i| face_normal[i] * (a.normal[i] + b.normal[i] + c.normal[i]))
+        .sum();
+      assert!(facing > 0.0, "Triangle {:?} winds clockwise", triangle);
+    }
+  }
+
+  #[test]
+  fn grid() {
+    let grid = super::grid(4.0, 2.0, 4, 2);
+    assert_consistent(&grid);
+    let (vertices, indices) = grid;
+    assert_eq!(vertices.len(), 5 * 3);
+    assert_eq!(indices.len(), 4 * 2 * 6);
+    assert_eq!(vertices[0].position, [-2.0, 0.0, -1.0]);
+    assert_eq!(vertices[0].tex_coords, [0.0, 0.0]);
+    assert_eq!(vertices[14].position, [2.0, 0.0, 1.0]);
+    assert_eq!(vertices[14].tex_coords, [1.0, 1.0]);
+  }
+
+  #[test]
+  fn plane() {
+    let plane = super::plane(0);
+    assert_consistent(&plane);
+    // Zero subdivisions still make one quad.
+    let (vertices, indices) = plane;
+    assert_eq!(vertices.len(), 4);
+    assert_eq!(indices, [0, 2, 3, 0, 3, 1]);
+    assert!(vertices
+      .iter()
+      .all(|vertex| vertex.position.map(f32::abs) == [0.5, 0.0, 0.5]));
+  }
+
+  #[test]
+  fn cube() {
+    let cube = super::cube();
+    assert_consistent(&cube);
+    let (vertices, indices) = cube;
+    assert_eq!(vertices.len(), 6 * 4);
+    assert_eq!(indices.len(), 6 * 6);
+    for vertex in &vertices {
+      assert!(vertex.position.iter().all(|p| p.abs() == 0.5));
+      // The normal points out of the face the vertex is on.
+      for axis in 0..3 {
+        if vertex.normal[axis] != 0.0 {
+          assert_eq!(vertex.position[axis], vertex.normal[axis] * 0.5);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn uv_sphere() {
+    let sphere = super::uv_sphere(3, 4);
+    assert_consistent(&sphere);
+    let (vertices, indices) = sphere;
+    assert_eq!(vertices.len(), 4 * 5);
+    // A fan of one triangle per sector at each pole, two in between.
+    assert_eq!(indices.len(), (4 + 4 * 2 + 4) * 3);
+    for vertex in &vertices {
+      for axis in 0..3 {
+        assert!((vertex.position[axis] - vertex.normal[axis] * 0.5).abs() < 1e-6);
+      }
+    }
+  }
+}
@@ -0,0 +1,263 @@
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::culling;
+use crate::draw_queue::DrawQueue;
+use crate::material::{Material, MaterialParams};
+use crate::mesh::Mesh;
+use crate::texture::Texture;
+use crate::Vertex;
+
+/// The part of a `Model` drawn with one material.
+pub struct ModelMesh {
+  pub mesh: Mesh,
+  /// Index into `Model::materials`.
+  pub material: usize,
+}
+
+/// A static model read from a Wavefront .obj file, with a material for each
+/// one its .mtl files define.
+pub struct Model {
+  pub meshes: Vec<ModelMesh>,
+  pub materials: Vec<Material>,
+  /// How far the furthest vertex is from the model's origin, see
+  /// `culling::bounding_radius`. The camera frames models dropped onto the
+  /// window with it, which the browser doesn't load.
+  #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+  pub radius: f32,
+}
+
+impl Model {
+  /// Reads the .obj file at `path`, and the .mtl files and diffuse textures
+  /// it names, relative to it. Faces with more than three corners are
+  /// triangulated and meshes without normals get the average of their
+  /// faces' at each vertex. Material libraries that can't be read are
+  /// logged, meshes without a material are drawn plain white.
+  pub fn load(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    material_layout: &wgpu::BindGroupLayout,
+    path: &Path,
+  ) -> Result<Self> {
+    let (models, definitions) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
+      .with_context(|| format!("Couldn't read {}", path.display()))?;
+    let definitions = definitions.unwrap_or_else(|e| {
+      log::warn!("Couldn't read the materials of {}: {:?}", path.display(), e);
+      Vec::new()
+    });
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let white = Texture::from_color(device, queue, [255; 4], "white");
+    let mut materials: Vec<_> = definitions
+      .iter()
+      .map(|definition| create_material(device, queue, material_layout, definition, &white, dir))
+      .collect();
+    // Added once a mesh needs it.
+    let mut plain = None;
+    let mut meshes = Vec::new();
+    let mut radius = 0.0f32;
+    for model in models {
+      let mesh = model.mesh;
+      if mesh.indices.is_empty() {
+        continue;
+      }
+      let material = match mesh.material_id {
+        Some(id) if id < definitions.len() => id,
+        _ => *plain.get_or_insert_with(|| {
+          let definition = tobj::Material {
+            diffuse: [1.0; 3],
+            dissolve: 1.0,
+            ..Default::default()
+          };
+          materials.push(create_material(
+            device,
+            queue,
+            material_layout,
+            &definition,
+            &white,
+            dir,
+          ));
+          materials.len() - 1
+        }),
+      };
+      let vertices = vertices(&mesh);
+      radius = radius.max(culling::bounding_radius(&vertices));
+      meshes.push(ModelMesh {
+        mesh: Mesh::new(device, &model.name, &vertices, &mesh.indices),
+        material,
+      });
+    }
+    if meshes.is_empty() {
+      bail!("{} has no faces", path.display());
+    }
+
+    Ok(Self {
+      meshes,
+      materials,
+      radius,
+    })
+  }
+}
+
+/// Draws `Model`s through a `DrawQueue`, which sorts their meshes in with
+/// everything else.
+pub trait DrawModel<'a> {
+  /// Submits `instances` from `instance_buffer` of every mesh of `model`
+  /// with `pipeline`, binding what `bind_groups` gives for its material.
+  fn draw_model_instanced(
+    &mut self,
+    model: &'a Model,
+    pipeline: &'a wgpu::RenderPipeline,
+    bind_groups: impl Fn(&'a Material) -> Vec<&'a wgpu::BindGroup>,
+    instance_buffer: wgpu::BufferSlice<'a>,
+    instances: Range<u32>,
+  );
+}
+
+impl<'a> DrawModel<'a> for DrawQueue<'a> {
+  fn draw_model_instanced(
+    &mut self,
+    model: &'a Model,
+    pipeline: &'a wgpu::RenderPipeline,
+    bind_groups: impl Fn(&'a Material) -> Vec<&'a wgpu::BindGroup>,
+    instance_buffer: wgpu::BufferSlice<'a>,
+    instances: Range<u32>,
+  ) {
+    for mesh in &model.meshes {
+      self.submit_draw(mesh.mesh.draw_item(
+        pipeline,
+        bind_groups(&model.materials[mesh.material]),
+        instance_buffer,
+        instances.clone(),
+        0,
+      ));
+    }
+  }
+}
+
+/// The vertices of `mesh`, with texture coordinates flipped to grow
+/// downwards like wgpu's, and normals averaged from the faces if it has
+/// none.
+fn vertices(mesh: &tobj::Mesh) -> Vec<Vertex> {
+  let mut vertices: Vec<_> = (0..mesh.positions.len() / 3)
+    .map(|i| Vertex {
+      position: [0, 1, 2].map(|axis| mesh.positions[i * 3 + axis]),
+      tex_coords: match mesh.texcoords.get(i * 2..i * 2 + 2) {
+        Some(&[u, v]) => [u, 1.0 - v],
+        _ => [0.0; 2],
+      },
+      normal: match mesh.normals.get(i * 3..i * 3 + 3) {
+        Some(&[x, y, z]) => [x, y, z],
+        _ => [0.0; 3],
+      },
+      color: [1.0; 4],
+    })
+    .collect();
+  if mesh.normals.is_empty() {
+    average_normals(&mut vertices, &mesh.indices);
+  }
+  vertices
+}
+
+/// Sets the normal of each vertex to the average of those of the triangles
+/// `indices` makes of `vertices` that use it. The normals have to start at
+/// zero.
+fn average_normals(vertices: &mut [Vertex], indices: &[u32]) {
+  for triangle in indices.chunks_exact(3) {
+    let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position);
+    let (ab, ac) = (
+      [0, 1, 2].map(|i| b[i] - a[i]),
+      [0, 1, 2].map(|i| c[i] - a[i]),
+    );
+    // Not normalized, so bigger faces count for more.
+    let face_normal = [
+      ab[1] * ac[2] - ab[2] * ac[1],
+      ab[2] * ac[0] - ab[0] * ac[2],
+      ab[0] * ac[1] - ab[1] * ac[0],
+    ];
+    for &index in triangle {
+      let normal = &mut vertices[index as usize].normal;
+      for axis in 0..3 {
+        normal[axis] += face_normal[axis];
+      }
+    }
+  }
+  for vertex in vertices {
+    let [x, y, z] = vertex.normal;
+    let length = (x * x + y * y + z * z).sqrt();
+    vertex.normal = if length > 0.0 {
+      [x / length, y / length, z / length]
+    } else {
+      [0.0, 1.0, 0.0]
+    };
+  }
+}
+
+/// The material `definition` describes, as far as the scene shader can draw
+/// it, with its diffuse texture read relative to `dir`, or `white` if it has
+/// none or it can't be read.
+fn create_material(
+  device: &wgpu::Device,
+  queue: &wgpu::Queue,
+  layout: &wgpu::BindGroupLayout,
+  definition: &tobj::Material,
+  white: &Texture,
+  dir: &Path,
+) -> Material {
+  let file = &definition.diffuse_texture;
+  let texture = Some(file).filter(|file| !file.is_empty()).and_then(|file| {
+    let path = dir.join(file);
+    let texture = std::fs::read(&path)
+      .map_err(anyhow::Error::from)
+      .and_then(|bytes| Texture::from_bytes(device, queue, &bytes, file));
+    match texture {
+      Ok(texture) => Some(texture),
+      Err(e) => {
+        log::warn!("Couldn't load texture {}: {:?}", path.display(), e);
+        None
+      }
+    }
+  });
+  let [r, g, b] = definition.diffuse;
+  Material::new(
+    device,
+    layout,
+    texture.as_ref().unwrap_or(white),
+    white,
+    MaterialParams {
+      base_color: [r, g, b, definition.dissolve],
+      ..Default::default()
+    },
+    &definition.name,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn averages_normals() {
+    // Two triangles folded along the x axis, one facing up, one facing +z.
+    let mut vertices = [
+      [0.0, 0.0, 0.0],
+      [1.0, 0.0, 0.0],
+      [0.0, 0.0, -1.0],
+      [0.0, 1.0, 0.0],
+    ]
+    .map(|position| Vertex {
+      position,
+      tex_coords: [0.0; 2],
+      normal: [0.0; 3],
+      color: [1.0; 4],
+    });
+    average_normals(&mut vertices, &[0, 1, 2, 0, 1, 3]);
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    assert_eq!(vertices[0].normal, [0.0, half, half]);
+    assert_eq!(vertices[1].normal, [0.0, half, half]);
+    assert_eq!(vertices[2].normal, [0.0, 1.0, 0.0]);
+    assert_eq!(vertices[3].normal, [0.0, 0.0, 1.0]);
+  }
+}
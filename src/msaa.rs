@@ -0,0 +1,55 @@
+use crate::texture;
+
+/// Sample counts `--msaa` and `msaa` in the config accept. The ones the
+/// adapter can't do fall back to the highest it can, see `clamp`.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub const REQUESTABLE: [u32; 4] = [1, 2, 4, 8];
+
+/// The sample counts the scene can be drawn with into `format`, lowest
+/// first. WebGPU, and so WebGL through wgpu, guarantee 4 for every format
+/// that can be rendered to. wgpu 0.12 can't be asked about 2 or 8, its
+/// format features have no multisample flags, so those count as unsupported
+/// everywhere.
+pub fn supported(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> Vec<u32> {
+  let renderable = |format| {
+    adapter
+      .get_texture_format_features(format)
+      .allowed_usages
+      .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+  };
+  if renderable(format) && renderable(texture::Texture::DEPTH_FORMAT) {
+    vec![1, 4]
+  } else {
+    vec![1]
+  }
+}
+
+/// The highest of `supported` that's at most `requested`, with a warning if
+/// that isn't `requested`.
+pub fn clamp(requested: u32, supported: &[u32]) -> u32 {
+  let samples = supported
+    .iter()
+    .copied()
+    .filter(|&samples| samples <= requested)
+    .max()
+    .unwrap_or(1);
+  if samples != requested {
+    log::warn!(
+      "{} MSAA samples aren't supported here, using {} (supported: {:?})",
+      requested,
+      samples,
+      supported
+    );
+  }
+  samples
+}
+
+/// The count after `current` in `supported`, back to the first after the
+/// last.
+pub fn next(current: u32, supported: &[u32]) -> u32 {
+  supported
+    .iter()
+    .copied()
+    .find(|&samples| samples > current)
+    .unwrap_or(supported[0])
+}
@@ -0,0 +1,280 @@
+use wgpu::util::DeviceExt;
+
+/// Width and height of the noise texture in pixels.
+const NOISE_SIZE: u32 = 256;
+/// Must match `workgroup_size` in `noise.wgsl`.
+const WORKGROUP_SIZE: u32 = 8;
+/// Lattice cells across the texture at the lowest octave.
+const NOISE_FREQUENCY: f32 = 4.0;
+/// Side of one preview tile as a fraction of the window's shorter side.
+const PREVIEW_FRACTION: f32 = 0.2;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NoiseParams {
+  seed: u32,
+  frequency: f32,
+  tileable: u32,
+  _padding: u32,
+}
+
+/// A grayscale value noise texture written by a compute shader, and a
+/// preview of it in the corner of the window.
+pub struct NoiseTexture {
+  compute_bind_group: wgpu::BindGroup,
+  compute_pipeline: wgpu::ComputePipeline,
+  params_buffer: wgpu::Buffer,
+  preview_bind_group: wgpu::BindGroup,
+  preview_pipeline: wgpu::RenderPipeline,
+}
+
+impl NoiseTexture {
+  /// `None` if the device can't run compute shaders or write storage
+  /// textures, which WebGL can't.
+  pub fn new(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+  ) -> Option<Self> {
+    let compute = adapter
+      .get_downlevel_properties()
+      .flags
+      .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+    if !compute || device.limits().max_storage_textures_per_shader_stage == 0 {
+      log::warn!("No compute shader support, the noise texture is disabled");
+      return None;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("noise_texture"),
+      size: wgpu::Extent3d {
+        width: NOISE_SIZE,
+        height: NOISE_SIZE,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::Rgba8Unorm,
+      usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Noise Params Buffer"),
+      contents: bytemuck::cast_slice(&[NoiseParams {
+        seed: 0,
+        frequency: NOISE_FREQUENCY,
+        tileable: 1,
+        _padding: 0,
+      }]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let compute_bind_group_layout =
+      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+          wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+              ty: wgpu::BufferBindingType::Uniform,
+              has_dynamic_offset: false,
+              min_binding_size: None,
+            },
+            count: None,
+          },
+          wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+              access: wgpu::StorageTextureAccess::WriteOnly,
+              format: wgpu::TextureFormat::Rgba8Unorm,
+              view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+          },
+        ],
+        label: Some("noise_compute_bind_group_layout"),
+      });
+    let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout: &compute_bind_group_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: params_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::TextureView(&view),
+        },
+      ],
+      label: Some("noise_compute_bind_group"),
+    });
+
+    let compute_shader = device.create_shader_module(&wgpu::include_wgsl!("noise.wgsl"));
+    let compute_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Noise Compute Pipeline Layout"),
+      bind_group_layouts: &[&compute_bind_group_layout],
+      push_constant_ranges: &[],
+    });
+    let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+      label: Some("Noise Compute Pipeline"),
+      layout: Some(&compute_layout),
+      module: &compute_shader,
+      entry_point: "cs_main",
+    });
+
+    let preview_bind_group_layout =
+      device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+          wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+              multisampled: false,
+              view_dimension: wgpu::TextureViewDimension::D2,
+              sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+          },
+          wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+          },
+        ],
+        label: Some("noise_preview_bind_group_layout"),
+      });
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      ..Default::default()
+    });
+    let preview_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout: &preview_bind_group_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: wgpu::BindingResource::TextureView(&view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(&sampler),
+        },
+      ],
+      label: Some("noise_preview_bind_group"),
+    });
+
+    // The upscale shader already stretches a texture over the viewport.
+    let preview_shader = device.create_shader_module(&wgpu::include_wgsl!("upscale.wgsl"));
+    let preview_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Noise Preview Pipeline Layout"),
+      bind_group_layouts: &[&preview_bind_group_layout],
+      push_constant_ranges: &[],
+    });
+    let preview_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("Noise Preview Pipeline"),
+      layout: Some(&preview_layout),
+      vertex: wgpu::VertexState {
+        module: &preview_shader,
+        entry_point: "vs_main",
+        buffers: &[],
+      },
+      fragment: Some(wgpu::FragmentState {
+        module: &preview_shader,
+        entry_point: "fs_main",
+        targets: &[wgpu::ColorTargetState {
+          format,
+          blend: Some(wgpu::BlendState::REPLACE),
+          write_mask: wgpu::ColorWrites::ALL,
+        }],
+      }),
+      primitive: wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: None,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: false,
+        conservative: false,
+      },
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState {
+        count: 1,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      multiview: None,
+    });
+
+    Some(Self {
+      compute_bind_group,
+      compute_pipeline,
+      params_buffer,
+      preview_bind_group,
+      preview_pipeline,
+    })
+  }
+
+  /// Fills the texture with noise for `seed`. A `tileable` texture rounds
+  /// `NOISE_FREQUENCY` to whole cells and wraps the lattice around, so its
+  /// opposite edges line up.
+  pub fn generate(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    queue: &wgpu::Queue,
+    seed: u32,
+    tileable: bool,
+  ) {
+    let params = NoiseParams {
+      seed,
+      frequency: NOISE_FREQUENCY,
+      tileable: tileable as u32,
+      _padding: 0,
+    };
+    queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+      label: Some("Noise Pass"),
+    });
+    compute_pass.set_pipeline(&self.compute_pipeline);
+    compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+    let workgroups = NOISE_SIZE.div_ceil(WORKGROUP_SIZE);
+    compute_pass.dispatch(workgroups, workgroups, 1);
+  }
+
+  /// Draws the texture 2x2 times in the top left of `view`, which is
+  /// `width` by `height` pixels. Any seam between the tiles shows the
+  /// texture isn't tileable.
+  pub fn draw_preview(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    width: u32,
+    height: u32,
+  ) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Noise Preview Pass"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Load,
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(&self.preview_pipeline);
+    render_pass.set_bind_group(0, &self.preview_bind_group, &[]);
+    let tile = (width.min(height) as f32 * PREVIEW_FRACTION)
+      .floor()
+      .max(1.0);
+    for (x, y) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+      render_pass.set_viewport(x * tile, y * tile, tile, tile, 0.0, 1.0);
+      render_pass.draw(0..3, 0..1);
+    }
+  }
+}
@@ -0,0 +1,77 @@
+struct NoiseParams {
+  seed: u32;
+  frequency: f32;
+  tileable: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: NoiseParams;
+
+[[group(0), binding(1)]]
+var output: texture_storage_2d<rgba8unorm, write>;
+
+let OCTAVES: i32 = 4;
+
+// A random value in [0, 1] for each lattice point.
+fn hash(x: i32, y: i32) -> f32 {
+  var h = (u32(x) * 0x27d4eb2du) ^ (u32(y) * 0x165667b1u) ^ (params.seed * 0x9e3779b9u);
+  h = (h ^ (h >> 15u)) * 0x85ebca6bu;
+  h = (h ^ (h >> 13u)) * 0xc2b2ae35u;
+  h = h ^ (h >> 16u);
+  return f32(h) / 4294967295.0;
+}
+
+// Wrapping the lattice every `period` cells makes the right edge continue
+// the left one and the bottom the top.
+fn wrap(cell: i32, period: i32) -> i32 {
+  if (period <= 0) {
+    return cell;
+  }
+  return ((cell % period) + period) % period;
+}
+
+// Smoothly interpolated value noise with a lattice point every whole `p`.
+fn value_noise(p: vec2<f32>, period: i32) -> f32 {
+  let cell = vec2<i32>(floor(p));
+  let f = fract(p);
+  let t = f * f * (3.0 - 2.0 * f);
+
+  let x0 = wrap(cell.x, period);
+  let x1 = wrap(cell.x + 1, period);
+  let y0 = wrap(cell.y, period);
+  let y1 = wrap(cell.y + 1, period);
+  let top = mix(hash(x0, y0), hash(x1, y0), t.x);
+  let bottom = mix(hash(x0, y1), hash(x1, y1), t.x);
+  return mix(top, bottom, t.y);
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+  let size = textureDimensions(output);
+  if (i32(id.x) >= size.x || i32(id.y) >= size.y) {
+    return;
+  }
+  let uv = vec2<f32>(id.xy) / vec2<f32>(size);
+
+  // A tileable texture needs a whole number of cells across it.
+  var frequency = params.frequency;
+  var period = 0;
+  if (params.tileable != 0u) {
+    frequency = max(round(frequency), 1.0);
+    period = i32(frequency);
+  }
+
+  var value = 0.0;
+  var amplitude = 0.5;
+  var total = 0.0;
+  for (var octave = 0; octave < OCTAVES; octave = octave + 1) {
+    value = value + value_noise(uv * frequency, period) * amplitude;
+    total = total + amplitude;
+    frequency = frequency * 2.0;
+    period = period * 2;
+    amplitude = amplitude * 0.5;
+  }
+  value = value / total;
+
+  textureStore(output, vec2<i32>(id.xy), vec4<f32>(value, value, value, 1.0));
+}
@@ -0,0 +1,174 @@
+use wgpu::util::DeviceExt;
+
+use crate::config::Config;
+use crate::layout_cache::BindGroupLayoutCache;
+use crate::texture;
+
+/// Stencil value written wherever the selected object is drawn. The outline
+/// is only drawn where the stencil does *not* hold this value.
+pub const SELECTED_STENCIL_REFERENCE: u32 = 1;
+
+const OUTLINE_COLOR: [f32; 4] = [1.0, 0.6, 0.0, 1.0];
+/// In logical pixels, the outline is this times the scale factor wide.
+const OUTLINE_WIDTH: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OutlineUniform {
+  color: [f32; 4],
+  viewport: [f32; 2],
+  // Outline width in pixels, kept constant in screen space.
+  width: f32,
+  _padding: f32,
+}
+
+impl OutlineUniform {
+  fn new(config: &wgpu::SurfaceConfiguration, scale_factor: f64) -> Self {
+    Self {
+      color: OUTLINE_COLOR,
+      viewport: [config.width as f32, config.height as f32],
+      width: OUTLINE_WIDTH * scale_factor as f32,
+      _padding: 0.0,
+    }
+  }
+}
+
+pub struct Outline {
+  bind_group: wgpu::BindGroup,
+  buffer: wgpu::Buffer,
+  pipeline: wgpu::RenderPipeline,
+}
+
+impl Outline {
+  pub fn new(
+    device: &wgpu::Device,
+    bind_group_layouts: &mut BindGroupLayoutCache,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+    buffers: &[wgpu::VertexBufferLayout],
+    app_config: &Config,
+    scale_factor: f64,
+  ) -> Self {
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Outline Buffer"),
+      contents: bytemuck::cast_slice(&[OutlineUniform::new(config, scale_factor)]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let bind_group_layout = bind_group_layouts.get(
+      device,
+      &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None,
+        },
+        count: None,
+      }],
+      "outline_bind_group_layout",
+    );
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("outline_bind_group"),
+      layout: &bind_group_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: buffer.as_entire_binding(),
+      }],
+    });
+
+    let shader = device.create_shader_module(&wgpu::include_wgsl!("outline.wgsl"));
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Outline Pipeline Layout"),
+      bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+      push_constant_ranges: &[],
+    });
+
+    let stencil_face = wgpu::StencilFaceState {
+      compare: wgpu::CompareFunction::NotEqual,
+      fail_op: wgpu::StencilOperation::Keep,
+      depth_fail_op: wgpu::StencilOperation::Keep,
+      pass_op: wgpu::StencilOperation::Keep,
+    };
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("Outline Pipeline"),
+      layout: Some(&layout),
+      vertex: wgpu::VertexState {
+        module: &shader,
+        entry_point: "vs_main",
+        buffers,
+      },
+      fragment: Some(wgpu::FragmentState {
+        module: &shader,
+        entry_point: "fs_main",
+        targets: &[wgpu::ColorTargetState {
+          format: config.format,
+          blend: Some(wgpu::BlendState::REPLACE),
+          write_mask: wgpu::ColorWrites::ALL,
+        }],
+      }),
+      primitive: wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        // Culled like the scene, so it outlines the faces that are drawn.
+        front_face: app_config.front_face,
+        cull_mode: app_config.cull_mode,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: false,
+        conservative: false,
+      },
+      // The outline is drawn on top of everything so it stays visible even
+      // when the selected object is partially hidden.
+      depth_stencil: Some(wgpu::DepthStencilState {
+        format: texture::Texture::DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::Always,
+        stencil: wgpu::StencilState {
+          front: stencil_face,
+          back: stencil_face,
+          read_mask: 0xff,
+          write_mask: 0x00,
+        },
+        bias: wgpu::DepthBiasState::default(),
+      }),
+      multisample: wgpu::MultisampleState {
+        count: app_config.msaa_samples,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      multiview: None,
+    });
+
+    Self {
+      bind_group,
+      buffer,
+      pipeline,
+    }
+  }
+
+  /// Updates the outline for a new target size or display scale factor.
+  pub fn resize(
+    &self,
+    queue: &wgpu::Queue,
+    config: &wgpu::SurfaceConfiguration,
+    scale_factor: f64,
+  ) {
+    queue.write_buffer(
+      &self.buffer,
+      0,
+      bytemuck::cast_slice(&[OutlineUniform::new(config, scale_factor)]),
+    );
+  }
+
+  /// Binds the outline pipeline and its uniform at group 1. The caller binds
+  /// the camera at group 0 and redraws the selected object.
+  pub fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+    render_pass.set_pipeline(&self.pipeline);
+    render_pass.set_bind_group(1, &self.bind_group, &[]);
+    render_pass.set_stencil_reference(SELECTED_STENCIL_REFERENCE);
+  }
+}
@@ -0,0 +1,61 @@
+// Vertex shader
+struct CameraUniform {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct OutlineUniform {
+    color: vec4<f32>;
+    viewport: vec2<f32>;
+    width: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> outline: OutlineUniform;
+
+struct VertexInput {
+  [[location(0)]] position: vec3<f32>;
+};
+
+struct InstanceInput {
+  [[location(5)]] model_matrix_0: vec4<f32>;
+  [[location(6)]] model_matrix_1: vec4<f32>;
+  [[location(7)]] model_matrix_2: vec4<f32>;
+  [[location(8)]] model_matrix_3: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput, instance: InstanceInput) -> [[builtin(position)]] vec4<f32> {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  let view_proj_model = camera.view_proj * model_matrix;
+  var clip_position = view_proj_model * vec4<f32>(model.position, 1.0);
+  let clip_center = view_proj_model * vec4<f32>(0.0, 0.0, 0.0, 1.0);
+
+  // Push each vertex away from the object's center in screen space rather
+  // than scaling the mesh, so the outline keeps the same pixel width no
+  // matter how far away the camera is.
+  let ndc_offset = clip_position.xy / clip_position.w - clip_center.xy / clip_center.w;
+  if (length(ndc_offset) > 0.0) {
+    let pixel_to_ndc = 2.0 / outline.viewport;
+    clip_position = vec4<f32>(
+      clip_position.xy + normalize(ndc_offset) * outline.width * pixel_to_ndc * clip_position.w,
+      clip_position.zw,
+    );
+  }
+  return clip_position;
+}
+
+
+// Fragment shader
+[[stage(fragment)]]
+fn fs_main() -> [[location(0)]] vec4<f32> {
+    return outline.color;
+}
@@ -0,0 +1,370 @@
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::mesh::Vertex as _;
+use crate::shader_include::{self, EntryPoints};
+use crate::skinning::{self, SkinnedVertex};
+use crate::{texture, InstanceRaw, Vertex};
+
+/// Format of the offscreen ID target. Each object writes `id + 1` so that
+/// the cleared value of 0 can stand for "background".
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+const BACKGROUND_ID: u32 = 0;
+
+type MapResult = Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>;
+
+pub struct Picker {
+  depth_clear: f32,
+  depth_texture: texture::Texture,
+  id_texture: wgpu::Texture,
+  id_view: wgpu::TextureView,
+  map_result: MapResult,
+  occluder_pipeline: wgpu::RenderPipeline,
+  pending: bool,
+  pipeline: wgpu::RenderPipeline,
+  readback_buffer: wgpu::Buffer,
+  skinned_occluder_pipeline: wgpu::RenderPipeline,
+}
+
+impl Picker {
+  pub fn new(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+    joint_bind_group_layout: &wgpu::BindGroupLayout,
+    app_config: &Config,
+  ) -> Result<Self, Error> {
+    let (id_texture, id_view) = Self::create_id_texture(device, config);
+    let depth_texture = texture::Texture::create_depth_texture(device, config, 1, "picking_depth");
+
+    // Even though we only ever read back a single pixel, copies out of a
+    // texture need a row pitch aligned to 256 bytes, so the buffer is sized
+    // for one fully padded row.
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Picking Readback Buffer"),
+      size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+      mapped_at_creation: false,
+    });
+
+    let (pipeline, occluder_pipeline, skinned_occluder_pipeline) = create_pipelines(
+      device,
+      camera_bind_group_layout,
+      joint_bind_group_layout,
+      app_config,
+    )?;
+
+    Ok(Self {
+      depth_clear: app_config.depth_clear,
+      depth_texture,
+      id_texture,
+      id_view,
+      map_result: Arc::new(Mutex::new(None)),
+      occluder_pipeline,
+      pending: false,
+      pipeline,
+      readback_buffer,
+      skinned_occluder_pipeline,
+    })
+  }
+
+  fn create_id_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+  ) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("picking_id_texture"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: ID_FORMAT,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+  }
+
+  pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+    let (id_texture, id_view) = Self::create_id_texture(device, config);
+    self.id_texture = id_texture;
+    self.id_view = id_view;
+    self.depth_texture = texture::Texture::create_depth_texture(device, config, 1, "picking_depth");
+  }
+
+  /// Rebuilds the pipelines from `app_config`, after its culling changed.
+  pub fn rebuild_pipelines(
+    &mut self,
+    device: &wgpu::Device,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+    joint_bind_group_layout: &wgpu::BindGroupLayout,
+    app_config: &Config,
+  ) -> Result<(), Error> {
+    let (pipeline, occluder_pipeline, skinned_occluder_pipeline) = create_pipelines(
+      device,
+      camera_bind_group_layout,
+      joint_bind_group_layout,
+      app_config,
+    )?;
+    self.pipeline = pipeline;
+    self.occluder_pipeline = occluder_pipeline;
+    self.skinned_occluder_pipeline = skinned_occluder_pipeline;
+    Ok(())
+  }
+
+  /// Whether a readback is still in flight. The readback buffer can only be
+  /// mapped once at a time, so new picks are ignored until it completes.
+  pub fn is_pending(&self) -> bool {
+    self.pending
+  }
+
+  /// Begins the ID pass. The caller binds the camera at group 0, draws what
+  /// can't be picked after `bind_occluders` or `bind_skinned_occluders`, so
+  /// it still hides what's behind it, then the objects after `bind_objects`.
+  pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Picking Pass"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view: &self.id_view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Clear(wgpu::Color {
+            r: BACKGROUND_ID as f64,
+            g: 0.0,
+            b: 0.0,
+            a: 0.0,
+          }),
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+        view: &self.depth_texture.view,
+        depth_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(self.depth_clear),
+          store: true,
+        }),
+        stencil_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(0),
+          store: true,
+        }),
+      }),
+    })
+  }
+
+  /// Sets up `render_pass` to only write the depth of static meshes.
+  pub fn bind_occluders<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+    render_pass.set_pipeline(&self.occluder_pipeline);
+  }
+
+  /// Sets up `render_pass` to only write the depth of skinned meshes, with
+  /// their joint matrices at group 1.
+  pub fn bind_skinned_occluders<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+    render_pass.set_pipeline(&self.skinned_occluder_pipeline);
+  }
+
+  /// Sets up `render_pass` to write the ids of the instances drawn, by their
+  /// index.
+  pub fn bind_objects<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+    render_pass.set_pipeline(&self.pipeline);
+  }
+
+  /// Copies the single ID texel at (`x`, `y`) into the readback buffer.
+  pub fn copy_pixel(&self, encoder: &mut wgpu::CommandEncoder, x: u32, y: u32) {
+    encoder.copy_texture_to_buffer(
+      wgpu::ImageCopyTexture {
+        aspect: wgpu::TextureAspect::All,
+        texture: &self.id_texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d { x, y, z: 0 },
+      },
+      wgpu::ImageCopyBuffer {
+        buffer: &self.readback_buffer,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          bytes_per_row: std::num::NonZeroU32::new(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+          rows_per_image: std::num::NonZeroU32::new(1),
+        },
+      },
+      wgpu::Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: 1,
+      },
+    );
+  }
+
+  /// Starts mapping the readback buffer. Must be called after the commands
+  /// from `copy_pixel` have been submitted.
+  pub fn request_readback(&mut self, device: &wgpu::Device) {
+    let map_result = self.map_result.clone();
+    let mapping = self
+      .readback_buffer
+      .slice(..)
+      .map_async(wgpu::MapMode::Read);
+    self.pending = true;
+
+    cfg_if::cfg_if! {
+      if #[cfg(target_arch = "wasm32")] {
+        // The browser resolves the mapping on its own, we just can't block on it.
+        let _ = device;
+        wasm_bindgen_futures::spawn_local(async move {
+          let result = mapping.await;
+          *map_result.lock().unwrap() = Some(result);
+        });
+      } else {
+        device.poll(wgpu::Maintain::Wait);
+        let result = pollster::block_on(mapping);
+        *map_result.lock().unwrap() = Some(result);
+      }
+    }
+  }
+
+  /// Returns `Some` once a requested readback has completed, holding the
+  /// picked object id or `None` if the background was clicked.
+  pub fn poll_result(&mut self) -> Option<Option<u32>> {
+    if !self.pending {
+      return None;
+    }
+    let result = self.map_result.lock().unwrap().take()?;
+    self.pending = false;
+
+    if let Err(e) = result {
+      log::warn!("Failed to read back picking buffer: {:?}", e);
+      return None;
+    }
+
+    let id = {
+      let data = self.readback_buffer.slice(..).get_mapped_range();
+      let texel: [u8; 4] = data[..4].try_into().unwrap();
+      u32::from_ne_bytes(texel)
+    };
+    self.readback_buffer.unmap();
+
+    match id {
+      BACKGROUND_ID => Some(None),
+      id => Some(Some(id - 1)),
+    }
+  }
+}
+
+/// The ID pass pipelines for objects, static occluders and skinned
+/// occluders, culling and testing depth like the scene does.
+fn create_pipelines(
+  device: &wgpu::Device,
+  camera_bind_group_layout: &wgpu::BindGroupLayout,
+  joint_bind_group_layout: &wgpu::BindGroupLayout,
+  app_config: &Config,
+) -> Result<
+  (
+    wgpu::RenderPipeline,
+    wgpu::RenderPipeline,
+    wgpu::RenderPipeline,
+  ),
+  Error,
+> {
+  let compile_error = |source| Error::ShaderCompile {
+    path: "picking.wgsl",
+    source,
+  };
+  let shader =
+    shader_include::create_shader_module(device, "picking.wgsl", skinning::SHADER_CONSTANTS)
+      .map_err(compile_error)?;
+  let entry_points = |vertex| {
+    shader
+      .entry_points(vertex, "fs_main")
+      .map_err(compile_error)
+  };
+  let static_buffers = [Vertex::desc(), InstanceRaw::desc()];
+  let pipeline = create_pipeline(
+    device,
+    &[camera_bind_group_layout],
+    &entry_points("vs_main")?,
+    &static_buffers,
+    wgpu::ColorWrites::ALL,
+    app_config,
+    "Picking Pipeline",
+  );
+  let occluder_pipeline = create_pipeline(
+    device,
+    &[camera_bind_group_layout],
+    &entry_points("vs_main")?,
+    &static_buffers,
+    wgpu::ColorWrites::empty(),
+    app_config,
+    "Picking Occluder Pipeline",
+  );
+  let skinned_occluder_pipeline = create_pipeline(
+    device,
+    &[camera_bind_group_layout, joint_bind_group_layout],
+    &entry_points("vs_skinned")?,
+    &[SkinnedVertex::desc(), InstanceRaw::desc()],
+    wgpu::ColorWrites::empty(),
+    app_config,
+    "Picking Skinned Occluder Pipeline",
+  );
+  Ok((pipeline, occluder_pipeline, skinned_occluder_pipeline))
+}
+
+/// A pipeline of the ID pass, writing ids through `write_mask`.
+fn create_pipeline(
+  device: &wgpu::Device,
+  bind_group_layouts: &[&wgpu::BindGroupLayout],
+  entry_points: &EntryPoints,
+  buffers: &[wgpu::VertexBufferLayout],
+  write_mask: wgpu::ColorWrites,
+  app_config: &Config,
+  label: &str,
+) -> wgpu::RenderPipeline {
+  let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+    label: Some(label),
+    bind_group_layouts,
+    push_constant_ranges: &[],
+  });
+  device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    label: Some(label),
+    layout: Some(&layout),
+    vertex: wgpu::VertexState {
+      module: entry_points.module,
+      entry_point: entry_points.vertex,
+      buffers,
+    },
+    fragment: Some(wgpu::FragmentState {
+      module: entry_points.module,
+      entry_point: entry_points.fragment,
+      targets: &[wgpu::ColorTargetState {
+        format: ID_FORMAT,
+        // Integer targets can't be blended.
+        blend: None,
+        write_mask,
+      }],
+    }),
+    primitive: wgpu::PrimitiveState {
+      topology: wgpu::PrimitiveTopology::TriangleList,
+      strip_index_format: None,
+      front_face: app_config.front_face,
+      cull_mode: app_config.cull_mode,
+      polygon_mode: wgpu::PolygonMode::Fill,
+      unclipped_depth: false,
+      conservative: false,
+    },
+    depth_stencil: Some(wgpu::DepthStencilState {
+      format: texture::Texture::DEPTH_FORMAT,
+      depth_write_enabled: true,
+      depth_compare: app_config.depth_compare,
+      stencil: wgpu::StencilState::default(),
+      bias: wgpu::DepthBiasState::default(),
+    }),
+    multisample: wgpu::MultisampleState {
+      count: 1,
+      mask: !0,
+      alpha_to_coverage_enabled: false,
+    },
+    multiview: None,
+  })
+}
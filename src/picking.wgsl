@@ -0,0 +1,84 @@
+// Vertex shader
+struct CameraUniform {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+  [[location(0)]] position: vec3<f32>;
+};
+
+struct InstanceInput {
+  [[location(5)]] model_matrix_0: vec4<f32>;
+  [[location(6)]] model_matrix_1: vec4<f32>;
+  [[location(7)]] model_matrix_2: vec4<f32>;
+  [[location(8)]] model_matrix_3: vec4<f32>;
+};
+
+struct VertexOutput {
+  [[builtin(position)]] clip_position: vec4<f32>;
+  [[location(0), interpolate(flat)]] object_id: u32;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+  model: VertexInput,
+  instance: InstanceInput,
+  [[builtin(instance_index)]] instance_index: u32,
+) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  var out: VertexOutput;
+  // 0 is reserved for the background.
+  out.object_id = instance_index + 1u;
+  out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+  return out;
+}
+
+// MAX_JOINTS is injected from skinning.rs.
+struct JointUniform {
+    matrices: array<mat4x4<f32>, MAX_JOINTS>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> joints: JointUniform;
+
+struct SkinnedVertexInput {
+  [[location(0)]] position: vec3<f32>;
+  [[location(10)]] joints: vec4<u32>;
+  [[location(11)]] weights: vec4<f32>;
+};
+
+// Only hides what's behind the skinned model, its id is never written.
+[[stage(vertex)]]
+fn vs_skinned(model: SkinnedVertexInput, instance: InstanceInput) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  let skin_matrix =
+    joints.matrices[model.joints.x] * model.weights.x +
+    joints.matrices[model.joints.y] * model.weights.y +
+    joints.matrices[model.joints.z] * model.weights.z +
+    joints.matrices[model.joints.w] * model.weights.w;
+  var out: VertexOutput;
+  out.object_id = 0u;
+  out.clip_position = camera.view_proj * model_matrix * skin_matrix * vec4<f32>(model.position, 1.0);
+  return out;
+}
+
+
+// Fragment shader
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] u32 {
+    return in.object_id;
+}
@@ -0,0 +1,121 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::mesh::Vertex as _;
+use crate::skinning::{self, SkinnedVertex};
+use crate::{shader_include, texture, InstanceRaw, Vertex};
+
+/// The scene's main and skinned pipelines. Rebuilt when the sample count
+/// changes.
+pub fn create_scene_pipelines(
+  device: &wgpu::Device,
+  render_pipeline_layout: &wgpu::PipelineLayout,
+  skinned_pipeline_layout: &wgpu::PipelineLayout,
+  color_format: wgpu::TextureFormat,
+  app_config: &Config,
+) -> Result<(wgpu::RenderPipeline, wgpu::RenderPipeline), Error> {
+  let compile_error = |path| move |source| Error::ShaderCompile { path, source };
+  // Both include common.wgsl, which `include_wgsl!` can't resolve.
+  let shader = shader_include::create_shader_module(device, "shader.wgsl", &[])
+    .map_err(compile_error("shader.wgsl"))?;
+  let fragment = if app_config.show_normals {
+    "fs_normals"
+  } else {
+    "fs_main"
+  };
+  let render_pipeline = create_render_pipeline(
+    device,
+    render_pipeline_layout,
+    color_format,
+    &[Vertex::desc(), InstanceRaw::desc()],
+    &shader
+      .entry_points("vs_main", fragment)
+      .map_err(compile_error("shader.wgsl"))?,
+    app_config,
+    "Render Pipeline",
+  );
+
+  let skinned_shader =
+    shader_include::create_shader_module(device, "skinned.wgsl", skinning::SHADER_CONSTANTS)
+      .map_err(compile_error("skinned.wgsl"))?;
+  let skinned_pipeline = create_render_pipeline(
+    device,
+    skinned_pipeline_layout,
+    color_format,
+    &[SkinnedVertex::desc(), InstanceRaw::desc()],
+    &skinned_shader
+      .entry_points("vs_main", fragment)
+      .map_err(compile_error("skinned.wgsl"))?,
+    app_config,
+    "Skinned Pipeline",
+  );
+
+  Ok((render_pipeline, skinned_pipeline))
+}
+
+/// Builds a pipeline that shades into the scene and marks the depth-stencil
+/// buffer like the main scene pipeline does. The depth test, culling and
+/// sample count come from `app_config`.
+fn create_render_pipeline(
+  device: &wgpu::Device,
+  layout: &wgpu::PipelineLayout,
+  color_format: wgpu::TextureFormat,
+  vertex_layouts: &[wgpu::VertexBufferLayout],
+  entry_points: &shader_include::EntryPoints,
+  app_config: &Config,
+  label: &str,
+) -> wgpu::RenderPipeline {
+  let stencil_write = wgpu::StencilFaceState {
+    compare: wgpu::CompareFunction::Always,
+    fail_op: wgpu::StencilOperation::Keep,
+    depth_fail_op: wgpu::StencilOperation::Keep,
+    pass_op: wgpu::StencilOperation::Replace,
+  };
+
+  device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    label: Some(label),
+    layout: Some(layout),
+    vertex: wgpu::VertexState {
+      module: entry_points.module,
+      entry_point: entry_points.vertex,
+      buffers: vertex_layouts,
+    },
+    fragment: Some(wgpu::FragmentState {
+      module: entry_points.module,
+      entry_point: entry_points.fragment,
+      targets: &[wgpu::ColorTargetState {
+        format: color_format,
+        blend: Some(wgpu::BlendState::REPLACE),
+        write_mask: wgpu::ColorWrites::ALL,
+      }],
+    }),
+    primitive: wgpu::PrimitiveState {
+      topology: wgpu::PrimitiveTopology::TriangleList,
+      strip_index_format: None,
+      front_face: app_config.front_face,
+      cull_mode: app_config.cull_mode,
+      polygon_mode: wgpu::PolygonMode::Fill,
+      unclipped_depth: false,
+      conservative: false,
+    },
+    depth_stencil: Some(wgpu::DepthStencilState {
+      format: texture::Texture::DEPTH_FORMAT,
+      depth_write_enabled: true,
+      depth_compare: app_config.depth_compare,
+      // Every fragment that passes the depth test writes the current
+      // stencil reference, which is only non-zero for the selected object.
+      stencil: wgpu::StencilState {
+        front: stencil_write,
+        back: stencil_write,
+        read_mask: 0xff,
+        write_mask: 0xff,
+      },
+      bias: wgpu::DepthBiasState::default(),
+    }),
+    multisample: wgpu::MultisampleState {
+      count: app_config.msaa_samples,
+      mask: !0,
+      alpha_to_coverage_enabled: false,
+    },
+    multiview: None,
+  })
+}
@@ -0,0 +1,392 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Records a pass of the graph. Gets what the graph runs for, the pass's
+/// own encoder and the views of the textures it declared.
+pub type RunPass<C> = fn(&C, &mut wgpu::CommandEncoder, &PassViews);
+
+/// A texture the graph creates for its passes, at the graph's size, and
+/// creates again when that changes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Transient {
+  pub format: wgpu::TextureFormat,
+  pub sample_count: u32,
+  pub usage: wgpu::TextureUsages,
+}
+
+enum Resource {
+  Transient(Transient),
+  /// Owned outside the graph. Its view, if passes need one, is handed to
+  /// `execute`.
+  Imported,
+}
+
+struct Pass<C> {
+  name: &'static str,
+  reads: Vec<&'static str>,
+  writes: Vec<&'static str>,
+  run: RunPass<C>,
+}
+
+/// The passes of a frame and the textures they read and write. `compile`
+/// orders the passes so each runs after the ones writing what it reads,
+/// `execute` records them, each into its own command buffer.
+pub struct RenderGraph<C> {
+  resources: Vec<(&'static str, Resource)>,
+  passes: Vec<Pass<C>>,
+  /// Indices into `passes`, in the order they run.
+  order: Vec<usize>,
+  /// `None` until the first `resize`.
+  targets: Option<FrameTargets>,
+}
+
+/// The transient textures at one size, created together so the
+/// multisampled color, depth and the rest always match.
+struct FrameTargets {
+  width: u32,
+  height: u32,
+  views: HashMap<&'static str, wgpu::TextureView>,
+}
+
+impl<C> RenderGraph<C> {
+  pub fn new() -> Self {
+    Self {
+      resources: Vec::new(),
+      passes: Vec::new(),
+      order: Vec::new(),
+      targets: None,
+    }
+  }
+
+  /// Declares a texture the graph creates, see `resize`.
+  pub fn add_transient(&mut self, name: &'static str, transient: Transient) {
+    self.resources.push((name, Resource::Transient(transient)));
+  }
+
+  /// Declares a texture owned by someone else, written before the graph
+  /// runs or by one of its passes.
+  pub fn import(&mut self, name: &'static str) {
+    self.resources.push((name, Resource::Imported));
+  }
+
+  /// Adds a pass that reads and writes the declared textures. Passes
+  /// writing the same texture run in the order they're added.
+  pub fn add_pass(
+    &mut self,
+    name: &'static str,
+    reads: &[&'static str],
+    writes: &[&'static str],
+    run: RunPass<C>,
+  ) {
+    self.passes.push(Pass {
+      name,
+      reads: reads.to_vec(),
+      writes: writes.to_vec(),
+      run,
+    });
+  }
+
+  /// Works out the order the passes run in. Call after adding them all.
+  ///
+  /// Fails if a pass uses a texture that isn't declared, reads and writes
+  /// the same one, or reads a transient no pass writes, which would see
+  /// whatever the last frame left in it. Imported textures may be written
+  /// before the graph runs. Also fails if the passes depend on each other
+  /// in a cycle, naming them.
+  pub fn compile(&mut self) -> Result<()> {
+    for pass in &self.passes {
+      for name in pass.reads.iter().chain(&pass.writes) {
+        if !self.resources.iter().any(|(resource, _)| resource == name) {
+          bail!("Pass {} uses {}, which isn't declared", pass.name, name);
+        }
+      }
+      if let Some(name) = pass.reads.iter().find(|name| pass.writes.contains(name)) {
+        bail!(
+          "Pass {} reads and writes {}, it can't sample what it renders to",
+          pass.name,
+          name
+        );
+      }
+    }
+    for (name, resource) in &self.resources {
+      let produced = matches!(resource, Resource::Imported)
+        || self.passes.iter().any(|pass| pass.writes.contains(name));
+      if produced {
+        continue;
+      }
+      if let Some(reader) = self.passes.iter().find(|pass| pass.reads.contains(name)) {
+        bail!("Pass {} reads {}, but no pass writes it", reader.name, name);
+      }
+    }
+
+    // Writers of a texture run in the order they were added, then its
+    // readers.
+    let mut runs_after = vec![Vec::new(); self.passes.len()];
+    for (name, _) in &self.resources {
+      let writers = (0..self.passes.len())
+        .filter(|&index| self.passes[index].writes.contains(name))
+        .collect::<Vec<_>>();
+      for pair in writers.windows(2) {
+        runs_after[pair[1]].push(pair[0]);
+      }
+      if let Some(&last_writer) = writers.last() {
+        for (index, pass) in self.passes.iter().enumerate() {
+          if pass.reads.contains(name) && !writers.contains(&index) {
+            runs_after[index].push(last_writer);
+          }
+        }
+      }
+    }
+
+    // Runs the first pass, in the order added, whose dependencies all ran.
+    let mut order = Vec::with_capacity(self.passes.len());
+    while order.len() < self.passes.len() {
+      let next = (0..self.passes.len()).find(|index| {
+        !order.contains(index) && runs_after[*index].iter().all(|dep| order.contains(dep))
+      });
+      match next {
+        Some(index) => order.push(index),
+        None => bail!(
+          "Passes {} depend on each other in a cycle",
+          self.cycle(&runs_after, &order).join(" -> ")
+        ),
+      }
+    }
+    self.order = order;
+    Ok(())
+  }
+
+  /// Names of passes that wait on each other in a cycle, none of them in
+  /// `order`, with the first repeated at the end. Every pass left out of
+  /// `order` waits on another left out, so following those leads into one.
+  fn cycle(&self, runs_after: &[Vec<usize>], order: &[usize]) -> Vec<&'static str> {
+    let waiting_on = |index: usize| {
+      runs_after[index]
+        .iter()
+        .copied()
+        .find(|dep| !order.contains(dep))
+        .expect("A pass left out waits on nothing left out")
+    };
+    let mut path = vec![(0..self.passes.len())
+      .find(|index| !order.contains(index))
+      .expect("Every pass was ordered")];
+    loop {
+      let next = waiting_on(*path.last().unwrap());
+      if let Some(start) = path.iter().position(|&index| index == next) {
+        // Listed in the order they run in, each before the one waiting on it.
+        let mut cycle = path[start..]
+          .iter()
+          .rev()
+          .map(|&index| self.passes[index].name)
+          .collect::<Vec<_>>();
+        cycle.push(cycle[0]);
+        return cycle;
+      }
+      path.push(next);
+    }
+  }
+
+  /// Creates the transient textures at `width` by `height`, all of them,
+  /// replacing those from before, or none. On failure the ones from before
+  /// stay, still at their size, see `size`.
+  ///
+  /// Fails if a side is 0 or more than the device allows. Those are the
+  /// checks wgpu would otherwise panic on, through the uncaptured error
+  /// handler, halfway through creating them.
+  pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> Result<()> {
+    let descriptors =
+      self.target_descriptors(width, height, device.limits().max_texture_dimension_2d)?;
+    let views = descriptors
+      .iter()
+      .map(|(name, descriptor)| {
+        let texture = device.create_texture(descriptor);
+        (
+          *name,
+          texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        )
+      })
+      .collect();
+    self.targets = Some(FrameTargets {
+      width,
+      height,
+      views,
+    });
+    Ok(())
+  }
+
+  /// What each transient texture is created from at `width` by `height`,
+  /// all of them at that size.
+  fn target_descriptors(
+    &self,
+    width: u32,
+    height: u32,
+    max_dimension: u32,
+  ) -> Result<Vec<(&'static str, wgpu::TextureDescriptor<'static>)>> {
+    if width == 0 || height == 0 || width.max(height) > max_dimension {
+      bail!(
+        "Frame targets can't be {}x{}, the device allows 1 to {} pixels a side",
+        width,
+        height,
+        max_dimension
+      );
+    }
+    Ok(
+      self
+        .resources
+        .iter()
+        .filter_map(|(name, resource)| match resource {
+          Resource::Transient(transient) => Some((
+            *name,
+            wgpu::TextureDescriptor {
+              label: Some(name),
+              size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+              },
+              mip_level_count: 1,
+              sample_count: transient.sample_count,
+              dimension: wgpu::TextureDimension::D2,
+              format: transient.format,
+              usage: transient.usage,
+            },
+          )),
+          Resource::Imported => None,
+        })
+        .collect(),
+    )
+  }
+
+  /// Width and height of the transient textures, `None` before they're
+  /// first created.
+  pub fn size(&self) -> Option<(u32, u32)> {
+    self
+      .targets
+      .as_ref()
+      .map(|targets| (targets.width, targets.height))
+  }
+
+  /// Records the passes in order, each into its own command buffer, to be
+  /// submitted in the order returned. `imported` has the views of the
+  /// imported textures passes need one of.
+  pub fn execute(
+    &self,
+    context: &C,
+    device: &wgpu::Device,
+    imported: &[(&'static str, &wgpu::TextureView)],
+  ) -> Vec<wgpu::CommandBuffer> {
+    self
+      .order
+      .iter()
+      .map(|&index| {
+        let pass = &self.passes[index];
+        let views = pass
+          .reads
+          .iter()
+          .chain(&pass.writes)
+          .filter_map(|&name| {
+            let transient = self
+              .targets
+              .as_ref()
+              .and_then(|targets| targets.views.get(name));
+            let view = transient.or_else(|| {
+              imported
+                .iter()
+                .find(|(imported, _)| *imported == name)
+                .map(|(_, view)| *view)
+            })?;
+            Some((name, view))
+          })
+          .collect();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+          label: Some(pass.name),
+        });
+        (pass.run)(
+          context,
+          &mut encoder,
+          &PassViews {
+            pass: pass.name,
+            views,
+          },
+        );
+        encoder.finish()
+      })
+      .collect()
+  }
+}
+
+/// The views of the textures a pass declared.
+pub struct PassViews<'a> {
+  pass: &'static str,
+  views: HashMap<&'static str, &'a wgpu::TextureView>,
+}
+
+impl<'a> PassViews<'a> {
+  /// Panics unless the pass declared `name` and it has a view.
+  pub fn get(&self, name: &str) -> &'a wgpu::TextureView {
+    match self.views.get(name) {
+      Some(view) => view,
+      None => panic!("Pass {} has no view of {}", self.pass, name),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn transient(format: wgpu::TextureFormat, sample_count: u32) -> Transient {
+    Transient {
+      format,
+      sample_count,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    }
+  }
+
+  fn graph() -> RenderGraph<()> {
+    let mut graph = RenderGraph::new();
+    graph.import("output");
+    graph.add_transient("msaa", transient(wgpu::TextureFormat::Bgra8UnormSrgb, 4));
+    graph.add_transient("depth", transient(wgpu::TextureFormat::Depth32Float, 4));
+    graph.add_transient(
+      "resolved",
+      transient(wgpu::TextureFormat::Bgra8UnormSrgb, 1),
+    );
+    graph
+  }
+
+  #[test]
+  fn targets_share_one_size() {
+    let descriptors = graph().target_descriptors(640, 360, 8192).unwrap();
+    let names = descriptors.iter().map(|(name, _)| *name);
+    assert!(names.eq(["msaa", "depth", "resolved"]));
+    for (name, descriptor) in &descriptors {
+      assert_eq!(
+        descriptor.size,
+        wgpu::Extent3d {
+          width: 640,
+          height: 360,
+          depth_or_array_layers: 1,
+        },
+        "{}",
+        name
+      );
+    }
+    let sample_counts = descriptors.iter().map(|(_, d)| d.sample_count);
+    assert!(sample_counts.eq([4, 4, 1]));
+  }
+
+  #[test]
+  fn rejects_sizes_no_texture_can_have() {
+    let graph = graph();
+    for (width, height) in [(0, 360), (640, 0), (8193, 360), (640, 8193)] {
+      assert!(
+        graph.target_descriptors(width, height, 8192).is_err(),
+        "{}x{}",
+        width,
+        height
+      );
+    }
+    assert!(graph.target_descriptors(8192, 8192, 8192).is_ok());
+  }
+}
@@ -0,0 +1,182 @@
+use cgmath::prelude::*;
+use wgpu::util::DeviceExt;
+
+use crate::{Instance, Material, Vertex};
+
+/// A rectangle in pixels. It may hang off the target, `set_scissor_rect`
+/// clamps it first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+  pub x: i32,
+  pub y: i32,
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Rect {
+  pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+    Self {
+      x,
+      y,
+      width,
+      height,
+    }
+  }
+
+  /// A `width` by `height` rectangle centered on `ndc`, a point in `self`
+  /// in normalized device coordinates (y up, `[0, 0]` is the middle).
+  pub fn centered_at(&self, ndc: [f32; 2], width: u32, height: u32) -> Self {
+    let center_x = self.x + ((ndc[0] + 1.0) * 0.5 * self.width as f32) as i32;
+    let center_y = self.y + ((1.0 - ndc[1]) * 0.5 * self.height as f32) as i32;
+    Self {
+      x: center_x - width as i32 / 2,
+      y: center_y - height as i32 / 2,
+      width,
+      height,
+    }
+  }
+
+  /// The part of `self` inside `bounds`, which may be empty.
+  pub fn clamped_to(&self, bounds: &Rect) -> Rect {
+    let left = self.x.max(bounds.x);
+    let top = self.y.max(bounds.y);
+    let right = (self.x + self.width as i32).min(bounds.x + bounds.width as i32);
+    let bottom = (self.y + self.height as i32).min(bounds.y + bounds.height as i32);
+    Rect {
+      x: left,
+      y: top,
+      width: (right - left).max(0) as u32,
+      height: (bottom - top).max(0) as u32,
+    }
+  }
+}
+
+/// Restricts drawing to `rect` clamped to `bounds`, which should be the
+/// current viewport inside the target. Out of range scissor rects are a
+/// validation error, so always go through this. Returns false if nothing is
+/// left to draw into.
+pub fn set_scissor_rect(render_pass: &mut wgpu::RenderPass, rect: &Rect, bounds: &Rect) -> bool {
+  // `bounds` itself has to be inside the target, which the callers get from
+  // the target's size.
+  let clamped = rect.clamped_to(bounds);
+  render_pass.set_scissor_rect(
+    clamped.x as u32,
+    clamped.y as u32,
+    clamped.width,
+    clamped.height,
+  );
+  clamped.width > 0 && clamped.height > 0
+}
+
+const QUAD_HALF_EXTENT: f32 = 0.6;
+
+const QUAD_VERTICES: &[Vertex] = &[
+  Vertex {
+    position: [-QUAD_HALF_EXTENT, -QUAD_HALF_EXTENT, 0.0],
+    tex_coords: [0.0, 1.0],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  },
+  Vertex {
+    position: [QUAD_HALF_EXTENT, -QUAD_HALF_EXTENT, 0.0],
+    tex_coords: [1.0, 1.0],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  },
+  Vertex {
+    position: [QUAD_HALF_EXTENT, QUAD_HALF_EXTENT, 0.0],
+    tex_coords: [1.0, 0.0],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  },
+  Vertex {
+    position: [-QUAD_HALF_EXTENT, QUAD_HALF_EXTENT, 0.0],
+    tex_coords: [0.0, 0.0],
+    normal: [0.0, 0.0, 1.0],
+    color: [1.0, 1.0, 1.0, 1.0],
+  },
+];
+
+const QUAD_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+/// Degrees per second the demo quad spins by.
+const QUAD_SPEED: cgmath::Deg<f32> = cgmath::Deg(45.0);
+
+/// Fraction of the viewport the clip box covers in each direction.
+const CLIP_BOX_FRACTION: f32 = 0.3;
+
+/// A spinning quad clipped to a box that follows the cursor.
+pub struct ScissorDemo {
+  index_buffer: wgpu::Buffer,
+  instance: Instance,
+  instance_buffer: wgpu::Buffer,
+  vertex_buffer: wgpu::Buffer,
+}
+
+impl ScissorDemo {
+  pub fn new(device: &wgpu::Device) -> Self {
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Scissor Demo Vertex Buffer"),
+      contents: bytemuck::cast_slice(QUAD_VERTICES),
+      usage: wgpu::BufferUsages::VERTEX,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Scissor Demo Index Buffer"),
+      contents: bytemuck::cast_slice(QUAD_INDICES),
+      usage: wgpu::BufferUsages::INDEX,
+    });
+
+    let instance = Instance {
+      position: (0.0, 0.8, 1.5).into(),
+      rotation: cgmath::Quaternion::one(),
+      tint: [1.0; 4],
+    };
+    let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Scissor Demo Instance Buffer"),
+      contents: bytemuck::cast_slice(&[instance.to_raw()]),
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    Self {
+      index_buffer,
+      instance,
+      instance_buffer,
+      vertex_buffer,
+    }
+  }
+
+  pub fn update(&mut self, queue: &wgpu::Queue, dt: f32) {
+    let rotation = cgmath::Quaternion::from_angle_z(QUAD_SPEED * dt);
+    self.instance.rotation = (rotation * self.instance.rotation).normalize();
+    queue.write_buffer(
+      &self.instance_buffer,
+      0,
+      bytemuck::cast_slice(&[self.instance.to_raw()]),
+    );
+  }
+
+  /// Draws the quad with the scene pipeline already bound, clipped to a box
+  /// around `cursor` (in normalized device coordinates of `viewport`), then
+  /// resets the scissor to `viewport`.
+  pub fn draw<'a>(
+    &'a self,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    material: &'a Material,
+    viewport: &Rect,
+    cursor: [f32; 2],
+  ) {
+    let clip_box = viewport.centered_at(
+      cursor,
+      (viewport.width as f32 * CLIP_BOX_FRACTION) as u32,
+      (viewport.height as f32 * CLIP_BOX_FRACTION) as u32,
+    );
+    if set_scissor_rect(render_pass, &clip_box, viewport) {
+      render_pass.set_bind_group(0, &material.bind_group, &[]);
+      render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+      render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+      render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+      render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..1);
+    }
+    set_scissor_rect(render_pass, viewport, viewport);
+  }
+}
@@ -0,0 +1,320 @@
+use anyhow::*;
+use std::rc::Rc;
+
+/// Surface formats are all four bytes per pixel.
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Captures the scene into a PNG.
+///
+/// Multisampled textures can't be the source of a buffer copy, so a
+/// multisampled frame is first resolved into a single-sample texture of our
+/// own and copied out of that. Reading the scene rather than the surface also
+/// means nothing depends on the surface supporting `COPY_SRC`.
+pub struct Screenshot {
+  // Shared with the download in the browser, which outlives the frame.
+  readback_buffer: Rc<wgpu::Buffer>,
+  resolve_texture: wgpu::Texture,
+  resolve_view: wgpu::TextureView,
+  format: wgpu::TextureFormat,
+  width: u32,
+  height: u32,
+}
+
+impl Screenshot {
+  pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+    let (resolve_texture, resolve_view) = Self::create_resolve_texture(device, config);
+
+    Self {
+      readback_buffer: Rc::new(Self::create_readback_buffer(device, config)),
+      resolve_texture,
+      resolve_view,
+      format: config.format,
+      width: config.width,
+      height: config.height,
+    }
+  }
+
+  fn create_resolve_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+  ) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("screenshot_resolve_texture"),
+      size: wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: config.format,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+  }
+
+  fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded = width * BYTES_PER_PIXEL;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded.div_ceil(align) * align
+  }
+
+  fn create_readback_buffer(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+  ) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Screenshot Readback Buffer"),
+      size: (Self::padded_bytes_per_row(config.width) * config.height) as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+      mapped_at_creation: false,
+    })
+  }
+
+  pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+    *self = Self::new(device, config);
+  }
+
+  /// Resolves the multisampled `msaa_view` into the capture texture and copies
+  /// it into the readback buffer. `msaa_view` must have been stored by the
+  /// pass that drew the frame.
+  pub fn capture(&self, encoder: &mut wgpu::CommandEncoder, msaa_view: &wgpu::TextureView) {
+    // A pass without any draws that loads the samples and resolves them.
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Screenshot Resolve Pass"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view: msaa_view,
+        resolve_target: Some(&self.resolve_view),
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Load,
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: None,
+    });
+
+    self.capture_texture(encoder, &self.resolve_texture);
+  }
+
+  /// Copies the single-sample `texture`, the size the screenshot was created
+  /// with, into the readback buffer.
+  pub fn capture_texture(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+    encoder.copy_texture_to_buffer(
+      wgpu::ImageCopyTexture {
+        aspect: wgpu::TextureAspect::All,
+        texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+      },
+      wgpu::ImageCopyBuffer {
+        buffer: &self.readback_buffer,
+        layout: wgpu::ImageDataLayout {
+          offset: 0,
+          bytes_per_row: std::num::NonZeroU32::new(Self::padded_bytes_per_row(self.width)),
+          rows_per_image: std::num::NonZeroU32::new(self.height),
+        },
+      },
+      wgpu::Extent3d {
+        width: self.width,
+        height: self.height,
+        depth_or_array_layers: 1,
+      },
+    );
+  }
+
+  /// Waits for the commands from `capture` or `capture_texture` to finish and
+  /// writes the frame into `dir` as a PNG named after the time, creating
+  /// `dir` if needed. Must be called after they have been submitted. Returns
+  /// the path written to.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn save(&self, device: &wgpu::Device, dir: &str) -> Result<String> {
+    let pixels = self.read(device)?;
+
+    let path = timestamped_path(dir, "screenshot", "png")?;
+    // The samples are already sRGB encoded (or meant to be displayed as-is),
+    // which is what PNG viewers expect.
+    image::save_buffer(
+      &path,
+      &pixels,
+      self.width,
+      self.height,
+      image::ColorType::Rgba8,
+    )?;
+
+    Ok(path.display().to_string())
+  }
+
+  /// Waits for the commands from `capture` or `capture_texture` to finish and
+  /// returns the frame as tightly packed RGBA rows, `size` big.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn read(&self, device: &wgpu::Device) -> Result<Vec<u8>> {
+    let swap_red_blue = swaps_red_blue(self.format)?;
+    let slice = self.readback_buffer.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(mapping)?;
+    Ok(read_pixels(
+      &self.readback_buffer,
+      self.width,
+      self.height,
+      swap_red_blue,
+    ))
+  }
+
+  /// Width and height of the captured frames.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn size(&self) -> (u32, u32) {
+    (self.width, self.height)
+  }
+
+  /// Downloads the frame from `capture` or `capture_texture` as a PNG named
+  /// after the time, once the readback buffer is mapped. We can't block on
+  /// that in the browser, so this returns the file name straight away.
+  #[cfg(target_arch = "wasm32")]
+  pub fn save(&self, _device: &wgpu::Device, _dir: &str) -> Result<String> {
+    let swap_red_blue = swaps_red_blue(self.format)?;
+    // The browser renames downloads that would collide.
+    let name = format!("{}.png", file_stem("screenshot", &chrono::Local::now()));
+
+    let buffer = self.readback_buffer.clone();
+    let (width, height) = (self.width, self.height);
+    let file_name = name.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+      let result = async {
+        buffer.slice(..).map_async(wgpu::MapMode::Read).await?;
+        let pixels = read_pixels(&buffer, width, height, swap_red_blue);
+        let mut png = Vec::new();
+        image::ImageEncoder::write_image(
+          image::codecs::png::PngEncoder::new(&mut png),
+          &pixels,
+          width,
+          height,
+          image::ColorType::Rgba8,
+        )?;
+        download(&file_name, &png)
+      }
+      .await;
+      if let Err(e) = result {
+        log::warn!("Failed to download screenshot: {:?}", e);
+      }
+    });
+
+    Ok(name)
+  }
+}
+
+/// Copies the pixels out of the mapped readback `buffer` of a `width` by
+/// `height` frame without the row padding, as RGBA, and unmaps it.
+fn read_pixels(buffer: &wgpu::Buffer, width: u32, height: u32, swap_red_blue: bool) -> Vec<u8> {
+  let padded_bytes_per_row = Screenshot::padded_bytes_per_row(width) as usize;
+  let bytes_per_row = (width * BYTES_PER_PIXEL) as usize;
+  let mut pixels = Vec::with_capacity(bytes_per_row * height as usize);
+  {
+    let data = buffer.slice(..).get_mapped_range();
+    for row in data.chunks(padded_bytes_per_row) {
+      pixels.extend_from_slice(&row[..bytes_per_row]);
+    }
+  }
+  buffer.unmap();
+
+  if swap_red_blue {
+    for pixel in pixels.chunks_mut(BYTES_PER_PIXEL as usize) {
+      pixel.swap(0, 2);
+    }
+  }
+  pixels
+}
+
+fn swaps_red_blue(format: wgpu::TextureFormat) -> Result<bool> {
+  match format {
+    wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+    wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+    format => bail!("Can't save screenshots of a {:?} surface", format),
+  }
+}
+
+/// `prefix_YYYYMMDD_HHMMSS_mmm`.
+fn file_stem(prefix: &str, time: &chrono::DateTime<chrono::Local>) -> String {
+  format!("{}_{}", prefix, time.format("%Y%m%d_%H%M%S_%3f"))
+}
+
+/// `dir/prefix_YYYYMMDD_HHMMSS_mmm.extension` for the current local time,
+/// with `_1`, `_2`... after the time if that's taken. Creates `dir` if
+/// needed.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn timestamped_path(dir: &str, prefix: &str, extension: &str) -> Result<std::path::PathBuf> {
+  std::fs::create_dir_all(dir).with_context(|| format!("Couldn't create {}", dir))?;
+  let stem = file_stem(prefix, &chrono::Local::now());
+  Ok(free_path(std::path::Path::new(dir), &stem, extension))
+}
+
+/// `dir/stem.extension`, or with `_1`, `_2`... after `stem` if that's
+/// taken.
+#[cfg(not(target_arch = "wasm32"))]
+fn free_path(dir: &std::path::Path, stem: &str, extension: &str) -> std::path::PathBuf {
+  let mut path = dir.join(format!("{}.{}", stem, extension));
+  let mut suffix = 1;
+  while path.exists() {
+    path = dir.join(format!("{}_{}.{}", stem, suffix, extension));
+    suffix += 1;
+  }
+  path
+}
+
+#[cfg(target_arch = "wasm32")]
+fn download(file_name: &str, bytes: &[u8]) -> Result<()> {
+  use wasm_bindgen::JsCast;
+
+  let js_error = |e| anyhow!("{:?}", e);
+  let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+  let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(
+    &parts,
+    web_sys::BlobPropertyBag::new().type_("image/png"),
+  )
+  .map_err(js_error)?;
+  let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+
+  let document = web_sys::window()
+    .and_then(|window| window.document())
+    .context("No document to download from")?;
+  let link: web_sys::HtmlAnchorElement = document
+    .create_element("a")
+    .map_err(js_error)?
+    .dyn_into()
+    .map_err(|_| anyhow!("Created an element that isn't a link"))?;
+  link.set_href(&url);
+  link.set_download(file_name);
+  link.click();
+  web_sys::Url::revoke_object_url(&url).map_err(js_error)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::TimeZone;
+
+  #[test]
+  fn names_files_after_the_time() {
+    let time = chrono::Local
+      .with_ymd_and_hms(2024, 2, 29, 7, 5, 9)
+      .unwrap()
+      + chrono::Duration::milliseconds(42);
+    assert_eq!(file_stem("shot", &time), "shot_20240229_070509_042");
+  }
+
+  #[test]
+  fn numbers_taken_paths() {
+    let dir = std::env::temp_dir().join("screenshot_free_path_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["shot.png", "shot_1.png"] {
+      std::fs::write(dir.join(name), []).unwrap();
+    }
+    assert_eq!(free_path(&dir, "shot", "png"), dir.join("shot_2.png"));
+    assert_eq!(free_path(&dir, "other", "png"), dir.join("other.png"));
+    std::fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -1,26 +1,54 @@
-// Vertex shader
-struct CameraUniform {
-    view_proj: mat4x4<f32>;
-};
-
-[[group(1), binding(0)]]
-var<uniform> camera: CameraUniform;
+//!include "common.wgsl"
 
+// Vertex shader
 struct VertexInput {
   [[location(0)]] position: vec3<f32>;
   [[location(1)]] tex_coords: vec2<f32>;
+  [[location(2)]] normal: vec3<f32>;
+  [[location(3)]] color: vec4<f32>;
+};
+
+struct InstanceInput {
+  [[location(5)]] model_matrix_0: vec4<f32>;
+  [[location(6)]] model_matrix_1: vec4<f32>;
+  [[location(7)]] model_matrix_2: vec4<f32>;
+  [[location(8)]] model_matrix_3: vec4<f32>;
+  [[location(9)]] tint: vec4<f32>;
 };
 
 struct VertexOutput {
   [[builtin(position)]] clip_position: vec4<f32>;
   [[location(0)]] tex_coords: vec2<f32>;
+  [[location(1)]] world_normal: vec3<f32>;
+  [[location(2)]] world_position: vec3<f32>;
+  [[location(3)]] light_space_position: vec4<f32>;
+  [[location(4)]] color: vec4<f32>;
 };
 
 [[stage(vertex)]]
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  // Instances are only translated and rotated, so the upper 3x3 of the model
+  // matrix is fine for transforming normals.
+  let normal_matrix = mat3x3<f32>(
+    instance.model_matrix_0.xyz,
+    instance.model_matrix_1.xyz,
+    instance.model_matrix_2.xyz,
+  );
+  let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
   var out: VertexOutput;
   out.tex_coords = model.tex_coords;
-  out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+  out.color = model.color * instance.tint;
+  out.world_normal = normal_matrix * model.normal;
+  out.world_position = world_position.xyz;
+  out.light_space_position = light.view_proj * world_position;
+  out.clip_position = camera.view_proj * world_position;
   return out;
 }
 
@@ -32,7 +60,104 @@ var t_diffuse: texture_2d<f32>;
 [[group(0), binding(1)]]
 var s_diffuse: sampler;
 
+struct MaterialUniform {
+    base_color: vec4<f32>;
+    metallic: f32;
+    roughness: f32;
+    reflectivity: f32;
+};
+
+[[group(0), binding(2)]]
+var<uniform> material: MaterialUniform;
+
+[[group(0), binding(3)]]
+var t_metallic_roughness: texture_2d<f32>;
+
+[[group(3), binding(0)]]
+var t_environment: texture_cube<f32>;
+
+[[group(3), binding(1)]]
+var s_environment: sampler;
+
+let PI: f32 = 3.14159265359;
+
+// Trowbridge-Reitz GGX normal distribution: how many microfacets are aligned
+// with the half vector.
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+  let a = roughness * roughness;
+  let a2 = a * a;
+  let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+  return a2 / (PI * denom * denom);
+}
+
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+  // k remapped for direct (analytic) lighting.
+  let r = roughness + 1.0;
+  let k = r * r / 8.0;
+  return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+// Smith's method: microfacets shadowing each other from both the view and
+// the light directions.
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+  return geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+  return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let base_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.base_color * in.color;
+    let metallic_roughness = textureSample(t_metallic_roughness, s_diffuse, in.tex_coords);
+    let metallic = metallic_roughness.b * material.metallic;
+    let roughness = metallic_roughness.g * material.roughness;
+    let albedo = base_color.rgb;
+
+    let normal = normalize(in.world_normal);
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    let light_dir = -light.direction;
+    let half_dir = normalize(view_dir + light_dir);
+
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+    let h_dot_v = max(dot(half_dir, view_dir), 0.0);
+
+    // Dielectrics reflect around 4% at normal incidence, metals tint their
+    // reflection with the base color.
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+    // Cook-Torrance specular BRDF.
+    let d = distribution_ggx(n_dot_h, roughness);
+    let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+    let f = fresnel_schlick(h_dot_v, f0);
+    let specular = d * g * f / (4.0 * n_dot_v * max(n_dot_l, 0.0001));
+
+    // Whatever isn't reflected is refracted and diffused, except by metals
+    // which absorb it.
+    let k_d = (vec3<f32>(1.0) - f) * (1.0 - metallic);
+    let diffuse = k_d * albedo / PI;
+
+    let shadow = fetch_shadow(in.light_space_position);
+    let radiance = light.color * PI;
+    let direct = (diffuse + specular) * radiance * n_dot_l * shadow;
+
+    // No image based lighting yet, just a flat ambient term.
+    let ambient = vec3<f32>(0.15) * albedo;
+    let lit_color = ambient + direct;
+
+    // Mirror the direction from the camera to the fragment about the normal
+    // to find where on the environment this fragment reflects.
+    let reflection = textureSample(t_environment, s_environment, reflect(-view_dir, normal)).rgb;
+
+    let color = mix(lit_color, reflection, material.reflectivity);
+    return vec4<f32>(color, base_color.a);
+}
+
+// Replaces fs_main while the normals are shown, see `Config::show_normals`.
+[[stage(fragment)]]
+fn fs_normals(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return normal_color(in.world_normal);
 }
@@ -0,0 +1,168 @@
+use anyhow::*;
+use std::collections::HashSet;
+
+const INCLUDE_DIRECTIVE: &str = "//!include";
+
+/// The shaders that can be loaded or included, for the web where there are
+/// no files to read them from.
+#[cfg(target_arch = "wasm32")]
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+  ("common.wgsl", include_str!("common.wgsl")),
+  ("picking.wgsl", include_str!("picking.wgsl")),
+  ("shader.wgsl", include_str!("shader.wgsl")),
+  ("skinned.wgsl", include_str!("skinned.wgsl")),
+];
+
+/// Loads the WGSL source `name` (relative to `src`) with every
+/// `//!include "other.wgsl"` line replaced by that file, recursively. Each
+/// file is only included once, later includes of it are dropped, so shared
+/// declarations aren't repeated.
+pub fn load(name: &str) -> Result<String> {
+  let mut source = String::new();
+  resolve(name, &mut Vec::new(), &mut HashSet::new(), &mut source)?;
+  Ok(source)
+}
+
+/// Creates a module from `load(name)`, with `constants` declared first as
+/// `let NAME: u32 = value;`. Use these for anything the Rust side has to agree
+/// on, like array sizes, so it's only defined once. A constant can also
+/// toggle a code path with `if (NAME != 0u)`, which the compiler folds.
+///
+/// WGSL `override` constants would let one module be specialized per
+/// pipeline instead, but wgpu 0.12 has no way to set them (no `constants` in
+/// `VertexState` or `FragmentState`) and its naga doesn't parse `override`,
+/// so every backend gets the injected declarations. Newer wgpu takes them in
+/// `PipelineCompilationOptions::constants` on all backends, WebGL included,
+/// since naga substitutes them before translating the shader.
+pub fn create_shader_module(
+  device: &wgpu::Device,
+  name: &str,
+  constants: &[(&str, u32)],
+) -> Result<Shader> {
+  let mut source = String::new();
+  for (constant, value) in constants {
+    source.push_str(&format!("let {}: u32 = {}u;\n", constant, value));
+  }
+  source.push_str(&load(name)?);
+
+  let entry_points = naga::front::wgsl::parse_str(&source)
+    .map_err(|e| anyhow!("Couldn't parse {}:\n{}", name, e.emit_to_string(&source)))?
+    .entry_points
+    .into_iter()
+    .map(|entry_point| entry_point.name)
+    .collect();
+  let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+    label: Some(name),
+    source: wgpu::ShaderSource::Wgsl(source.into()),
+  });
+
+  Ok(Shader {
+    entry_points,
+    module,
+    name: name.to_owned(),
+  })
+}
+
+/// A shader module and the names of its entry points, so one file can hold
+/// several that pipelines pick from.
+pub struct Shader {
+  entry_points: Vec<String>,
+  pub module: wgpu::ShaderModule,
+  name: String,
+}
+
+impl Shader {
+  /// The `vertex` and `fragment` entry points, checking the module has them.
+  pub fn entry_points<'a>(&'a self, vertex: &'a str, fragment: &'a str) -> Result<EntryPoints<'a>> {
+    Ok(EntryPoints {
+      module: &self.module,
+      vertex: self.entry_point(vertex)?,
+      fragment: self.entry_point(fragment)?,
+    })
+  }
+
+  fn entry_point<'a>(&self, name: &'a str) -> Result<&'a str> {
+    if self
+      .entry_points
+      .iter()
+      .any(|entry_point| entry_point == name)
+    {
+      Ok(name)
+    } else {
+      bail!(
+        "{} has no entry point {}, only {}",
+        self.name,
+        name,
+        self.entry_points.join(", ")
+      )
+    }
+  }
+}
+
+/// What a render pipeline runs, the entry points known to exist in `module`.
+pub struct EntryPoints<'a> {
+  pub module: &'a wgpu::ShaderModule,
+  pub vertex: &'a str,
+  pub fragment: &'a str,
+}
+
+/// Appends `name` to `source`. `stack` holds the files currently being
+/// included, to catch cycles.
+fn resolve(
+  name: &str,
+  stack: &mut Vec<String>,
+  included: &mut HashSet<String>,
+  source: &mut String,
+) -> Result<()> {
+  if stack.iter().any(|including| including == name) {
+    bail!("Include cycle: {} -> {}", stack.join(" -> "), name);
+  }
+  if !included.insert(name.to_owned()) {
+    return Ok(());
+  }
+
+  let contents = read(name)?;
+  stack.push(name.to_owned());
+  for (number, line) in contents.lines().enumerate() {
+    match include_path(line).with_context(|| format!("At {}:{}", name, number + 1))? {
+      Some(path) => resolve(path, stack, included, source)
+        .with_context(|| format!("Included from {}:{}", name, number + 1))?,
+      None => {
+        source.push_str(line);
+        source.push('\n');
+      }
+    }
+  }
+  stack.pop();
+  Ok(())
+}
+
+/// The file an include directive on `line` names, if it is one.
+fn include_path(line: &str) -> Result<Option<&str>> {
+  let rest = match line.trim().strip_prefix(INCLUDE_DIRECTIVE) {
+    Some(rest) => rest.trim(),
+    None => return Ok(None),
+  };
+  rest
+    .strip_prefix('"')
+    .and_then(|rest| rest.strip_suffix('"'))
+    .map(Some)
+    .with_context(|| format!("Expected a quoted file name after {}", INCLUDE_DIRECTIVE))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(name: &str) -> Result<String> {
+  let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("src")
+    .join(name);
+  std::fs::read_to_string(&path).with_context(|| format!("Couldn't read shader {}", path.display()))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(name: &str) -> Result<String> {
+  EMBEDDED_SHADERS
+    .iter()
+    .find(|(embedded, _)| *embedded == name)
+    .map(|(_, source)| source.to_string())
+    .with_context(|| format!("No shader named {}", name))
+}
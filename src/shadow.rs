@@ -0,0 +1,273 @@
+use wgpu::util::DeviceExt;
+
+use crate::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::layout_cache::BindGroupLayoutCache;
+
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+// Half extent of the orthographic box the light renders. This needs to cover
+// everything that should cast or receive shadows.
+const LIGHT_EXTENT: f32 = 4.0;
+
+// Depth bias applied while rendering the shadow map. Without it, surfaces
+// facing the light compare against their own slightly quantised depth and
+// shadow themselves in stripes ("shadow acne"). Too much bias detaches
+// shadows from their casters ("peter panning"), so the slope-scaled term
+// does most of the work and only kicks in at grazing angles.
+//
+// Filtering trades off against this too: the comparison sampler uses linear
+// filtering, which gets us 2x2 hardware PCF for free. Softer, wider PCF kernels
+// sample further from the fragment and need more bias to stay acne-free.
+const DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+  constant: 2,
+  slope_scale: 2.0,
+  clamp: 0.0,
+};
+
+pub struct DirectionalLight {
+  pub position: cgmath::Point3<f32>,
+  pub target: cgmath::Point3<f32>,
+  pub color: cgmath::Vector3<f32>,
+}
+
+impl DirectionalLight {
+  fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+    let view = cgmath::Matrix4::look_at_rh(self.position, self.target, cgmath::Vector3::unit_y());
+    let proj = cgmath::ortho(
+      -LIGHT_EXTENT,
+      LIGHT_EXTENT,
+      -LIGHT_EXTENT,
+      LIGHT_EXTENT,
+      0.1,
+      20.0,
+    );
+
+    OPENGL_TO_WGPU_MATRIX * proj * view
+  }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+  view_proj: [[f32; 4]; 4],
+  // Uniforms require 16 byte spacing, hence the padding after each vec3.
+  direction: [f32; 3],
+  _padding: f32,
+  color: [f32; 3],
+  _padding2: f32,
+}
+
+impl LightUniform {
+  fn new(light: &DirectionalLight) -> Self {
+    use cgmath::InnerSpace;
+    Self {
+      view_proj: light.build_view_projection_matrix().into(),
+      direction: (light.target - light.position).normalize().into(),
+      _padding: 0.0,
+      color: light.color.into(),
+      _padding2: 0.0,
+    }
+  }
+}
+
+pub struct ShadowMap {
+  /// Light uniform, shadow map and comparison sampler for the main pass.
+  pub bind_group: wgpu::BindGroup,
+  pub bind_group_layout: wgpu::BindGroupLayout,
+  // Only the light uniform, since the shadow pass can't sample the texture
+  // it's rendering into.
+  light_bind_group: wgpu::BindGroup,
+  light_buffer: wgpu::Buffer,
+  pipeline: wgpu::RenderPipeline,
+  view: wgpu::TextureView,
+}
+
+impl ShadowMap {
+  pub fn new(
+    device: &wgpu::Device,
+    bind_group_layouts: &mut BindGroupLayoutCache,
+    light: &DirectionalLight,
+    buffers: &[wgpu::VertexBufferLayout],
+  ) -> Self {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("shadow_map"),
+      size: wgpu::Extent3d {
+        width: SHADOW_MAP_SIZE,
+        height: SHADOW_MAP_SIZE,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: SHADOW_FORMAT,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("shadow_sampler"),
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      compare: Some(wgpu::CompareFunction::LessEqual),
+      ..Default::default()
+    });
+
+    let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Light Buffer"),
+      contents: bytemuck::cast_slice(&[LightUniform::new(light)]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let light_uniform_entry = wgpu::BindGroupLayoutEntry {
+      binding: 0,
+      visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+      ty: wgpu::BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Uniform,
+        has_dynamic_offset: false,
+        min_binding_size: None,
+      },
+      count: None,
+    };
+
+    let light_bind_group_layout =
+      bind_group_layouts.get(device, &[light_uniform_entry], "light_bind_group_layout");
+
+    let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("light_bind_group"),
+      layout: &light_bind_group_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: light_buffer.as_entire_binding(),
+      }],
+    });
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[
+        light_uniform_entry,
+        wgpu::BindGroupLayoutEntry {
+          binding: 1,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Depth,
+          },
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 2,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+          count: None,
+        },
+      ],
+      label: Some("shadow_bind_group_layout"),
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("shadow_bind_group"),
+      layout: &bind_group_layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: light_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::TextureView(&view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: wgpu::BindingResource::Sampler(&sampler),
+        },
+      ],
+    });
+
+    let shader = device.create_shader_module(&wgpu::include_wgsl!("shadow.wgsl"));
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Shadow Pipeline Layout"),
+      bind_group_layouts: &[&light_bind_group_layout],
+      push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("Shadow Pipeline"),
+      layout: Some(&layout),
+      vertex: wgpu::VertexState {
+        module: &shader,
+        entry_point: "vs_main",
+        buffers,
+      },
+      // Depth only.
+      fragment: None,
+      primitive: wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        // The pentagons are single sided, but should still cast a shadow
+        // when the light is behind them.
+        cull_mode: None,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: false,
+        conservative: false,
+      },
+      depth_stencil: Some(wgpu::DepthStencilState {
+        format: SHADOW_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil: wgpu::StencilState::default(),
+        bias: DEPTH_BIAS,
+      }),
+      multisample: wgpu::MultisampleState {
+        count: 1,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      multiview: None,
+    });
+
+    Self {
+      bind_group,
+      bind_group_layout,
+      light_bind_group,
+      light_buffer,
+      pipeline,
+      view,
+    }
+  }
+
+  pub fn update_light(&self, queue: &wgpu::Queue, light: &DirectionalLight) {
+    queue.write_buffer(
+      &self.light_buffer,
+      0,
+      bytemuck::cast_slice(&[LightUniform::new(light)]),
+    );
+  }
+
+  /// Begins the depth-only pass from the light's point of view with the
+  /// shadow pipeline and light uniform bound. The caller issues the draws
+  /// for every shadow caster.
+  pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Shadow Pass"),
+      color_attachments: &[],
+      depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+        view: &self.view,
+        depth_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(1.0),
+          store: true,
+        }),
+        stencil_ops: None,
+      }),
+    });
+    render_pass.set_pipeline(&self.pipeline);
+    render_pass.set_bind_group(0, &self.light_bind_group, &[]);
+
+    render_pass
+  }
+}
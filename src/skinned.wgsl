@@ -0,0 +1,94 @@
+//!include "common.wgsl"
+
+// Vertex shader
+
+// MAX_JOINTS is injected from skinning.rs.
+struct JointUniform {
+    matrices: array<mat4x4<f32>, MAX_JOINTS>;
+};
+
+[[group(3), binding(0)]]
+var<uniform> joints: JointUniform;
+
+struct VertexInput {
+  [[location(0)]] position: vec3<f32>;
+  [[location(1)]] tex_coords: vec2<f32>;
+  [[location(2)]] normal: vec3<f32>;
+  [[location(10)]] joints: vec4<u32>;
+  [[location(11)]] weights: vec4<f32>;
+};
+
+struct InstanceInput {
+  [[location(5)]] model_matrix_0: vec4<f32>;
+  [[location(6)]] model_matrix_1: vec4<f32>;
+  [[location(7)]] model_matrix_2: vec4<f32>;
+  [[location(8)]] model_matrix_3: vec4<f32>;
+  [[location(9)]] tint: vec4<f32>;
+};
+
+struct VertexOutput {
+  [[builtin(position)]] clip_position: vec4<f32>;
+  [[location(0)]] tex_coords: vec2<f32>;
+  [[location(1)]] world_normal: vec3<f32>;
+  [[location(2)]] light_space_position: vec4<f32>;
+  [[location(3)]] tint: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+  let model_matrix = mat4x4<f32>(
+    instance.model_matrix_0,
+    instance.model_matrix_1,
+    instance.model_matrix_2,
+    instance.model_matrix_3,
+  );
+  // Blend the joint transforms by their weights (linear blend skinning).
+  let skin_matrix =
+    joints.matrices[model.joints.x] * model.weights.x +
+    joints.matrices[model.joints.y] * model.weights.y +
+    joints.matrices[model.joints.z] * model.weights.z +
+    joints.matrices[model.joints.w] * model.weights.w;
+  let world_matrix = model_matrix * skin_matrix;
+  // Fine for normals as long as the joints don't scale non-uniformly.
+  let normal_matrix = mat3x3<f32>(
+    world_matrix[0].xyz,
+    world_matrix[1].xyz,
+    world_matrix[2].xyz,
+  );
+  let world_position = world_matrix * vec4<f32>(model.position, 1.0);
+
+  var out: VertexOutput;
+  out.tex_coords = model.tex_coords;
+  out.world_normal = normal_matrix * model.normal;
+  out.light_space_position = light.view_proj * world_position;
+  out.clip_position = camera.view_proj * world_position;
+  out.tint = instance.tint;
+  return out;
+}
+
+
+// Fragment shader
+[[group(0), binding(0)]]
+var t_diffuse: texture_2d<f32>;
+
+[[group(0), binding(1)]]
+var s_diffuse: sampler;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+
+    let ambient_strength = 0.15;
+    let normal = normalize(in.world_normal);
+    let diffuse_strength = max(dot(normal, -light.direction), 0.0);
+    let shadow = fetch_shadow(in.light_space_position);
+
+    let lighting = (ambient_strength + diffuse_strength * shadow) * light.color;
+    return vec4<f32>(lighting * object_color.rgb, object_color.a);
+}
+
+// Replaces fs_main while the normals are shown, see `Config::show_normals`.
+[[stage(fragment)]]
+fn fs_normals(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return normal_color(in.world_normal);
+}
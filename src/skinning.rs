@@ -0,0 +1,402 @@
+use anyhow::*;
+use cgmath::prelude::*;
+
+use crate::draw_queue::DrawItem;
+use crate::mesh::{self, Mesh};
+
+/// Size of the joint matrix array in `skinned.wgsl`. Joints are passed in a
+/// uniform rather than a storage buffer so this also works on WebGL.
+pub const MAX_JOINTS: usize = 64;
+
+/// Constants `skinned.wgsl` expects to be defined, see
+/// `shader_include::create_shader_module`.
+pub const SHADER_CONSTANTS: &[(&str, u32)] = &[("MAX_JOINTS", MAX_JOINTS as u32)];
+
+const JOINT_BUFFER_SIZE: wgpu::BufferAddress =
+  (MAX_JOINTS * std::mem::size_of::<[[f32; 4]; 4]>()) as wgpu::BufferAddress;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinnedVertex {
+  position: [f32; 3],
+  tex_coords: [f32; 2],
+  normal: [f32; 3],
+  joints: [u32; 4],
+  weights: [f32; 4],
+}
+
+impl SkinnedVertex {
+  // Instance data sits at 5-9, so joints and weights go after it.
+  const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+    0 => Float32x3,
+    1 => Float32x2,
+    2 => Float32x3,
+    10 => Uint32x4,
+    11 => Float32x4,
+  ];
+}
+
+impl mesh::Vertex for SkinnedVertex {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    wgpu::VertexBufferLayout {
+      array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+      step_mode: wgpu::VertexStepMode::Vertex,
+      attributes: &Self::ATTRIBS,
+    }
+  }
+}
+
+#[derive(Copy, Clone)]
+struct Transform {
+  translation: cgmath::Vector3<f32>,
+  rotation: cgmath::Quaternion<f32>,
+  scale: cgmath::Vector3<f32>,
+}
+
+impl Transform {
+  fn matrix(&self) -> cgmath::Matrix4<f32> {
+    cgmath::Matrix4::from_translation(self.translation)
+      * cgmath::Matrix4::from(self.rotation)
+      * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+  }
+}
+
+struct Node {
+  children: Vec<usize>,
+  // The node's rest pose, which animation channels override.
+  transform: Transform,
+}
+
+enum Keyframes {
+  Translation(Vec<cgmath::Vector3<f32>>),
+  Rotation(Vec<cgmath::Quaternion<f32>>),
+  Scale(Vec<cgmath::Vector3<f32>>),
+}
+
+struct Channel {
+  node: usize,
+  times: Vec<f32>,
+  keyframes: Keyframes,
+}
+
+impl Channel {
+  /// Finds the keyframes either side of `time` and how far between them it is.
+  fn keyframe_span(&self, time: f32) -> (usize, usize, f32) {
+    let last = self.times.len() - 1;
+    if time <= self.times[0] {
+      return (0, 0, 0.0);
+    }
+    if time >= self.times[last] {
+      return (last, last, 0.0);
+    }
+    let next = self.times.partition_point(|t| *t <= time);
+    let previous = next - 1;
+    let span = self.times[next] - self.times[previous];
+    (previous, next, (time - self.times[previous]) / span)
+  }
+
+  fn apply(&self, time: f32, transform: &mut Transform) {
+    let (previous, next, t) = self.keyframe_span(time);
+    match &self.keyframes {
+      Keyframes::Translation(values) => {
+        transform.translation = values[previous].lerp(values[next], t);
+      }
+      Keyframes::Rotation(values) => {
+        transform.rotation = values[previous].nlerp(values[next], t);
+      }
+      Keyframes::Scale(values) => {
+        transform.scale = values[previous].lerp(values[next], t);
+      }
+    }
+  }
+}
+
+struct Animation {
+  channels: Vec<Channel>,
+  duration: f32,
+}
+
+/// A single skinned mesh with one looping animation clip, loaded from a
+/// binary glTF (.glb).
+pub struct SkinnedModel {
+  animation: Option<Animation>,
+  inverse_bind_matrices: Vec<cgmath::Matrix4<f32>>,
+  joint_buffer: wgpu::Buffer,
+  pub joint_bind_group: wgpu::BindGroup,
+  joints: Vec<usize>,
+  mesh: Mesh,
+  nodes: Vec<Node>,
+  roots: Vec<usize>,
+  time: f32,
+}
+
+impl SkinnedModel {
+  pub fn joint_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          // Makes creating the pipeline fail if the shader's joint array
+          // doesn't fit the buffer.
+          min_binding_size: wgpu::BufferSize::new(JOINT_BUFFER_SIZE),
+        },
+        count: None,
+      }],
+      label: Some("joint_bind_group_layout"),
+    })
+  }
+
+  pub fn from_glb(
+    device: &wgpu::Device,
+    joint_bind_group_layout: &wgpu::BindGroupLayout,
+    bytes: &[u8],
+  ) -> Result<Self> {
+    let gltf = gltf::Gltf::from_slice(bytes)?;
+    let blob = gltf.blob.as_deref();
+    let buffers = gltf
+      .buffers()
+      .map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Bin => blob.ok_or_else(|| anyhow!("glb is missing its BIN chunk")),
+        gltf::buffer::Source::Uri(uri) => bail!("external buffer {} isn't supported", uri),
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let get_buffer = |buffer: gltf::Buffer| buffers.get(buffer.index()).copied();
+
+    let nodes = gltf
+      .nodes()
+      .map(|node| {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        Node {
+          children: node.children().map(|child| child.index()).collect(),
+          transform: Transform {
+            translation: translation.into(),
+            rotation: cgmath::Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]),
+            scale: scale.into(),
+          },
+        }
+      })
+      .collect::<Vec<_>>();
+
+    let scene = gltf
+      .default_scene()
+      .or_else(|| gltf.scenes().next())
+      .ok_or_else(|| anyhow!("glTF has no scenes"))?;
+    let roots = scene.nodes().map(|node| node.index()).collect();
+
+    // The skinned mesh's own node transform is ignored, as per the spec:
+    // joint matrices already place the vertices relative to the scene.
+    let skinned_node = gltf
+      .nodes()
+      .find(|node| node.mesh().is_some() && node.skin().is_some())
+      .ok_or_else(|| anyhow!("glTF has no skinned mesh"))?;
+    let mesh = skinned_node.mesh().unwrap();
+    let skin = skinned_node.skin().unwrap();
+
+    let joints = skin.joints().map(|joint| joint.index()).collect::<Vec<_>>();
+    if joints.len() > MAX_JOINTS {
+      bail!(
+        "skin has {} joints, at most {} are supported",
+        joints.len(),
+        MAX_JOINTS
+      );
+    }
+    let inverse_bind_matrices: Vec<_> = match skin.reader(get_buffer).read_inverse_bind_matrices() {
+      Some(matrices) => matrices.map(cgmath::Matrix4::from).collect(),
+      None => vec![cgmath::Matrix4::identity(); joints.len()],
+    };
+    if inverse_bind_matrices.len() != joints.len() {
+      bail!(
+        "skin has {} joints but {} inverse bind matrices",
+        joints.len(),
+        inverse_bind_matrices.len()
+      );
+    }
+
+    let primitive = mesh
+      .primitives()
+      .next()
+      .ok_or_else(|| anyhow!("skinned mesh has no primitives"))?;
+    let reader = primitive.reader(get_buffer);
+    let positions = reader
+      .read_positions()
+      .ok_or_else(|| anyhow!("skinned mesh has no positions"))?;
+    let mut normals = reader.read_normals();
+    let mut tex_coords = reader.read_tex_coords(0).map(|t| t.into_f32());
+    let mut vertex_joints = reader
+      .read_joints(0)
+      .ok_or_else(|| anyhow!("skinned mesh has no joint indices"))?
+      .into_u16();
+    let mut weights = reader
+      .read_weights(0)
+      .ok_or_else(|| anyhow!("skinned mesh has no joint weights"))?
+      .into_f32();
+
+    let vertices = positions
+      .map(|position| {
+        let joints = vertex_joints.next().unwrap_or_default();
+        SkinnedVertex {
+          position,
+          tex_coords: tex_coords
+            .as_mut()
+            .and_then(|t| t.next())
+            .unwrap_or_default(),
+          normal: normals
+            .as_mut()
+            .and_then(|n| n.next())
+            .unwrap_or([0.0, 0.0, 1.0]),
+          joints: joints.map(u32::from),
+          weights: weights.next().unwrap_or([1.0, 0.0, 0.0, 0.0]),
+        }
+      })
+      .collect::<Vec<_>>();
+    let indices = match reader.read_indices() {
+      Some(indices) => indices.into_u32().collect(),
+      None => (0..vertices.len() as u32).collect::<Vec<_>>(),
+    };
+
+    let animation = gltf.animations().next().map(|animation| {
+      let channels = animation
+        .channels()
+        .filter_map(|channel| {
+          let reader = channel.reader(get_buffer);
+          let times = reader.read_inputs()?.collect::<Vec<_>>();
+          let keyframes = match reader.read_outputs()? {
+            gltf::animation::util::ReadOutputs::Translations(values) => {
+              Keyframes::Translation(values.map(cgmath::Vector3::from).collect())
+            }
+            gltf::animation::util::ReadOutputs::Rotations(values) => Keyframes::Rotation(
+              values
+                .into_f32()
+                .map(|[x, y, z, w]| cgmath::Quaternion::new(w, x, y, z))
+                .collect(),
+            ),
+            gltf::animation::util::ReadOutputs::Scales(values) => {
+              Keyframes::Scale(values.map(cgmath::Vector3::from).collect())
+            }
+            gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => return None,
+          };
+          if channel.sampler().interpolation() != gltf::animation::Interpolation::Linear {
+            log::warn!("Only linear interpolation is supported, skipping channel");
+            return None;
+          }
+          if times.is_empty() {
+            return None;
+          }
+          Some(Channel {
+            node: channel.target().node().index(),
+            times,
+            keyframes,
+          })
+        })
+        .collect::<Vec<_>>();
+      let duration = channels
+        .iter()
+        .map(|channel| *channel.times.last().unwrap())
+        .fold(0.0, f32::max);
+      Animation { channels, duration }
+    });
+
+    let mesh = Mesh::new(device, "Skinned", &vertices, &indices);
+    let joint_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Joint Buffer"),
+      size: JOINT_BUFFER_SIZE,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    let joint_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("joint_bind_group"),
+      layout: joint_bind_group_layout,
+      entries: &[wgpu::BindGroupEntry {
+        binding: 0,
+        resource: joint_buffer.as_entire_binding(),
+      }],
+    });
+
+    Ok(Self {
+      animation,
+      inverse_bind_matrices,
+      joint_buffer,
+      joint_bind_group,
+      joints,
+      mesh,
+      nodes,
+      roots,
+      time: 0.0,
+    })
+  }
+
+  pub fn is_animated(&self) -> bool {
+    self.animation.is_some()
+  }
+
+  /// Advances the animation by `dt` seconds and uploads the new joint matrices.
+  pub fn update(&mut self, queue: &wgpu::Queue, dt: f32) {
+    let mut transforms = self
+      .nodes
+      .iter()
+      .map(|node| node.transform)
+      .collect::<Vec<_>>();
+
+    if let Some(animation) = &self.animation {
+      if animation.duration > 0.0 {
+        self.time = (self.time + dt) % animation.duration;
+      }
+      for channel in &animation.channels {
+        channel.apply(self.time, &mut transforms[channel.node]);
+      }
+    }
+
+    let mut global_transforms = vec![cgmath::Matrix4::identity(); self.nodes.len()];
+    let mut stack = self
+      .roots
+      .iter()
+      .map(|root| (*root, cgmath::Matrix4::identity()))
+      .collect::<Vec<_>>();
+    while let Some((index, parent)) = stack.pop() {
+      let global = parent * transforms[index].matrix();
+      global_transforms[index] = global;
+      stack.extend(
+        self.nodes[index]
+          .children
+          .iter()
+          .map(|child| (*child, global)),
+      );
+    }
+
+    let mut joint_matrices = [[[0.0f32; 4]; 4]; MAX_JOINTS];
+    for (i, joint) in self.joints.iter().enumerate() {
+      joint_matrices[i] = (global_transforms[*joint] * self.inverse_bind_matrices[i]).into();
+    }
+    queue.write_buffer(&self.joint_buffer, 0, bytemuck::cast_slice(&joint_matrices));
+  }
+
+  /// Draws the model into `render_pass` with whatever pipeline is set,
+  /// binding the joint matrices at `joint_group` and taking one instance
+  /// from `instance_buffer`.
+  pub fn draw<'a>(
+    &'a self,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    joint_group: u32,
+    instance_buffer: wgpu::BufferSlice<'a>,
+  ) {
+    render_pass.set_bind_group(joint_group, &self.joint_bind_group, &[]);
+    self.mesh.draw_instanced(render_pass, instance_buffer, 0..1);
+  }
+
+  /// A draw of the model with `pipeline`, binding `bind_groups` followed by
+  /// the joint matrices, and taking one instance from `instance_buffer`.
+  pub fn draw_item<'a>(
+    &'a self,
+    pipeline: &'a wgpu::RenderPipeline,
+    mut bind_groups: Vec<&'a wgpu::BindGroup>,
+    instance_buffer: wgpu::BufferSlice<'a>,
+  ) -> DrawItem<'a> {
+    bind_groups.push(&self.joint_bind_group);
+    self
+      .mesh
+      .draw_item(pipeline, bind_groups, instance_buffer, 0..1, 0)
+  }
+}
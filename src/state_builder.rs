@@ -0,0 +1,90 @@
+use winit::window::Window;
+
+use crate::{Config, Error, RenderCallback, State, Target, UpdateCallback};
+
+/// Sets up a `State` for a window a setting at a time, starting from the
+/// `Config` defaults. Anything without a method here can be set on a
+/// `Config` given to `from_config`.
+#[derive(Default)]
+pub struct StateBuilder {
+  config: Config,
+  render_callback: Option<Box<RenderCallback>>,
+  update_callback: Option<Box<UpdateCallback>>,
+}
+
+impl StateBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn from_config(config: Config) -> Self {
+    Self {
+      config,
+      ..Default::default()
+    }
+  }
+
+  /// The config the `State` is built with.
+  pub fn config(&self) -> &Config {
+    &self.config
+  }
+
+  /// What the scene is cleared to when there's no background image.
+  pub fn clear_color(mut self, color: wgpu::Color) -> Self {
+    self.config.clear_color = color;
+    self.config.clear_color_hex = None;
+    self
+  }
+
+  /// Sets `vsync` and `tearing` to present with `present_mode`, falling
+  /// back to `Fifo` where the surface doesn't support it.
+  pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+    self.config.vsync = present_mode == wgpu::PresentMode::Fifo;
+    self.config.tearing = present_mode == wgpu::PresentMode::Immediate;
+    self
+  }
+
+  /// Multisample count of the scene, lowered to what the adapter supports.
+  pub fn msaa(mut self, samples: u32) -> Self {
+    self.config.msaa_samples = samples;
+    self
+  }
+
+  pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+    self.config.power_preference = power_preference;
+    self
+  }
+
+  /// Features to create the device with, say for a render callback that
+  /// needs them. Building fails on adapters without them.
+  pub fn features(mut self, features: wgpu::Features) -> Self {
+    self.config.features = features;
+    self
+  }
+
+  /// Draws in the scene pass after the scene, see
+  /// `State::set_render_callback`.
+  pub fn render_callback(
+    mut self,
+    callback: impl for<'a> FnMut(&mut wgpu::RenderPass<'a>, &'a State) + 'static,
+  ) -> Self {
+    self.render_callback = Some(Box::new(callback));
+    self
+  }
+
+  /// Runs at the end of every update, see `State::set_update_callback`.
+  pub fn update_callback(mut self, callback: impl FnMut(&mut State, f32) + 'static) -> Self {
+    self.update_callback = Some(Box::new(callback));
+    self
+  }
+
+  /// Creates the device and everything drawn with it for `window`, with the
+  /// callbacks set. Fails if there's no suitable adapter or it can't create
+  /// a device.
+  pub async fn build(self, window: &Window) -> Result<State, Error> {
+    let mut state = State::new(Target::Window(window), self.config).await?;
+    *state.render_callback.get_mut() = self.render_callback;
+    state.update_callback = self.update_callback;
+    Ok(state)
+  }
+}
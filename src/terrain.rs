@@ -0,0 +1,60 @@
+use anyhow::{bail, Result};
+use cgmath::prelude::*;
+
+use crate::{mesh, Vertex};
+
+/// Largest heightmap side that still fits the u16 indices of `mesh::grid`.
+const MAX_HEIGHTMAP_SIZE: u32 = 256;
+
+/// A grid with one vertex per pixel of `heightmap`, centered on the origin.
+/// Neighboring vertices are `spacing` apart in X and Z, and a white pixel is
+/// `amplitude` above a black one. The top of the image points to -Z.
+pub fn from_heightmap(
+  heightmap: &image::DynamicImage,
+  spacing: f32,
+  amplitude: f32,
+) -> Result<(Vec<Vertex>, Vec<u16>)> {
+  let heightmap = heightmap.to_luma16();
+  let (width, depth) = heightmap.dimensions();
+  if width < 2 || depth < 2 {
+    bail!(
+      "Heightmap is {}x{}, it needs at least 2x2 pixels",
+      width,
+      depth
+    );
+  }
+  if width > MAX_HEIGHTMAP_SIZE || depth > MAX_HEIGHTMAP_SIZE {
+    bail!(
+      "Heightmap is {}x{}, it can be at most {}x{} pixels",
+      width,
+      depth,
+      MAX_HEIGHTMAP_SIZE,
+      MAX_HEIGHTMAP_SIZE
+    );
+  }
+
+  let height = |x: u32, z: u32| heightmap.get_pixel(x, z)[0] as f32 / u16::MAX as f32 * amplitude;
+
+  // `grid` lays its vertices out row by row from -Z, like the pixels.
+  let (mut vertices, indices) = mesh::grid(
+    (width - 1) as f32 * spacing,
+    (depth - 1) as f32 * spacing,
+    (width - 1) as u16,
+    (depth - 1) as u16,
+  );
+  for z in 0..depth {
+    for x in 0..width {
+      // Central differences, one-sided at the edges.
+      let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+      let (back, front) = (z.saturating_sub(1), (z + 1).min(depth - 1));
+      let slope_x = (height(right, z) - height(left, z)) / ((right - left) as f32 * spacing);
+      let slope_z = (height(x, front) - height(x, back)) / ((front - back) as f32 * spacing);
+      let normal = cgmath::Vector3::new(-slope_x, 1.0, -slope_z).normalize();
+
+      let vertex = &mut vertices[(z * width + x) as usize];
+      vertex.position[1] = height(x, z);
+      vertex.normal = normal.into();
+    }
+  }
+  Ok((vertices, indices))
+}
@@ -1,13 +1,110 @@
 use anyhow::*;
 use image::GenericImageView;
 
+/// A texture's view and sampler. The view keeps the texture alive.
 pub struct Texture {
-  pub texture: wgpu::Texture,
   pub view: wgpu::TextureView,
   pub sampler: wgpu::Sampler,
+  pub size: wgpu::Extent3d,
 }
 
 impl Texture {
+  // Carries a stencil aspect alongside depth for the selection outline.
+  pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+  pub fn create_depth_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+    label: &str,
+  ) -> Self {
+    let size = wgpu::Extent3d {
+      width: config.width,
+      height: config.height,
+      depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some(label),
+      size,
+      mip_level_count: 1,
+      sample_count,
+      dimension: wgpu::TextureDimension::D2,
+      format: Self::DEPTH_FORMAT,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      compare: Some(wgpu::CompareFunction::LessEqual),
+      lod_min_clamp: -100.0,
+      lod_max_clamp: 100.0,
+      ..Default::default()
+    });
+
+    Self {
+      view,
+      sampler,
+      size,
+    }
+  }
+
+  /// Creates a 1x1 texture of a single linear color. Handy as a stand-in when
+  /// a material doesn't provide an optional texture.
+  pub fn from_color(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    color: [u8; 4],
+    label: &str,
+  ) -> Self {
+    let size = wgpu::Extent3d {
+      width: 1,
+      height: 1,
+      depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some(label),
+      size,
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::Rgba8Unorm,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    queue.write_texture(
+      wgpu::ImageCopyTexture {
+        aspect: wgpu::TextureAspect::All,
+        texture: &texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d::ZERO,
+      },
+      &color,
+      wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: std::num::NonZeroU32::new(4),
+        rows_per_image: std::num::NonZeroU32::new(1),
+      },
+      size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+    Self {
+      view,
+      sampler,
+      size,
+    }
+  }
+
+  /// Decodes a PNG, JPEG or any other format `image` is built with, see
+  /// `from_image`.
   pub fn from_bytes(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
@@ -59,20 +156,94 @@ impl Texture {
     );
 
     let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-      address_mode_u: wgpu::AddressMode::ClampToEdge,
-      address_mode_v: wgpu::AddressMode::ClampToEdge,
-      address_mode_w: wgpu::AddressMode::ClampToEdge,
-      mag_filter: wgpu::FilterMode::Linear,
-      min_filter: wgpu::FilterMode::Nearest,
-      mipmap_filter: wgpu::FilterMode::Nearest,
-      ..Default::default()
-    });
+    let sampler = image_sampler(device, 1);
 
     Ok(Self {
-      texture,
       view,
       sampler,
+      size,
     })
   }
+
+  /// Layout entries for the view, at `binding`, and the sampler after it,
+  /// both read by fragment shaders. Images are filtered, so the view's
+  /// float samples are too.
+  pub fn layout_entries(binding: u32) -> [wgpu::BindGroupLayoutEntry; 2] {
+    [
+      wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+          multisampled: false,
+          view_dimension: wgpu::TextureViewDimension::D2,
+          sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+      },
+      wgpu::BindGroupLayoutEntry {
+        binding: binding + 1,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+      },
+    ]
+  }
+
+  /// The entries binding the view and sampler where `layout_entries` put
+  /// them.
+  pub fn bind_group_entries(&self, binding: u32) -> [wgpu::BindGroupEntry<'_>; 2] {
+    [
+      wgpu::BindGroupEntry {
+        binding,
+        resource: wgpu::BindingResource::TextureView(&self.view),
+      },
+      wgpu::BindGroupEntry {
+        binding: binding + 1,
+        resource: wgpu::BindingResource::Sampler(&self.sampler),
+      },
+    ]
+  }
+
+  /// Samples an image texture with `anisotropy` taps along the direction it
+  /// stretches in, 1 for none. Bind groups using the old sampler keep it.
+  pub fn set_anisotropy(&mut self, device: &wgpu::Device, anisotropy: u8) {
+    self.sampler = image_sampler(device, anisotropy);
+  }
+}
+
+/// The most anisotropic taps wgpu takes.
+pub const MAX_ANISOTROPY: u8 = 16;
+
+/// How many anisotropic taps samplers on `adapter` can take, 1 where it has
+/// no anisotropic filtering. wgpu would otherwise quietly ignore the clamp.
+pub fn max_anisotropy(adapter: &wgpu::Adapter) -> u8 {
+  if adapter
+    .get_downlevel_properties()
+    .flags
+    .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING)
+  {
+    MAX_ANISOTROPY
+  } else {
+    1
+  }
+}
+
+fn image_sampler(device: &wgpu::Device, anisotropy: u8) -> wgpu::Sampler {
+  // Anisotropic filtering needs every filter linear.
+  let filter = if anisotropy > 1 {
+    wgpu::FilterMode::Linear
+  } else {
+    wgpu::FilterMode::Nearest
+  };
+  device.create_sampler(&wgpu::SamplerDescriptor {
+    address_mode_u: wgpu::AddressMode::ClampToEdge,
+    address_mode_v: wgpu::AddressMode::ClampToEdge,
+    address_mode_w: wgpu::AddressMode::ClampToEdge,
+    mag_filter: wgpu::FilterMode::Linear,
+    min_filter: filter,
+    mipmap_filter: filter,
+    anisotropy_clamp: std::num::NonZeroU8::new(anisotropy)
+      .filter(|&anisotropy| anisotropy.get() > 1),
+    ..Default::default()
+  })
 }
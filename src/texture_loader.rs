@@ -0,0 +1,127 @@
+use anyhow::Result;
+use std::sync::mpsc;
+
+type Loaded = (String, Result<image::DynamicImage>);
+
+/// Reads and decodes images off the render loop, on a thread natively and
+/// after a fetch on the web. Uploading them is left to the caller, on the
+/// thread that renders.
+pub struct TextureLoader {
+  pending: usize,
+  receiver: mpsc::Receiver<Loaded>,
+  sender: mpsc::Sender<Loaded>,
+}
+
+impl TextureLoader {
+  pub fn new() -> Self {
+    let (sender, receiver) = mpsc::channel();
+    Self {
+      pending: 0,
+      receiver,
+      sender,
+    }
+  }
+
+  /// Starts loading the image at `path`, a file natively or a URL relative
+  /// to the page on the web.
+  pub fn load(&mut self, path: &str) {
+    self.pending += 1;
+    let sender = self.sender.clone();
+    let path = path.to_owned();
+    // Sending fails only once the loader is gone, and then nobody wants the
+    // image any more.
+    cfg_if::cfg_if! {
+      if #[cfg(target_arch = "wasm32")] {
+        wasm_bindgen_futures::spawn_local(async move {
+          let image = fetch(&path).await.and_then(|bytes| decode(&bytes));
+          let _ = sender.send((path, image));
+        });
+      } else {
+        std::thread::spawn(move || {
+          let image = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| decode(&bytes));
+          let _ = sender.send((path, image));
+        });
+      }
+    }
+  }
+
+  /// Starts decoding an image the user dropped onto the page or picked.
+  #[cfg(target_arch = "wasm32")]
+  pub fn load_file(&mut self, file: web_sys::File) {
+    self.pending += 1;
+    let sender = self.sender.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+      let image = read_file(&file).await.and_then(|bytes| decode(&bytes));
+      let _ = sender.send((file.name(), image));
+    });
+  }
+
+  pub fn is_pending(&self) -> bool {
+    self.pending > 0
+  }
+
+  /// The images that finished loading since the last call, with their paths.
+  /// Failures are logged and left out.
+  pub fn finished(&mut self) -> Vec<(String, image::DynamicImage)> {
+    let loaded = self.receiver.try_iter().collect::<Vec<_>>();
+    self.collect(loaded)
+  }
+
+  /// Like `finished`, but blocks until everything that's pending is loaded.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn wait(&mut self) -> Vec<(String, image::DynamicImage)> {
+    // We hold a sender, so the channel can't disconnect.
+    let loaded = (0..self.pending)
+      .map(|_| self.receiver.recv().unwrap())
+      .collect();
+    self.collect(loaded)
+  }
+
+  fn collect(&mut self, loaded: Vec<Loaded>) -> Vec<(String, image::DynamicImage)> {
+    let mut images = Vec::new();
+    for (path, image) in loaded {
+      self.pending -= 1;
+      match image {
+        Ok(image) => images.push((path, image)),
+        Err(e) => log::error!("Failed to load texture {}: {:?}", path, e),
+      }
+    }
+    images
+  }
+}
+
+fn decode(bytes: &[u8]) -> Result<image::DynamicImage> {
+  Ok(image::load_from_memory(bytes)?)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch(path: &str) -> Result<Vec<u8>> {
+  use anyhow::{anyhow, bail, Context};
+  use wasm_bindgen::JsCast;
+  use wasm_bindgen_futures::JsFuture;
+
+  let js_error = |e| anyhow!("{:?}", e);
+  let window = web_sys::window().context("No window to fetch from")?;
+  let response: web_sys::Response = JsFuture::from(window.fetch_with_str(path))
+    .await
+    .map_err(js_error)?
+    .dyn_into()
+    .map_err(js_error)?;
+  if !response.ok() {
+    bail!("Fetch failed with status {}", response.status());
+  }
+  let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+    .await
+    .map_err(js_error)?;
+  Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn read_file(file: &web_sys::File) -> Result<Vec<u8>> {
+  let buffer = wasm_bindgen_futures::JsFuture::from(file.array_buffer())
+    .await
+    .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+  Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -0,0 +1,70 @@
+use anyhow::*;
+
+use crate::texture::Texture;
+
+/// Objects further from the camera than this, in world units, get the low
+/// quality texture.
+pub const LOD_DISTANCE: f32 = 8.0;
+
+/// How many times smaller the low quality texture is in each direction.
+const LOW_QUALITY_DIVISOR: u32 = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Quality {
+  High,
+  Low,
+}
+
+impl Quality {
+  pub fn for_distance(distance: f32) -> Self {
+    if distance > LOD_DISTANCE {
+      Self::Low
+    } else {
+      Self::High
+    }
+  }
+}
+
+/// A texture at full resolution and a reduced copy for far away objects.
+///
+/// Both stay on the GPU, so this costs about 1/16 more memory than the full
+/// texture alone and switching is free. What it saves is sampling
+/// bandwidth, far objects read from the small copy. Streaming would instead
+/// unload the full texture while nothing is near, saving memory but paying
+/// for a reload (and showing the small copy meanwhile) whenever something
+/// comes close again.
+pub struct LodTexture {
+  high: Texture,
+  low: Texture,
+}
+
+impl LodTexture {
+  pub fn from_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    img: &image::DynamicImage,
+    label: &str,
+  ) -> Result<Self> {
+    let high = Texture::from_image(device, queue, img, Some(label))?;
+    let low_image = img.resize(
+      (img.width() / LOW_QUALITY_DIVISOR).max(1),
+      (img.height() / LOW_QUALITY_DIVISOR).max(1),
+      image::imageops::FilterType::Triangle,
+    );
+    let low = Texture::from_image(device, queue, &low_image, Some(&format!("{} (low)", label)))?;
+    Ok(Self { high, low })
+  }
+
+  pub fn get(&self, quality: Quality) -> &Texture {
+    match quality {
+      Quality::High => &self.high,
+      Quality::Low => &self.low,
+    }
+  }
+
+  /// See `Texture::set_anisotropy`.
+  pub fn set_anisotropy(&mut self, device: &wgpu::Device, anisotropy: u8) {
+    self.high.set_anisotropy(device, anisotropy);
+    self.low.set_anisotropy(device, anisotropy);
+  }
+}
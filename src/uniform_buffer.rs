@@ -0,0 +1,168 @@
+use std::marker::PhantomData;
+
+/// `size` rounded up to the next multiple of `alignment`, which has to be a
+/// power of two like all of wgpu's alignment limits.
+pub fn align_to(size: u64, alignment: u64) -> u64 {
+  debug_assert!(alignment.is_power_of_two());
+  (size + alignment - 1) & !(alignment - 1)
+}
+
+/// `len` values of `T` in one uniform buffer, each at an offset the device
+/// accepts as a dynamic offset. Bind it once with
+/// `has_dynamic_offset: true` and pick the entry when drawing with
+/// `set_bind_group(group, &bind_group, &[buffer.offset(index)])`.
+// Nothing in the scene packs its uniforms this way yet.
+#[allow(dead_code)]
+pub struct DynamicUniformBuffer<T> {
+  buffer: wgpu::Buffer,
+  len: usize,
+  stride: u64,
+  _marker: PhantomData<T>,
+}
+
+#[allow(dead_code)]
+impl<T: bytemuck::Pod> DynamicUniformBuffer<T> {
+  pub fn new(device: &wgpu::Device, len: usize, label: &str) -> Self {
+    let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+    let stride = align_to(std::mem::size_of::<T>() as u64, alignment);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some(label),
+      size: stride * len.max(1) as u64,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+
+    Self {
+      buffer,
+      len,
+      stride,
+      _marker: PhantomData,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// The dynamic offset of entry `index`.
+  pub fn offset(&self, index: usize) -> u32 {
+    assert!(index < self.len, "Entry {} of {}", index, self.len);
+    (index as u64 * self.stride) as u32
+  }
+
+  pub fn write(&self, queue: &wgpu::Queue, index: usize, value: &T) {
+    queue.write_buffer(
+      &self.buffer,
+      self.offset(index) as u64,
+      bytemuck::bytes_of(value),
+    );
+  }
+
+  /// Binds one entry's worth of the buffer, which the dynamic offset then
+  /// moves along.
+  pub fn binding(&self) -> wgpu::BindingResource<'_> {
+    wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+      buffer: &self.buffer,
+      offset: 0,
+      size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::mem::size_of;
+
+  #[test]
+  fn aligns_to_the_device_limit() {
+    // 256 is the default `min_uniform_buffer_offset_alignment`, 64 what
+    // some desktop GPUs report.
+    for (size, alignment, aligned) in [
+      (0, 256, 0),
+      (1, 256, 256),
+      (80, 256, 256),
+      (256, 256, 256),
+      (257, 256, 512),
+      (48, 64, 64),
+      (80, 64, 128),
+      (128, 64, 128),
+    ] {
+      assert_eq!(
+        align_to(size, alignment),
+        aligned,
+        "{} to {}",
+        size,
+        alignment
+      );
+    }
+  }
+
+  /// The size WGSL lays the struct `name` in the shader `file` out with.
+  fn wgsl_size(file: &str, name: &str) -> u64 {
+    let source = crate::shader_include::load(file).unwrap();
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    let mut layouter = naga::proc::Layouter::default();
+    layouter.update(&module.types, &module.constants).unwrap();
+    let (handle, _) = module
+      .types
+      .iter()
+      .find(|(_, ty)| ty.name.as_deref() == Some(name))
+      .unwrap_or_else(|| panic!("No struct {} in {}", name, file));
+    layouter[handle].size as u64
+  }
+
+  #[test]
+  fn uniforms_are_padded_like_their_wgsl_structs() {
+    let uniforms = [
+      (
+        size_of::<crate::background::BackgroundUniform>(),
+        "background.wgsl",
+        "BackgroundUniform",
+      ),
+      (
+        size_of::<crate::camera::CameraUniform>(),
+        "common.wgsl",
+        "CameraUniform",
+      ),
+      (
+        size_of::<crate::shadow::LightUniform>(),
+        "common.wgsl",
+        "LightUniform",
+      ),
+      (
+        size_of::<crate::material::MaterialUniform>(),
+        "shader.wgsl",
+        "MaterialUniform",
+      ),
+      (
+        size_of::<crate::noise::NoiseParams>(),
+        "noise.wgsl",
+        "NoiseParams",
+      ),
+      (
+        size_of::<crate::outline::OutlineUniform>(),
+        "outline.wgsl",
+        "OutlineUniform",
+      ),
+      (
+        size_of::<crate::upscale::PresentUniform>(),
+        "upscale.wgsl",
+        "PresentUniform",
+      ),
+    ];
+    for (size, file, name) in uniforms {
+      let wgsl_size = wgsl_size(file, name);
+      // Uniform buffer structs are laid out at 16 byte strides, so the Rust
+      // side pads up to the next multiple of 16 even where WGSL stops short.
+      assert_eq!(
+        size as u64,
+        align_to(wgsl_size, 16),
+        "{} is {} bytes, WGSL's {}",
+        name,
+        size,
+        wgsl_size
+      );
+    }
+  }
+}
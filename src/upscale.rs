@@ -0,0 +1,360 @@
+use wgpu::util::DeviceExt;
+
+/// Lowest and highest supported `render_scale`.
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+pub const MAX_RENDER_SCALE: f32 = 1.0;
+
+/// The surface configuration shrunk by `render_scale`, which is what the scene
+/// targets are sized from. Only the size differs from `config`.
+pub fn scaled_config(
+  config: &wgpu::SurfaceConfiguration,
+  render_scale: f32,
+) -> wgpu::SurfaceConfiguration {
+  let render_scale = render_scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+  let scale = |size: u32| ((size as f32 * render_scale).round() as u32).max(1);
+  wgpu::SurfaceConfiguration {
+    width: scale(config.width),
+    height: scale(config.height),
+    ..config.clone()
+  }
+}
+
+/// How much the dynamic resolution changes the render scale at a time.
+const DYNAMIC_SCALE_STEP: f32 = 0.05;
+/// Frames to wait after a change before judging the new scale, long enough
+/// for the frame time average to settle.
+const DYNAMIC_COOLDOWN_FRAMES: u32 = 30;
+/// The average has to be this far over the target before the scale drops and
+/// this far under it before the scale recovers, so a frame time sitting right
+/// at the target (e.g. with vsync) doesn't flip the scale back and forth.
+const DYNAMIC_OVER_TARGET: f32 = 1.1;
+const DYNAMIC_UNDER_TARGET: f32 = 0.8;
+
+/// Picks a render scale that keeps the frame time around a target.
+pub struct DynamicResolution {
+  cooldown: u32,
+  target_frame_time: f32,
+}
+
+impl DynamicResolution {
+  pub fn new(target_frame_time: std::time::Duration) -> Self {
+    Self {
+      cooldown: DYNAMIC_COOLDOWN_FRAMES,
+      target_frame_time: target_frame_time.as_secs_f32(),
+    }
+  }
+
+  /// Call once per frame with the moving average of the frame time in
+  /// seconds. Returns the render scale to use from now on.
+  pub fn adjust(&mut self, average_frame_time: f32, render_scale: f32) -> f32 {
+    if self.cooldown > 0 {
+      self.cooldown -= 1;
+      return render_scale;
+    }
+
+    let new_scale = if average_frame_time > self.target_frame_time * DYNAMIC_OVER_TARGET {
+      render_scale - DYNAMIC_SCALE_STEP
+    } else if average_frame_time < self.target_frame_time * DYNAMIC_UNDER_TARGET {
+      render_scale + DYNAMIC_SCALE_STEP
+    } else {
+      render_scale
+    }
+    .clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+
+    if new_scale != render_scale {
+      self.cooldown = DYNAMIC_COOLDOWN_FRAMES;
+    }
+    new_scale
+  }
+}
+
+/// Range of the gamma the final pass applies on top of the surface's own
+/// encoding, 1.0 leaves the image alone.
+pub const MIN_GAMMA: f32 = 0.5;
+pub const MAX_GAMMA: f32 = 3.0;
+/// Range of the multiplier the final pass scales colors by before the gamma.
+/// There's no HDR target, so it scales colors already clamped to [0, 1].
+pub const MIN_EXPOSURE: f32 = 0.1;
+pub const MAX_EXPOSURE: f32 = 8.0;
+/// A tint that leaves the image alone, see `Upscaler::set_tint`.
+pub const NO_TINT: [f32; 4] = [0.0; 4];
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PresentUniform {
+  exposure: f32,
+  gamma: f32,
+  _padding: [f32; 2],
+  tint: [f32; 4],
+}
+
+/// Holds the offscreen texture the scene is resolved into and stretches it
+/// over the surface in a final pass.
+pub struct Upscaler {
+  bind_group: wgpu::BindGroup,
+  bind_group_layout: wgpu::BindGroupLayout,
+  pipeline: wgpu::RenderPipeline,
+  present: PresentUniform,
+  present_buffer: wgpu::Buffer,
+  sampler: wgpu::Sampler,
+  scene_texture: wgpu::Texture,
+  scene_view: wgpu::TextureView,
+}
+
+impl Upscaler {
+  /// `scene_config` is the `scaled_config` of the surface `config`.
+  pub fn new(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    scene_config: &wgpu::SurfaceConfiguration,
+  ) -> Self {
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+          },
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 1,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 2,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        },
+      ],
+      label: Some("upscale_bind_group_layout"),
+    });
+
+    let present = PresentUniform {
+      exposure: 1.0,
+      gamma: 1.0,
+      _padding: [0.0; 2],
+      tint: NO_TINT,
+    };
+    let present_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Present Buffer"),
+      contents: bytemuck::cast_slice(&[present]),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    // Bilinear filtering keeps a scaled down scene from looking blocky.
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      address_mode_u: wgpu::AddressMode::ClampToEdge,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Nearest,
+      ..Default::default()
+    });
+
+    let (scene_texture, scene_view) = Self::create_scene_texture(device, scene_config);
+    let bind_group = Self::create_bind_group(
+      device,
+      &bind_group_layout,
+      &scene_view,
+      &sampler,
+      &present_buffer,
+    );
+
+    let shader = device.create_shader_module(&wgpu::include_wgsl!("upscale.wgsl"));
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Upscale Pipeline Layout"),
+      bind_group_layouts: &[&bind_group_layout],
+      push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("Upscale Pipeline"),
+      layout: Some(&layout),
+      vertex: wgpu::VertexState {
+        module: &shader,
+        entry_point: "vs_main",
+        buffers: &[],
+      },
+      fragment: Some(wgpu::FragmentState {
+        module: &shader,
+        entry_point: "fs_present",
+        targets: &[wgpu::ColorTargetState {
+          format: config.format,
+          blend: Some(wgpu::BlendState::REPLACE),
+          write_mask: wgpu::ColorWrites::ALL,
+        }],
+      }),
+      primitive: wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: None,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        unclipped_depth: false,
+        conservative: false,
+      },
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState {
+        count: 1,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      multiview: None,
+    });
+
+    Self {
+      bind_group,
+      bind_group_layout,
+      pipeline,
+      present,
+      present_buffer,
+      sampler,
+      scene_texture,
+      scene_view,
+    }
+  }
+
+  fn create_scene_texture(
+    device: &wgpu::Device,
+    scene_config: &wgpu::SurfaceConfiguration,
+  ) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("scene_texture"),
+      size: wgpu::Extent3d {
+        width: scene_config.width,
+        height: scene_config.height,
+        depth_or_array_layers: 1,
+      },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: scene_config.format,
+      // Screenshots copy out of it when the scene isn't multisampled.
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+        | wgpu::TextureUsages::TEXTURE_BINDING
+        | wgpu::TextureUsages::COPY_SRC,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+  }
+
+  fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    scene_view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    present_buffer: &wgpu::Buffer,
+  ) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+      layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: 0,
+          resource: wgpu::BindingResource::TextureView(scene_view),
+        },
+        wgpu::BindGroupEntry {
+          binding: 1,
+          resource: wgpu::BindingResource::Sampler(sampler),
+        },
+        wgpu::BindGroupEntry {
+          binding: 2,
+          resource: present_buffer.as_entire_binding(),
+        },
+      ],
+      label: Some("upscale_bind_group"),
+    })
+  }
+
+  /// Recreates the scene texture, on resize or when the render scale changes.
+  pub fn resize(&mut self, device: &wgpu::Device, scene_config: &wgpu::SurfaceConfiguration) {
+    let (scene_texture, scene_view) = Self::create_scene_texture(device, scene_config);
+    self.scene_texture = scene_texture;
+    self.scene_view = scene_view;
+    self.bind_group = Self::create_bind_group(
+      device,
+      &self.bind_group_layout,
+      &self.scene_view,
+      &self.sampler,
+      &self.present_buffer,
+    );
+  }
+
+  pub fn gamma(&self) -> f32 {
+    self.present.gamma
+  }
+
+  /// Brightens (above 1.0) or darkens the midtones of what `draw` outputs,
+  /// clamped to `MIN_GAMMA`..`MAX_GAMMA`.
+  pub fn set_gamma(&mut self, queue: &wgpu::Queue, gamma: f32) {
+    self.present.gamma = gamma.clamp(MIN_GAMMA, MAX_GAMMA);
+    self.write_present(queue);
+  }
+
+  pub fn exposure(&self) -> f32 {
+    self.present.exposure
+  }
+
+  /// Multiplies what `draw` outputs by `exposure`, clamped to
+  /// `MIN_EXPOSURE`..`MAX_EXPOSURE`.
+  pub fn set_exposure(&mut self, queue: &wgpu::Queue, exposure: f32) {
+    self.present.exposure = exposure.clamp(MIN_EXPOSURE, MAX_EXPOSURE);
+    self.write_present(queue);
+  }
+
+  /// Mixes `tint`'s rgb into what `draw` outputs, by its alpha. Cleared
+  /// with `NO_TINT`.
+  pub fn set_tint(&mut self, queue: &wgpu::Queue, tint: [f32; 4]) {
+    self.present.tint = tint;
+    self.write_present(queue);
+  }
+
+  fn write_present(&self, queue: &wgpu::Queue) {
+    queue.write_buffer(
+      &self.present_buffer,
+      0,
+      bytemuck::cast_slice(&[self.present]),
+    );
+  }
+
+  /// The single-sample texture the scene pass resolves (or draws) into.
+  pub fn scene_texture(&self) -> &wgpu::Texture {
+    &self.scene_texture
+  }
+
+  pub fn scene_view(&self) -> &wgpu::TextureView {
+    &self.scene_view
+  }
+
+  /// Stretches the scene texture over `view`, applying the exposure and gamma.
+  pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Upscale Pass"),
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+          load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+          store: true,
+        },
+      }],
+      depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(&self.pipeline);
+    render_pass.set_bind_group(0, &self.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+  }
+}
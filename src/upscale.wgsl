@@ -0,0 +1,48 @@
+// Vertex shader
+struct VertexOutput {
+  [[builtin(position)]] clip_position: vec4<f32>;
+  [[location(0)]] tex_coords: vec2<f32>;
+};
+
+// A single triangle covering the screen, no vertex buffer needed.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+  var out: VertexOutput;
+  out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  out.tex_coords = uv;
+  return out;
+}
+
+
+// Fragment shader
+[[group(0), binding(0)]]
+var t_scene: texture_2d<f32>;
+
+[[group(0), binding(1)]]
+var s_scene: sampler;
+
+struct PresentUniform {
+  exposure: f32;
+  gamma: f32;
+  // rgb mixed in by a, over everything else.
+  tint: vec4<f32>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> present: PresentUniform;
+
+// A plain copy, which the noise preview also draws with.
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_scene, s_scene, in.tex_coords);
+}
+
+[[stage(fragment)]]
+fn fs_present(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(t_scene, s_scene, in.tex_coords);
+    let exposed = color.rgb * present.exposure;
+    let graded = pow(exposed, vec3<f32>(1.0 / present.gamma));
+    return vec4<f32>(mix(graded, present.tint.rgb, present.tint.a), color.a);
+}
@@ -0,0 +1,85 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+
+/// Frame rate the video is encoded at. Frames are taken as they are drawn,
+/// so it only plays back at the right speed if they are drawn this often.
+pub const VIDEO_FPS: u32 = 60;
+
+/// Whether an `ffmpeg` that runs is on the `PATH`.
+pub fn ffmpeg_available() -> bool {
+  Command::new("ffmpeg")
+    .arg("-version")
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .is_ok_and(|status| status.success())
+}
+
+/// Encodes frames into an MP4 by piping them to an `ffmpeg` process.
+pub struct VideoRecorder {
+  ffmpeg: Child,
+  frames: u32,
+  height: u32,
+  path: std::path::PathBuf,
+  width: u32,
+}
+
+impl VideoRecorder {
+  /// Starts `ffmpeg` writing a `width` by `height` video to `path`.
+  pub fn start(path: std::path::PathBuf, width: u32, height: u32) -> Result<Self> {
+    let ffmpeg = Command::new("ffmpeg")
+      .args(["-y", "-loglevel", "error"])
+      .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+      .args(["-video_size", &format!("{}x{}", width, height)])
+      .args(["-framerate", &VIDEO_FPS.to_string()])
+      .args(["-i", "-"])
+      // Most players only handle 4:2:0, which needs an even size.
+      .args([
+        "-vf",
+        "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+        "-pix_fmt",
+        "yuv420p",
+      ])
+      .arg(&path)
+      .stdin(Stdio::piped())
+      .spawn()
+      .context("Couldn't start ffmpeg")?;
+
+    Ok(Self {
+      ffmpeg,
+      frames: 0,
+      height,
+      path,
+      width,
+    })
+  }
+
+  /// Whether frames have to be `width` by `height`.
+  pub fn has_size(&self, width: u32, height: u32) -> bool {
+    (self.width, self.height) == (width, height)
+  }
+
+  /// Sends a frame of tightly packed RGBA rows, the size the recording was
+  /// started with.
+  pub fn push(&mut self, pixels: &[u8]) -> Result<()> {
+    let stdin = self.ffmpeg.stdin.as_mut().context("ffmpeg has no input")?;
+    stdin
+      .write_all(pixels)
+      .context("ffmpeg stopped taking frames")?;
+    self.frames += 1;
+    Ok(())
+  }
+
+  /// Waits for `ffmpeg` to write out the video. Returns where it went and
+  /// how many frames it has.
+  pub fn finish(mut self) -> Result<(std::path::PathBuf, u32)> {
+    // Closing its input tells ffmpeg there are no more frames.
+    drop(self.ffmpeg.stdin.take());
+    let status = self.ffmpeg.wait()?;
+    if !status.success() {
+      bail!("ffmpeg failed with {}", status);
+    }
+    Ok((self.path, self.frames))
+  }
+}
@@ -0,0 +1,241 @@
+use wasm_bindgen::{closure::Closure, JsCast};
+use winit::dpi::LogicalSize;
+use winit::event_loop::EventLoopProxy;
+
+use crate::gamepad::{self, GamepadInput};
+use crate::UserEvent;
+
+/// Id of the element the canvas is put in and sized to, unless the page says
+/// otherwise, see `container_id`.
+const DEFAULT_CONTAINER_ID: &str = "wasm-example";
+
+/// Attribute of `<body>` with the id of the container. `run` is the
+/// `wasm_bindgen(start)` function, which can't take arguments, so this is
+/// how a page passes one: `<body data-wgpu-container="my-canvas">`.
+const CONTAINER_ID_ATTRIBUTE: &str = "data-wgpu-container";
+
+/// Id of the file input that picks the diffuse texture, see
+/// `watch_texture_files`.
+const TEXTURE_INPUT_ID: &str = "wgpu-texture";
+
+fn document() -> Option<web_sys::Document> {
+  web_sys::window()?.document()
+}
+
+/// Id of the element to put the canvas in, from `CONTAINER_ID_ATTRIBUTE`.
+fn container_id() -> String {
+  document()
+    .and_then(|doc| doc.body())
+    .and_then(|body| body.get_attribute(CONTAINER_ID_ATTRIBUTE))
+    .unwrap_or_else(|| DEFAULT_CONTAINER_ID.to_string())
+}
+
+fn container() -> Option<web_sys::Element> {
+  document()?.get_element_by_id(&container_id())
+}
+
+/// Puts `canvas` into the container, or at the end of `<body>` if the page
+/// doesn't have one.
+pub fn attach_canvas(canvas: &web_sys::Element) {
+  let parent = match container() {
+    Some(container) => container,
+    None => {
+      log::warn!(
+        "There's no element with the id {:?}, adding the canvas to the body",
+        container_id()
+      );
+      document()
+        .and_then(|doc| doc.body())
+        .expect("The page has no body")
+        .into()
+    }
+  };
+  parent
+    .append_child(canvas)
+    .expect("Couldn't append canvas to document body.");
+}
+
+/// Size of the container in CSS pixels, which is what the canvas should
+/// take up. winit turns it into device pixels with `devicePixelRatio`, so
+/// the canvas is as sharp as the display. The container should get its size
+/// from the page, it would otherwise grow with the canvas and never shrink
+/// again. Without a container, or one without a height (or width), it's the
+/// browser window's.
+///
+/// When `devicePixelRatio` changes, say the page is zoomed or moved to
+/// another display, winit resizes the canvas for it and sends
+/// `ScaleFactorChanged`.
+pub fn canvas_size() -> LogicalSize<f64> {
+  let window = web_sys::window().expect("No browser window");
+  let (width, height) = container()
+    .map(|container| (container.client_width(), container.client_height()))
+    .unwrap_or((0, 0));
+  let window_length = |length: Result<wasm_bindgen::JsValue, _>| {
+    length
+      .ok()
+      .and_then(|length| length.as_f64())
+      .unwrap_or(1.0)
+  };
+  LogicalSize::new(
+    if width > 0 {
+      width as f64
+    } else {
+      window_length(window.inner_width())
+    },
+    if height > 0 {
+      height as f64
+    } else {
+      window_length(window.inner_height())
+    },
+  )
+}
+
+/// Sends `UserEvent::BrowserResized` whenever the browser window changes
+/// size, for as long as the page is open.
+pub fn watch_resize(proxy: EventLoopProxy<UserEvent>) {
+  let window = web_sys::window().expect("No browser window");
+  send_on(&window, "resize", proxy, UserEvent::BrowserResized);
+}
+
+/// Sends `UserEvent::PointerLockChanged` whenever the pointer is locked or
+/// unlocked, for as long as the page is open. Browsers unlock it themselves
+/// on Escape and when the page loses focus.
+pub fn watch_pointer_lock(proxy: EventLoopProxy<UserEvent>) {
+  let document = document().expect("No document");
+  send_on(
+    &document,
+    "pointerlockchange",
+    proxy,
+    UserEvent::PointerLockChanged,
+  );
+}
+
+/// Sends `UserEvent::GamepadsChanged` whenever a gamepad is connected or
+/// disconnected. Browsers hide gamepads until a button on one is pressed
+/// while the page has focus, that's when it counts as connected.
+pub fn watch_gamepads(proxy: EventLoopProxy<UserEvent>) {
+  let window = web_sys::window().expect("No browser window");
+  for event in ["gamepadconnected", "gamepaddisconnected"] {
+    send_on(&window, event, proxy.clone(), UserEvent::GamepadsChanged);
+  }
+}
+
+/// What the first connected gamepad does, read as the standard layout: the
+/// sticks are axes 0 to 3, y down, and the D-pad is buttons 12 to 15.
+pub fn gamepad_input() -> Option<GamepadInput> {
+  let gamepads = web_sys::window()?.navigator().get_gamepads().ok()?;
+  // Disconnected slots hold null.
+  let gamepad = gamepads.iter().find_map(|gamepad| {
+    gamepad
+      .dyn_into::<web_sys::Gamepad>()
+      .ok()
+      .filter(|gamepad| gamepad.connected())
+  })?;
+
+  let axes = gamepad.axes();
+  let axis = |index| axes.get(index).as_f64().unwrap_or(0.0) as f32;
+  let buttons = gamepad.buttons();
+  let pressed = |index| {
+    buttons
+      .get(index)
+      .dyn_into::<web_sys::GamepadButton>()
+      .is_ok_and(|button| button.pressed())
+  };
+  let (stick_x, stick_y) = gamepad::apply_deadzone((axis(0), -axis(1)));
+  let dpad_x = pressed(15) as i8 - pressed(14) as i8;
+  let dpad_y = pressed(12) as i8 - pressed(13) as i8;
+  Some(GamepadInput {
+    movement: (
+      (stick_x + dpad_x as f32).clamp(-1.0, 1.0),
+      (stick_y + dpad_y as f32).clamp(-1.0, 1.0),
+    ),
+    look: gamepad::apply_deadzone((axis(2), -axis(3))),
+  })
+}
+
+/// Whether the pointer is locked to the page.
+pub fn pointer_locked() -> bool {
+  document().is_some_and(|doc| doc.pointer_lock_element().is_some())
+}
+
+/// Sends `UserEvent::TextureFileChosen` for images dropped on `canvas` and
+/// for those picked with the page's `<input type="file">` with the id
+/// `TEXTURE_INPUT_ID`, if it has one.
+pub fn watch_texture_files(canvas: &web_sys::Element, proxy: EventLoopProxy<UserEvent>) {
+  // Browsers only allow dropping onto elements that cancel `dragover`.
+  listen(canvas, "dragover", |event| event.prevent_default());
+  let drop_proxy = proxy.clone();
+  listen(canvas, "drop", move |event| {
+    // Otherwise the browser opens the file instead.
+    event.prevent_default();
+    let file = event
+      .dyn_into::<web_sys::DragEvent>()
+      .ok()
+      .and_then(|event| event.data_transfer())
+      .and_then(|data| data.files())
+      .and_then(|files| files.get(0));
+    if let Some(file) = file {
+      let _ = drop_proxy.send_event(UserEvent::TextureFileChosen(file));
+    }
+  });
+
+  let input = document()
+    .and_then(|doc| doc.get_element_by_id(TEXTURE_INPUT_ID))
+    .and_then(|input| input.dyn_into::<web_sys::HtmlInputElement>().ok());
+  if let Some(input) = input {
+    let target = input.clone();
+    listen(&target, "change", move |_| {
+      if let Some(file) = input.files().and_then(|files| files.get(0)) {
+        let _ = proxy.send_event(UserEvent::TextureFileChosen(file));
+      }
+      // So picking the same file again is a change too.
+      input.set_value("");
+    });
+  }
+}
+
+/// Sends `UserEvent::ContextLost` when the browser takes the WebGL context
+/// of `canvas` away, say after a GPU reset or to free memory for another
+/// tab, and `UserEvent::ContextRestored` when it gives it back.
+pub fn watch_context(canvas: &web_sys::Element, proxy: EventLoopProxy<UserEvent>) {
+  let lost_proxy = proxy.clone();
+  listen(canvas, "webglcontextlost", move |event| {
+    // Browsers only restore contexts whose loss was cancelled.
+    event.prevent_default();
+    let _ = lost_proxy.send_event(UserEvent::ContextLost);
+  });
+  send_on(
+    canvas,
+    "webglcontextrestored",
+    proxy,
+    UserEvent::ContextRestored,
+  );
+}
+
+/// Sends `user_event` whenever `target` fires `event`.
+fn send_on(
+  target: &web_sys::EventTarget,
+  event: &str,
+  proxy: EventLoopProxy<UserEvent>,
+  user_event: UserEvent,
+) {
+  listen(target, event, move |_| {
+    // Fails only once the event loop is gone.
+    let _ = proxy.send_event(user_event.clone());
+  });
+}
+
+/// Calls `listener` whenever `target` fires `event`, for as long as the page
+/// is open.
+fn listen(
+  target: &web_sys::EventTarget,
+  event: &str,
+  listener: impl FnMut(web_sys::Event) + 'static,
+) {
+  let listener = Closure::wrap(Box::new(listener) as Box<dyn FnMut(web_sys::Event)>);
+  target
+    .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+    .unwrap_or_else(|e| panic!("Couldn't listen for {}: {:?}", event, e));
+  // The listener is never removed, so neither may the closure be.
+  listener.forget();
+}